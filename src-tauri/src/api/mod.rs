@@ -0,0 +1,235 @@
+//! 可选的本地 REST API：只监听 127.0.0.1，通过令牌鉴权，接口与 Tauri 命令一一对应
+//!
+//! 供笔记本、家庭看板等本机工具脚本化访问健康数据，OCR/AI 进度通过 SSE 推送。
+
+mod routes;
+
+use crate::error::{AppError, AppResult};
+use crate::repo::ConfigRepo;
+use axum::extract::{Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// 是否随程序启动 API 服务
+pub const ENABLED_KEY: &str = "api_server_enabled";
+/// 监听端口
+pub const PORT_KEY: &str = "api_server_port";
+/// 访问令牌，首次读取时生成
+pub const TOKEN_KEY: &str = "api_server_token";
+
+pub const DEFAULT_PORT: u16 = 17321;
+
+/// 停止服务时等待现有连接结束的最长时间，超时后强制关闭
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 浏览器 EventSource 无法设置请求头，仅此路径允许通过查询参数传递令牌
+const EVENTS_PATH: &str = "/api/events";
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ApiServerStatus {
+    pub enabled: bool,
+    pub running: bool,
+    /// 运行中时的监听地址，如 http://127.0.0.1:17321
+    pub address: Option<String>,
+    pub port: u16,
+    pub token: String,
+}
+
+/// 保存在 system_config 中的 API 服务设置
+pub struct ApiSettings {
+    pub enabled: bool,
+    pub port: u16,
+    pub token: String,
+}
+
+impl ApiSettings {
+    /// 读取设置，令牌为空时生成并保存
+    pub fn load(conn: &Connection) -> AppResult<Self> {
+        let config = ConfigRepo::new(conn);
+        let enabled = config.get_or(ENABLED_KEY, "false")? == "true";
+        let port = config
+            .get(PORT_KEY)?
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_PORT);
+        let token = match config.get(TOKEN_KEY)?.filter(|t| !t.is_empty()) {
+            Some(token) => token,
+            None => Self::reset_token(conn)?,
+        };
+        Ok(Self { enabled, port, token })
+    }
+
+    /// 生成新的访问令牌并保存
+    pub fn reset_token(conn: &Connection) -> AppResult<String> {
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        ConfigRepo::new(conn).set(TOKEN_KEY, &token)?;
+        Ok(token)
+    }
+}
+
+struct Running {
+    address: SocketAddr,
+    /// 丢弃后服务器与所有 SSE 连接随之关闭
+    shutdown: watch::Sender<()>,
+    /// 服务任务，结束时监听端口已释放
+    task: JoinHandle<()>,
+}
+
+/// API 服务的运行句柄，注入到 Tauri 状态中
+#[derive(Default)]
+pub struct ApiServer {
+    running: Mutex<Option<Running>>,
+    /// 串行执行启动与停止，避免重启时与另一次启动争用端口
+    lifecycle: tokio::sync::Mutex<()>,
+}
+
+impl ApiServer {
+    /// 在 127.0.0.1:<port> 上启动服务，已在运行时先停止并等待端口释放
+    pub async fn start(&self, app: AppHandle, port: u16, token: String) -> AppResult<SocketAddr> {
+        let _lifecycle = self.lifecycle.lock().await;
+        self.shutdown().await;
+
+        let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .await
+            .map_err(|e| AppError::io(&format!("监听端口 {} 失败", port), e))?;
+        let address = listener.local_addr().map_err(|e| AppError::io("读取监听地址失败", e))?;
+
+        let (shutdown, closed) = watch::channel(());
+        let router = routes::router(app, Arc::from(token), closed.clone());
+        let task = tokio::spawn(async move {
+            let mut closed = closed;
+            let result = axum::serve(listener, router)
+                .with_graceful_shutdown(async move { while closed.changed().await.is_ok() {} })
+                .await;
+            if let Err(e) = result {
+                log::error!("本地 API 服务异常退出: {}", e);
+            }
+        });
+
+        log::info!("本地 API 服务已启动: http://{}", address);
+        *self.lock() = Some(Running { address, shutdown, task });
+        Ok(address)
+    }
+
+    /// 停止服务并等待端口释放，返回停止前是否在运行
+    pub async fn stop(&self) -> bool {
+        let _lifecycle = self.lifecycle.lock().await;
+        self.shutdown().await
+    }
+
+    async fn shutdown(&self) -> bool {
+        let Some(Running { shutdown, mut task, .. }) = self.lock().take() else {
+            return false;
+        };
+        drop(shutdown);
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, &mut task).await.is_err() {
+            log::warn!("本地 API 服务未能在 {} 秒内关闭全部连接，强制停止", SHUTDOWN_TIMEOUT.as_secs());
+            task.abort();
+            task.await.ok();
+        }
+        log::info!("本地 API 服务已停止");
+        true
+    }
+
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.lock().as_ref().map(|r| r.address)
+    }
+
+    pub fn status(&self, settings: &ApiSettings) -> ApiServerStatus {
+        let address = self.address();
+        ApiServerStatus {
+            enabled: settings.enabled,
+            running: address.is_some(),
+            address: address.map(|a| format!("http://{}", a)),
+            port: settings.port,
+            token: settings.token.clone(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<Running>> {
+        self.running.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// 启动时按设置自动开启 API 服务（数据库锁定时跳过）
+pub fn start_if_enabled(app: &AppHandle) {
+    let settings = {
+        let db = app.state::<crate::db::Database>();
+        match db.write().and_then(|conn| ApiSettings::load(&conn)) {
+            Ok(settings) if settings.enabled => settings,
+            Ok(_) => return,
+            Err(e) => {
+                log::warn!("读取本地 API 设置失败: {}", e);
+                return;
+            }
+        }
+    };
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let server = app.state::<ApiServer>();
+        if let Err(e) = server.start(app.clone(), settings.port, settings.token).await {
+            log::warn!("启动本地 API 服务失败: {}", e);
+        }
+    });
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::Conflict { .. } => StatusCode::CONFLICT,
+            AppError::ConfigMissing { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::AiProvider { .. } => StatusCode::BAD_GATEWAY,
+            AppError::Locked(_) => StatusCode::LOCKED,
+            AppError::Busy(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Database(_) | AppError::Io(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(self)).into_response()
+    }
+}
+
+/// 校验 `Authorization: Bearer <令牌>`；仅 SSE 可使用 `?token=<令牌>`，避免令牌出现在其他请求的 URL 中
+async fn authorize(State(token): State<Arc<str>>, request: Request, next: Next) -> Response {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let query = request
+        .uri()
+        .query()
+        .filter(|_| request.uri().path() == EVENTS_PATH)
+        .and_then(|q| q.split('&').find_map(|pair| pair.strip_prefix("token=")));
+
+    if bearer.or(query).is_some_and(|given| token_matches(given, &token)) {
+        return next.run(request).await;
+    }
+
+    let body = serde_json::json!({
+        "code": "unauthorized",
+        "message": "缺少或无效的访问令牌",
+        "details": null,
+    });
+    (StatusCode::UNAUTHORIZED, Json(body)).into_response()
+}
+
+/// 逐字节比较全部内容，耗时不随首个不同字节的位置变化
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
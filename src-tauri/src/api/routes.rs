@@ -0,0 +1,299 @@
+//! 路由与处理函数，每个处理函数只做参数转换后调用对应的 Tauri 命令
+
+use super::{authorize, EVENTS_PATH};
+use crate::commands::{self, AppDir};
+use crate::db::Database;
+use crate::error::{AppError, AppResult};
+use crate::repo::FileRepo;
+use crate::repo::ai::AiAnalysis;
+use crate::repo::file::{CheckupFile, UploadFileInput};
+use crate::repo::job::{Job, JobFilter};
+use crate::repo::indicator::{CreateIndicatorInput, Indicator, UpdateIndicatorInput};
+use crate::repo::ocr::{OcrParsedItem, OcrResult};
+use crate::repo::patient::Patient;
+use crate::repo::project::{CreateProjectInput, Project, UpdateProjectInput};
+use crate::repo::record::{CheckupRecord, CreateRecordInput, UpdateRecordInput};
+use crate::services::events::EventHub;
+use crate::services::trend::ProjectTrend;
+use axum::extract::{Path, Query, State};
+use axum::http::header;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{middleware, Json, Router};
+use futures_util::Stream;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+use tokio::sync::{broadcast, watch};
+
+type ApiResult<T> = AppResult<Json<T>>;
+
+#[derive(Clone)]
+pub(super) struct ApiState {
+    app: AppHandle,
+    /// 服务停止时关闭，用于结束 SSE 连接
+    closed: watch::Receiver<()>,
+}
+
+pub(super) fn router(app: AppHandle, token: Arc<str>, closed: watch::Receiver<()>) -> Router {
+    Router::new()
+        .route("/api/patients", get(list_patients))
+        .route("/api/patients/active", get(get_active_patient).put(set_active_patient))
+        .route("/api/projects", get(list_projects).post(create_project))
+        .route("/api/projects/{id}", axum::routing::put(update_project).delete(delete_project))
+        .route("/api/projects/{id}/indicators", get(list_indicators))
+        .route("/api/projects/{id}/trends", get(get_project_trends))
+        .route("/api/indicators", axum::routing::post(create_indicator))
+        .route("/api/indicators/{id}", axum::routing::put(update_indicator).delete(delete_indicator))
+        .route("/api/records", get(list_records).post(create_record))
+        .route("/api/records/{id}", get(get_record).put(update_record).delete(delete_record))
+        .route("/api/records/{id}/files", get(list_files).post(upload_files))
+        .route("/api/records/{id}/ocr", get(get_ocr_results).post(start_ocr))
+        .route("/api/records/{id}/ocr/status", get(get_ocr_status))
+        .route("/api/records/{id}/ocr/cancel", axum::routing::post(cancel_ocr))
+        .route("/api/ocr/{id}/items", axum::routing::put(update_ocr_items))
+        .route("/api/records/{id}/ai", get(get_ai_analysis).post(start_ai_analysis))
+        .route("/api/analyses/{id}/cancel", axum::routing::post(cancel_ai_analysis))
+        .route("/api/files/{id}", get(read_file).delete(delete_file))
+        .route("/api/jobs", get(list_jobs))
+        .route("/api/jobs/{id}", get(get_job))
+        .route("/api/jobs/{id}/cancel", axum::routing::post(cancel_job))
+        .route("/api/trends", get(get_all_trends))
+        .route(EVENTS_PATH, get(events))
+        .route_layer(middleware::from_fn_with_state(token, authorize))
+        .with_state(ApiState { app, closed })
+}
+
+/// 将路径中的 id 写入请求体，复用带 id 字段的更新参数
+fn with_id<T: DeserializeOwned>(id: String, mut body: serde_json::Value) -> AppResult<T> {
+    if let Some(object) = body.as_object_mut() {
+        object.insert("id".into(), serde_json::Value::String(id));
+    }
+    serde_json::from_value(body).map_err(|e| AppError::Validation(format!("请求参数错误: {}", e)))
+}
+
+async fn list_patients(State(api): State<ApiState>) -> ApiResult<Vec<Patient>> {
+    commands::patient::list_patients(api.app.state()).map(Json)
+}
+
+async fn get_active_patient(State(api): State<ApiState>) -> ApiResult<Patient> {
+    commands::patient::get_active_patient(api.app.state()).map(Json)
+}
+
+#[derive(Deserialize)]
+struct ActivePatientBody {
+    id: String,
+}
+
+async fn set_active_patient(State(api): State<ApiState>, Json(body): Json<ActivePatientBody>) -> ApiResult<Patient> {
+    commands::patient::set_active_patient(body.id, api.app.state()).map(Json)
+}
+
+async fn list_projects(State(api): State<ApiState>) -> ApiResult<Vec<Project>> {
+    commands::project::list_projects(api.app.state()).map(Json)
+}
+
+async fn create_project(State(api): State<ApiState>, Json(input): Json<CreateProjectInput>) -> ApiResult<Project> {
+    commands::project::create_project(input, api.app.state(), api.app.state()).map(Json)
+}
+
+async fn update_project(
+    State(api): State<ApiState>,
+    Path(id): Path<String>,
+    Json(body): Json<serde_json::Value>,
+) -> ApiResult<Project> {
+    let input: UpdateProjectInput = with_id(id, body)?;
+    commands::project::update_project(input, api.app.state()).map(Json)
+}
+
+async fn delete_project(State(api): State<ApiState>, Path(id): Path<String>) -> ApiResult<bool> {
+    commands::project::delete_project(id, api.app.state()).map(Json)
+}
+
+async fn list_indicators(State(api): State<ApiState>, Path(project_id): Path<String>) -> ApiResult<Vec<Indicator>> {
+    commands::indicator::list_indicators(project_id, api.app.state()).map(Json)
+}
+
+async fn create_indicator(State(api): State<ApiState>, Json(input): Json<CreateIndicatorInput>) -> ApiResult<Indicator> {
+    commands::indicator::create_indicator(input, api.app.state()).map(Json)
+}
+
+async fn update_indicator(
+    State(api): State<ApiState>,
+    Path(id): Path<String>,
+    Json(body): Json<serde_json::Value>,
+) -> ApiResult<Indicator> {
+    let input: UpdateIndicatorInput = with_id(id, body)?;
+    commands::indicator::update_indicator(input, api.app.state()).map(Json)
+}
+
+async fn delete_indicator(State(api): State<ApiState>, Path(id): Path<String>) -> ApiResult<bool> {
+    commands::indicator::delete_indicator(id, api.app.state()).map(Json)
+}
+
+async fn list_records(State(api): State<ApiState>) -> ApiResult<Vec<CheckupRecord>> {
+    commands::record::list_records(api.app.state()).map(Json)
+}
+
+async fn create_record(State(api): State<ApiState>, Json(input): Json<CreateRecordInput>) -> ApiResult<CheckupRecord> {
+    commands::record::create_record(input, api.app.state()).map(Json)
+}
+
+async fn get_record(State(api): State<ApiState>, Path(id): Path<String>) -> ApiResult<CheckupRecord> {
+    commands::record::get_record(id, api.app.state()).map(Json)
+}
+
+async fn update_record(
+    State(api): State<ApiState>,
+    Path(id): Path<String>,
+    Json(body): Json<serde_json::Value>,
+) -> ApiResult<bool> {
+    let input: UpdateRecordInput = with_id(id, body)?;
+    commands::record::update_record(input, api.app.state()).map(Json)
+}
+
+async fn delete_record(State(api): State<ApiState>, Path(id): Path<String>) -> ApiResult<bool> {
+    commands::record::delete_record(id, api.app.state()).map(Json)
+}
+
+async fn list_files(State(api): State<ApiState>, Path(record_id): Path<String>) -> ApiResult<Vec<CheckupFile>> {
+    commands::file::list_files(record_id, api.app.state()).map(Json)
+}
+
+/// 请求体与 upload_files 命令相同，record_id 以路径为准
+async fn upload_files(
+    State(api): State<ApiState>,
+    Path(record_id): Path<String>,
+    Json(files): Json<Vec<serde_json::Value>>,
+) -> ApiResult<Vec<CheckupFile>> {
+    let files = files
+        .into_iter()
+        .map(|file| {
+            let mut file = file;
+            if let Some(object) = file.as_object_mut() {
+                object.insert("record_id".into(), serde_json::Value::String(record_id.clone()));
+            }
+            serde_json::from_value::<UploadFileInput>(file)
+                .map_err(|e| AppError::Validation(format!("请求参数错误: {}", e)))
+        })
+        .collect::<AppResult<Vec<_>>>()?;
+    commands::file::upload_files(files, api.app.state(), api.app.state()).map(Json)
+}
+
+/// 返回文件原始内容，Content-Type 为上传时识别的 MIME 类型
+async fn read_file(State(api): State<ApiState>, Path(id): Path<String>) -> AppResult<impl IntoResponse> {
+    let (stored_path, mime_type) = {
+        let db = api.app.state::<Database>();
+        let conn = db.read()?;
+        FileRepo::new(&conn).location(&id)?
+    };
+    let path = api.app.state::<AppDir>().0.join(stored_path);
+    let bytes = tokio::fs::read(&path).await.map_err(|e| AppError::io("读取文件失败", e))?;
+    Ok(([(header::CONTENT_TYPE, mime_type)], bytes))
+}
+
+async fn delete_file(State(api): State<ApiState>, Path(id): Path<String>) -> ApiResult<bool> {
+    commands::file::delete_file(id, api.app.state()).map(Json)
+}
+
+/// 识别任务入队，进度通过 /api/events 推送
+async fn start_ocr(State(api): State<ApiState>, Path(record_id): Path<String>) -> ApiResult<Job> {
+    commands::ocr::start_ocr(record_id, api.app.state(), api.app.state()).map(Json)
+}
+
+async fn cancel_ocr(State(api): State<ApiState>, Path(record_id): Path<String>) -> ApiResult<Job> {
+    commands::ocr::cancel_ocr(record_id, api.app.state(), api.app.state()).map(Json)
+}
+
+async fn get_ocr_status(State(api): State<ApiState>, Path(record_id): Path<String>) -> ApiResult<serde_json::Value> {
+    commands::ocr::get_ocr_status(record_id, api.app.state()).map(Json)
+}
+
+async fn get_ocr_results(State(api): State<ApiState>, Path(record_id): Path<String>) -> ApiResult<Vec<OcrResult>> {
+    commands::ocr::get_ocr_results(record_id, api.app.state()).map(Json)
+}
+
+/// 请求体为修正后的完整条目列表
+async fn update_ocr_items(
+    State(api): State<ApiState>,
+    Path(id): Path<String>,
+    Json(items): Json<Vec<OcrParsedItem>>,
+) -> ApiResult<OcrResult> {
+    commands::ocr::update_ocr_items(id, items, api.app.state()).map(Json)
+}
+
+/// 分析任务入队，内容通过 /api/events 流式推送
+async fn start_ai_analysis(State(api): State<ApiState>, Path(record_id): Path<String>) -> ApiResult<Job> {
+    commands::ai::start_ai_analysis(record_id, api.app.state(), api.app.state()).map(Json)
+}
+
+async fn cancel_ai_analysis(State(api): State<ApiState>, Path(analysis_id): Path<String>) -> ApiResult<Job> {
+    commands::ai::cancel_ai_analysis(analysis_id, api.app.state(), api.app.state()).map(Json)
+}
+
+async fn get_ai_analysis(State(api): State<ApiState>, Path(record_id): Path<String>) -> ApiResult<Vec<AiAnalysis>> {
+    commands::ai::get_ai_analysis(record_id, api.app.state()).map(Json)
+}
+
+async fn list_jobs(State(api): State<ApiState>, Query(filter): Query<JobFilter>) -> ApiResult<Vec<Job>> {
+    commands::job::list_jobs(Some(filter), api.app.state()).map(Json)
+}
+
+async fn get_job(State(api): State<ApiState>, Path(id): Path<String>) -> ApiResult<Job> {
+    commands::job::get_job(id, api.app.state()).map(Json)
+}
+
+async fn cancel_job(State(api): State<ApiState>, Path(id): Path<String>) -> ApiResult<Job> {
+    commands::job::cancel_job(id, api.app.state(), api.app.state()).map(Json)
+}
+
+async fn get_all_trends(State(api): State<ApiState>) -> ApiResult<Vec<ProjectTrend>> {
+    commands::trend::get_all_trends(api.app.state()).map(Json)
+}
+
+async fn get_project_trends(State(api): State<ApiState>, Path(project_id): Path<String>) -> ApiResult<ProjectTrend> {
+    commands::trend::get_project_trends(project_id, api.app.state()).map(Json)
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    /// 只推送该检查记录的事件
+    record_id: Option<String>,
+}
+
+/// OCR/AI 进度的 SSE 推送，事件名与前端收到的 Tauri 事件相同
+async fn events(
+    State(api): State<ApiState>,
+    Query(query): Query<EventsQuery>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = api.app.state::<EventHub>().subscribe();
+    let stream = futures_util::stream::unfold(
+        (receiver, api.closed, query.record_id),
+        |(mut receiver, mut closed, record_id)| async move {
+            loop {
+                let message = tokio::select! {
+                    message = receiver.recv() => message,
+                    _ = closed.changed() => return None,
+                };
+                let event = match message {
+                    Ok(event) => event,
+                    // 订阅者处理过慢，跳过被丢弃的事件
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                };
+                if record_id
+                    .as_deref()
+                    .is_some_and(|id| event.payload["record_id"].as_str() != Some(id))
+                {
+                    continue;
+                }
+                let sse = Event::default().event(&event.event).data(event.payload.to_string());
+                return Some((Ok(sse), (receiver, closed, record_id)));
+            }
+        },
+    );
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
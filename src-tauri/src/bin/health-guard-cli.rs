@@ -0,0 +1,3 @@
+fn main() {
+    std::process::exit(tauri_vue_app_lib::cli::run(std::env::args().skip(1)))
+}
@@ -0,0 +1,564 @@
+//! 无界面的命令行入口，与桌面端共用数据库、Repo 与识别/分析流程
+//!
+//! 用于批量导入历史纸质报告、定时导出趋势数据等脚本化场景。
+
+use crate::db::Database;
+use crate::error::{AppError, AppResult};
+use crate::repo::patient::Patient;
+use crate::repo::project::Project;
+use crate::repo::record::CreateRecordInput;
+use crate::repo::job::{Job, JobFilter, JobKind, JobStatus};
+use crate::repo::{FileRepo, JobRepo, PatientRepo, ProjectRepo, RecordRepo};
+use crate::services::events::EventSink;
+use crate::services::jobs::JobQueue;
+use crate::services::trend::ProjectTrend;
+use crate::services::{self, TrendService};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 提供数据库口令的环境变量（也可使用 `--passphrase`）
+pub const PASSPHRASE_ENV: &str = "HEALTH_GUARD_PASSPHRASE";
+
+const USAGE: &str = "用法: health-guard-cli [--data-dir <目录>] [--passphrase <口令>] <命令> [参数]
+
+命令:
+  patients                              列出成员
+  projects                              列出检查项目
+  records [--patient <成员>]            列出检查记录
+  import <目录> --project <项目> [--date <YYYY-MM-DD>] [--patient <成员>]
+                                        将目录中的报告图片导入到指定日期的检查记录
+  ocr <记录ID>                          识别检查记录下待识别的文件
+  analyze <记录ID>                      对检查记录进行 AI 分析，结果输出到标准输出
+  jobs [--record <记录ID>]              列出后台任务
+  trends [--project <项目>] [--patient <成员>] [--format json|csv] [--output <文件>]
+                                        输出或导出指标趋势
+
+成员、项目可使用 ID 或名称；未指定成员时使用当前成员。
+数据目录同样可通过 HEALTH_GUARD_DATA_DIR 指定，口令可通过 HEALTH_GUARD_PASSPHRASE 指定。";
+
+/// 不带参数值的开关选项
+const FLAGS: &[&str] = &["help"];
+
+/// 解析后的命令行参数：`--key value` / `--key=value` 形式的选项与 `--help` 等开关，其余为位置参数
+#[derive(Debug)]
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    fn parse(args: impl IntoIterator<Item = String>) -> AppResult<Self> {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                positional.push(arg);
+                continue;
+            };
+            let (key, value) = match name.split_once('=') {
+                Some((key, value)) => (key.to_string(), value.to_string()),
+                None if FLAGS.contains(&name) => (name.to_string(), String::new()),
+                None => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| AppError::Validation(format!("选项 --{} 缺少参数值", name)))?;
+                    (name.to_string(), value)
+                }
+            };
+            options.insert(key, value);
+        }
+        Ok(Self { positional, options })
+    }
+
+    fn option(&self, key: &str) -> Option<&str> {
+        self.options.get(key).map(String::as_str)
+    }
+
+    fn positional(&self, index: usize, name: &str) -> AppResult<&str> {
+        self.positional
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| AppError::Validation(format!("缺少参数 <{}>", name)))
+    }
+}
+
+/// 执行命令行，返回进程退出码
+pub fn run(args: impl IntoIterator<Item = String>) -> i32 {
+    let args = match Args::parse(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("错误: {}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    if args.options.contains_key("help") || args.positional.first().is_some_and(|c| c == "help") {
+        println!("{}", USAGE);
+        return 0;
+    }
+    let Some(command) = args.positional.first().cloned() else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+
+    match execute(&command, &args) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            1
+        }
+    }
+}
+
+fn execute(command: &str, args: &Args) -> AppResult<()> {
+    // 数据目录与桌面端使用相同的解析规则（含 --data-dir）
+    let app_dir = services::data_dir::resolve().path;
+    std::fs::create_dir_all(app_dir.join("pictures")).ok();
+    let db = Database::new(app_dir.clone())?;
+
+    let passphrase = args
+        .option("passphrase")
+        .map(str::to_string)
+        .or_else(|| std::env::var(PASSPHRASE_ENV).ok().filter(|v| !v.is_empty()));
+    if let Some(passphrase) = passphrase {
+        db.unlock(&passphrase)?;
+    }
+
+    match command {
+        "patients" => list_patients(&db),
+        "projects" => list_projects(&db),
+        "records" => list_records(&db, args),
+        "import" => import(&db, &app_dir, args),
+        "ocr" => ocr(&db, &app_dir, args.positional(1, "记录ID")?),
+        "analyze" => analyze(&db, &app_dir, args.positional(1, "记录ID")?),
+        "jobs" => list_jobs(&db, args),
+        "trends" => trends(&db, args),
+        other => Err(AppError::Validation(format!("未知命令: {}\n\n{}", other, USAGE))),
+    }
+}
+
+fn list_patients(db: &Database) -> AppResult<()> {
+    let conn = db.read()?;
+    let repo = PatientRepo::new(&conn);
+    let active = repo.active_id()?;
+    for patient in repo.list()? {
+        let marker = if patient.id == active { "*" } else { " " };
+        println!("{} {}\t{}", marker, patient.id, patient.name);
+    }
+    Ok(())
+}
+
+fn list_projects(db: &Database) -> AppResult<()> {
+    let conn = db.read()?;
+    for project in ProjectRepo::new(&conn).list()? {
+        println!("{}\t{}", project.id, project.name);
+    }
+    Ok(())
+}
+
+fn list_records(db: &Database, args: &Args) -> AppResult<()> {
+    let conn = db.read()?;
+    let patient = find_patient(&conn, args.option("patient"))?;
+    for record in RecordRepo::new(&conn).list(&patient.id)? {
+        println!(
+            "{}\t{}\t{}\t{} 个文件",
+            record.id,
+            record.checkup_date,
+            record.status,
+            record.file_count.unwrap_or(0)
+        );
+    }
+    Ok(())
+}
+
+/// 将目录下的报告图片（按文件名排序）导入到成员在该日期的检查记录，记录不存在时创建
+///
+/// 已导入过的文件（同名且大小相同）会跳过，重复执行不会产生重复文件。
+fn import(db: &Database, app_dir: &Path, args: &Args) -> AppResult<()> {
+    let dir = PathBuf::from(args.positional(1, "目录")?);
+    let project_arg = args
+        .option("project")
+        .ok_or_else(|| AppError::Validation("请使用 --project 指定检查项目".into()))?;
+    let checkup_date = match args.option("date") {
+        Some(date) => chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| AppError::Validation(format!("日期格式应为 YYYY-MM-DD: {}", date)))?
+            .format("%Y-%m-%d")
+            .to_string(),
+        None => chrono::Local::now().format("%Y-%m-%d").to_string(),
+    };
+
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .map_err(|e| AppError::io(&format!("读取目录失败 {}", dir.display()), e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && is_report_file(path))
+        .collect();
+    if paths.is_empty() {
+        return Err(AppError::Validation(format!("目录中没有可导入的报告文件: {}", dir.display())));
+    }
+    paths.sort();
+
+    let conn = db.write()?;
+    let patient = find_patient(&conn, args.option("patient"))?;
+    let project = find_project(&conn, project_arg)?;
+    let (record_id, imported) = import_files(&conn, app_dir, &patient.id, &project.id, &checkup_date, &paths)?;
+
+    for filename in &imported {
+        eprintln!("已导入 {}", filename);
+    }
+    let skipped = paths.len() - imported.len();
+    if skipped > 0 {
+        eprintln!("跳过 {} 个已导入的文件", skipped);
+    }
+    eprintln!("共导入 {} 个文件到 {} 的检查记录（{}）", imported.len(), patient.name, checkup_date);
+    // 标准输出只输出记录 ID，便于脚本串联 ocr / analyze
+    println!("{}", record_id);
+    Ok(())
+}
+
+/// 在同一事务中创建检查记录并登记文件，任一文件失败时整体回滚并删除已写入的物理文件；
+/// 返回记录 ID 与实际导入的文件名
+fn import_files(
+    conn: &rusqlite::Connection,
+    app_dir: &Path,
+    patient_id: &str,
+    project_id: &str,
+    checkup_date: &str,
+    paths: &[PathBuf],
+) -> AppResult<(String, Vec<String>)> {
+    let tx = crate::repo::transaction(conn)?;
+    let mut stored = Vec::new();
+    let result = (|| {
+        let records = RecordRepo::new(conn);
+        let record_id = match records.find_by_date(patient_id, checkup_date)? {
+            Some(id) => id,
+            None => records
+                .create(patient_id, CreateRecordInput {
+                    checkup_date: checkup_date.to_string(),
+                    notes: None,
+                })?
+                .id,
+        };
+
+        let files = FileRepo::new(conn);
+        let mut imported = Vec::new();
+        for path in paths {
+            let filename = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let bytes = std::fs::read(path).map_err(|e| AppError::io(&format!("读取文件失败 {}", path.display()), e))?;
+            if files.contains(&record_id, project_id, &filename, bytes.len() as i64)? {
+                continue;
+            }
+            let file = services::storage::store_file(conn, app_dir, &record_id, project_id, checkup_date, &filename, &bytes)?;
+            stored.push(app_dir.join(file.stored_path));
+            imported.push(filename);
+        }
+        Ok((record_id, imported))
+    })();
+
+    match result.and_then(|imported| tx.commit().map(|()| imported)) {
+        Ok(imported) => Ok(imported),
+        Err(e) => {
+            for path in &stored {
+                std::fs::remove_file(path).ok();
+            }
+            Err(e)
+        }
+    }
+}
+
+fn list_jobs(db: &Database, args: &Args) -> AppResult<()> {
+    let conn = db.read()?;
+    let filter = JobFilter {
+        record_id: args.option("record").map(str::to_string),
+        ..Default::default()
+    };
+    for job in JobRepo::new(&conn).list(None, &filter)? {
+        println!(
+            "{}\t{}\t{}\t{}\t{}/{}\t{}",
+            job.id,
+            job.kind.as_str(),
+            job.record_id,
+            job.status.as_str(),
+            job.progress_done,
+            job.progress_total,
+            job.last_error.unwrap_or_default().replace('\n', "; ")
+        );
+    }
+    Ok(())
+}
+
+/// 与桌面端相同，以后台任务的形式记录执行过程，但在当前进程内直接执行；Ctrl+C 取消任务
+fn run_job(db: &Database, app_dir: &Path, kind: JobKind, record_id: &str) -> AppResult<Job> {
+    let job = services::jobs::enqueue(db, kind, record_id)?;
+    let queue = JobQueue::default();
+    let job = block_on(async {
+        let run = services::jobs::run_job(db, app_dir, &ConsoleSink, &queue, &job.id);
+        tokio::pin!(run);
+        tokio::select! {
+            finished = &mut run => finished,
+            _ = tokio::signal::ctrl_c() => {
+                eprintln!("正在取消...");
+                queue.cancel_running(&job.id);
+                run.await
+            }
+        }
+    })??;
+    match job.status {
+        JobStatus::Failed => Err(AppError::Internal(format!("任务失败: {}", job.last_error.unwrap_or_default()))),
+        JobStatus::Cancelled => Err(AppError::Internal("任务已取消".into())),
+        _ => Ok(job),
+    }
+}
+
+fn ocr(db: &Database, app_dir: &Path, record_id: &str) -> AppResult<()> {
+    let job = run_job(db, app_dir, JobKind::Ocr, record_id)?;
+    if let Some(errors) = &job.last_error {
+        eprintln!("部分文件识别失败:\n{}", errors);
+    }
+    eprintln!("识别完成: {}/{}", job.progress_done, job.progress_total);
+    Ok(())
+}
+
+fn analyze(db: &Database, app_dir: &Path, record_id: &str) -> AppResult<()> {
+    // 分析内容通过 ai_stream_chunk 事件实时输出
+    run_job(db, app_dir, JobKind::AiAnalysis, record_id)?;
+    println!();
+    Ok(())
+}
+
+fn trends(db: &Database, args: &Args) -> AppResult<()> {
+    let conn = db.read()?;
+    let patient = find_patient(&conn, args.option("patient"))?;
+    let service = TrendService::new(&conn);
+    let trends = match args.option("project") {
+        Some(project) => vec![service.project_trend(&patient.id, &find_project(&conn, project)?.id)?],
+        None => service.all_trends(&patient.id)?,
+    };
+
+    let output = match args.option("format").unwrap_or("json") {
+        "json" => serde_json::to_string_pretty(&trends)
+            .map_err(|e| AppError::Internal(format!("序列化趋势数据失败: {}", e)))?,
+        "csv" => trends_csv(&trends),
+        other => return Err(AppError::Validation(format!("不支持的导出格式: {}（可选 json、csv）", other))),
+    };
+
+    match args.option("output") {
+        Some(path) => {
+            std::fs::write(path, output).map_err(|e| AppError::io(&format!("写入文件失败 {}", path), e))?;
+            eprintln!("已导出到 {}", path);
+        }
+        None => println!("{}", output),
+    }
+    Ok(())
+}
+
+/// 每个数据点一行
+fn trends_csv(trends: &[ProjectTrend]) -> String {
+    let mut csv = String::from("project,indicator,unit,reference_range,checkup_date,value,is_abnormal\n");
+    for project in trends {
+        for indicator in &project.indicators {
+            for point in &indicator.data_points {
+                let fields = [
+                    project.project_name.as_str(),
+                    indicator.indicator_name.as_str(),
+                    indicator.unit.as_str(),
+                    indicator.reference_range.as_str(),
+                    point.checkup_date.as_str(),
+                    point.value_text.as_str(),
+                    if point.is_abnormal { "true" } else { "false" },
+                ];
+                let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+                csv.push_str(&line.join(","));
+                csv.push('\n');
+            }
+        }
+    }
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn is_report_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| services::storage::REPORT_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// 按 ID 或名称查找成员，未指定时使用当前成员
+fn find_patient(conn: &rusqlite::Connection, patient: Option<&str>) -> AppResult<Patient> {
+    let repo = PatientRepo::new(conn);
+    let Some(patient) = patient else {
+        return repo.active();
+    };
+    repo.list()?
+        .into_iter()
+        .find(|p| p.id == patient || p.name == patient)
+        .ok_or_else(|| AppError::NotFound(format!("成员不存在: {}", patient)))
+}
+
+/// 按 ID 或名称查找检查项目
+fn find_project(conn: &rusqlite::Connection, project: &str) -> AppResult<Project> {
+    ProjectRepo::new(conn)
+        .list()?
+        .into_iter()
+        .find(|p| p.id == project || p.name == project)
+        .ok_or_else(|| AppError::NotFound(format!("检查项目不存在: {}", project)))
+}
+
+fn block_on<F: std::future::Future>(future: F) -> AppResult<F::Output> {
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| AppError::Internal(format!("创建异步运行时失败: {}", e)))?;
+    Ok(runtime.block_on(future))
+}
+
+fn retry_message(retry: &serde_json::Value) -> String {
+    format!(
+        "请求失败（{}），{:.1} 秒后进行第 {}/{} 次尝试",
+        retry["reason"].as_str().unwrap_or(""),
+        retry["delay_ms"].as_u64().unwrap_or(0) as f64 / 1000.0,
+        retry["attempt"].as_u64().unwrap_or(0),
+        retry["max_attempts"].as_u64().unwrap_or(0)
+    )
+}
+
+/// 将识别进度输出到标准错误，AI 分析内容输出到标准输出
+struct ConsoleSink;
+
+impl EventSink for ConsoleSink {
+    fn emit(&self, event: &str, payload: serde_json::Value) {
+        match event {
+            "ocr_progress" => {
+                let completed = payload["completed"].as_u64().unwrap_or(0);
+                let total = payload["total"].as_u64().unwrap_or(0);
+                let mut file = payload["current_file"].as_str().unwrap_or("").to_string();
+                if let (Some(page), Some(count)) = (payload["page"].as_u64(), payload["page_count"].as_u64()) {
+                    file.push_str(&format!("（第 {}/{} 页）", page, count));
+                }
+                let status = match payload["status"].as_str().unwrap_or("") {
+                    "processing" => "开始识别",
+                    "retrying" => {
+                        eprintln!("{} {}", file, retry_message(&payload["retry"]));
+                        return;
+                    }
+                    "success" => "识别完成",
+                    "parse_failed" => "解析失败",
+                    _ => "识别失败",
+                };
+                eprintln!("[{}/{}] {} {}", completed, total, status, file);
+            }
+            "ai_stream_retry" => eprintln!("{}", retry_message(&payload["retry"])),
+            "ocr_error" | "ai_stream_error" => {
+                eprintln!("{}", payload["error"].as_str().unwrap_or(""));
+            }
+            "ai_stream_chunk" => {
+                let mut stdout = std::io::stdout();
+                stdout.write_all(payload["content"].as_str().unwrap_or("").as_bytes()).ok();
+                stdout.flush().ok();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::project::CreateProjectInput;
+    use crate::services::trend::{IndicatorTrend, TrendDataPoint};
+    use rusqlite::Connection;
+
+    fn parse(args: &[&str]) -> AppResult<Args> {
+        Args::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn options_flags_and_positionals_are_parsed() {
+        let args = parse(&["import", "/tmp/reports", "--project", "血常规", "--date=2024-01-01"]).unwrap();
+        assert_eq!(args.positional, ["import", "/tmp/reports"]);
+        assert_eq!(args.option("project"), Some("血常规"));
+        assert_eq!(args.option("date"), Some("2024-01-01"));
+        assert_eq!(args.positional(1, "目录").unwrap(), "/tmp/reports");
+        assert_eq!(args.positional(2, "记录ID").unwrap_err().code(), "validation");
+
+        // --help 是开关，不会吞掉后面的参数
+        let args = parse(&["--help"]).unwrap();
+        assert!(args.options.contains_key("help"));
+        let args = parse(&["--help", "trends"]).unwrap();
+        assert!(args.options.contains_key("help"));
+        assert_eq!(args.positional, ["trends"]);
+
+        assert_eq!(parse(&["records", "--patient"]).unwrap_err().code(), "validation");
+    }
+
+    #[test]
+    fn trends_csv_escapes_fields() {
+        assert_eq!(csv_field("5.2"), "5.2");
+        assert_eq!(csv_field("3.5-5.5"), "3.5-5.5");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("他说\"阳性\""), "\"他说\"\"阳性\"\"\"");
+        assert_eq!(csv_field("第一行\n第二行"), "\"第一行\n第二行\"");
+
+        let trends = [ProjectTrend {
+            project_id: "p".into(),
+            project_name: "血常规".into(),
+            indicators: vec![IndicatorTrend {
+                indicator_id: "i".into(),
+                indicator_name: "白细胞, 计数".into(),
+                unit: "10^9/L".into(),
+                reference_range: "3.5-9.5".into(),
+                data_points: vec![TrendDataPoint {
+                    checkup_date: "2024-01-01".into(),
+                    value: Some(10.2),
+                    value_text: "10.2".into(),
+                    is_abnormal: true,
+                }],
+            }],
+        }];
+        assert_eq!(
+            trends_csv(&trends),
+            "project,indicator,unit,reference_range,checkup_date,value,is_abnormal\n\
+             血常规,\"白细胞, 计数\",10^9/L,3.5-9.5,2024-01-01,10.2,true\n"
+        );
+    }
+
+    #[test]
+    fn import_is_idempotent() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run(&mut conn, Path::new("unused")).unwrap();
+        let dir = std::env::temp_dir().join(format!("health-import-{}", uuid::Uuid::new_v4()));
+        let source = dir.join("reports");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::write(source.join("1.jpg"), b"one").unwrap();
+        std::fs::write(source.join("2.jpg"), b"two").unwrap();
+        let paths = [source.join("1.jpg"), source.join("2.jpg")];
+
+        let patient = PatientRepo::new(&conn).active_id().unwrap();
+        let project = ProjectRepo::new(&conn)
+            .create(CreateProjectInput { name: "血常规".into(), description: None, ocr_engine: None })
+            .unwrap();
+        let (record_id, imported) = import_files(&conn, &dir, &patient, &project.id, "2024-01-01", &paths).unwrap();
+        assert_eq!(imported, ["1.jpg", "2.jpg"]);
+
+        // 重复导入跳过已有文件，只登记新增的文件
+        std::fs::write(source.join("3.jpg"), b"three").unwrap();
+        let paths = [source.join("1.jpg"), source.join("2.jpg"), source.join("3.jpg")];
+        let (again, imported) = import_files(&conn, &dir, &patient, &project.id, "2024-01-01", &paths).unwrap();
+        assert_eq!(again, record_id);
+        assert_eq!(imported, ["3.jpg"]);
+        assert_eq!(FileRepo::new(&conn).list(&record_id).unwrap().len(), 3);
+
+        // 中途失败时整体回滚，已写入的物理文件也被删除
+        let paths = [source.join("4.jpg"), source.join("missing.jpg")];
+        std::fs::write(&paths[0], b"four").unwrap();
+        assert!(import_files(&conn, &dir, &patient, &project.id, "2024-02-01", &paths).is_err());
+        assert_eq!(RecordRepo::new(&conn).find_by_date(&patient, "2024-02-01").unwrap(), None);
+        assert!(!dir.join("pictures").join("血常规").join("2024-02-01").read_dir().unwrap().any(|_| true));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
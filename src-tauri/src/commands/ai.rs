@@ -1,335 +1,46 @@
-use serde::{Deserialize, Serialize};
-use tauri::Manager;
 use crate::db::Database;
-use crate::services::http_client;
+use crate::error::{AppError, AppResult};
+use crate::repo::job::{Job, JobKind};
+use crate::repo::{AiRepo, JobRepo, RecordRepo};
+use crate::services::jobs::{self, JobQueue};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct AiAnalysis {
-    pub id: String,
-    pub record_id: String,
-    pub request_prompt: String,
-    pub response_content: String,
-    pub model_used: String,
-    pub status: String,
-    pub error_message: String,
-    pub created_at: String,
-}
+pub use crate::repo::ai::AiAnalysis;
 
-/// 发起 AI 分析（流式返回）
+/// 发起 AI 分析（加入后台任务队列，内容通过 ai_stream_chunk 事件流式返回）
 #[tauri::command]
-pub async fn start_ai_analysis(
+pub fn start_ai_analysis(
     record_id: String,
-    app: tauri::AppHandle,
-    db: tauri::State<'_, Database>,
-) -> Result<String, String> {
-    use tauri::Emitter;
-    use futures_util::StreamExt;
-
-    // 1. 收集数据：当前 OCR 结果 + 历史数据
-    let (config, model, ai_prompt, prompt_data, analysis_id) = {
-        let conn = db.conn.lock().map_err(|e| e.to_string())?;
-
-        // 获取 AI 配置
-        let config = http_client::load_ai_config(&conn)?;
-        let model = http_client::get_default_model(&conn);
-
-        // 获取 AI 分析 Prompt 模板
-        let ai_prompt: String = conn
-            .query_row(
-                "SELECT config_value FROM system_config WHERE config_key = 'ai_analysis_prompt_template'",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap_or_else(|_| {
-                "请根据以下检查数据，综合分析患者的健康状况，指出异常指标，提供治疗建议和生活方式改善方案。请以中文回复，使用Markdown格式。".to_string()
-            });
-
-        // 获取当前检查记录的 OCR 数据
-        let checkup_date: String = conn
-            .query_row(
-                "SELECT checkup_date FROM checkup_records WHERE id = ?1",
-                [&record_id],
-                |row| row.get(0),
-            )
-            .map_err(|e| format!("记录不存在: {}", e))?;
-
-        // 收集当前记录的 OCR 解析结果
-        let mut stmt = conn
-            .prepare(
-                "SELECT o.parsed_items, p.name as project_name, o.checkup_date
-                 FROM ocr_results o
-                 LEFT JOIN checkup_projects p ON o.project_id = p.id
-                 WHERE o.record_id = ?1 AND o.status = 'success'
-                 ORDER BY p.name ASC"
-            )
-            .map_err(|e| format!("查询OCR结果失败: {}", e))?;
-
-        let current_data: Vec<(String, String, String)> = stmt
-            .query_map([&record_id], |row| {
-                Ok((
-                    row.get::<_, String>(0)?,
-                    row.get::<_, String>(1).unwrap_or_default(),
-                    row.get::<_, String>(2)?,
-                ))
-            })
-            .map_err(|e| format!("查询失败: {}", e))?
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| format!("解析数据失败: {}", e))?;
-
-        if current_data.is_empty() {
-            return Err("当前检查记录没有成功的 OCR 结果，请先进行 OCR 识别".into());
-        }
-
-        // 收集历史检查数据（最近3次）
-        let mut hist_stmt = conn
-            .prepare(
-                "SELECT o.parsed_items, p.name, r.checkup_date
-                 FROM ocr_results o
-                 JOIN checkup_records r ON o.record_id = r.id
-                 LEFT JOIN checkup_projects p ON o.project_id = p.id
-                 WHERE o.record_id != ?1 AND o.status = 'success'
-                 ORDER BY r.checkup_date DESC"
-            )
-            .map_err(|e| format!("查询历史数据失败: {}", e))?;
-
-        let history_data: Vec<(String, String, String)> = hist_stmt
-            .query_map([&record_id], |row| {
-                Ok((
-                    row.get::<_, String>(0)?,
-                    row.get::<_, String>(1).unwrap_or_default(),
-                    row.get::<_, String>(2)?,
-                ))
-            })
-            .map_err(|e| format!("查询失败: {}", e))?
-            .collect::<Result<Vec<_>, _>>()
-            .unwrap_or_default();
-
-        // 组装完整的 Prompt 数据
-        let mut prompt_parts = Vec::new();
-        prompt_parts.push(format!("## 本次检查（日期: {}）\n", checkup_date));
-
-        for (parsed_items, project_name, _date) in &current_data {
-            prompt_parts.push(format!("### {}\n", project_name));
-            prompt_parts.push(format!("{}\n", parsed_items));
-        }
-
-        if !history_data.is_empty() {
-            prompt_parts.push("\n## 历史检查数据\n".to_string());
-            let mut prev_date = String::new();
-            for (parsed_items, project_name, date) in &history_data {
-                if date != &prev_date {
-                    prompt_parts.push(format!("\n### 检查日期: {}\n", date));
-                    prev_date = date.clone();
-                }
-                prompt_parts.push(format!("#### {}\n", project_name));
-                prompt_parts.push(format!("{}\n", parsed_items));
-            }
-        }
-
-        let prompt_data = prompt_parts.join("");
-
-        // 预创建分析记录
-        let analysis_id = uuid::Uuid::new_v4().to_string();
-        let now = chrono::Local::now().to_rfc3339();
-        let full_prompt = format!("{}\n\n{}", ai_prompt, prompt_data);
-
-        conn.execute(
-            "INSERT INTO ai_analyses (id, record_id, request_prompt, response_content, model_used, status, error_message, created_at)
-             VALUES (?1, ?2, ?3, '', ?4, 'processing', '', ?5)",
-            rusqlite::params![analysis_id, record_id, full_prompt, model, now],
-        ).map_err(|e| format!("创建分析记录失败: {}", e))?;
-
-        // 更新检查记录状态
-        conn.execute(
-            "UPDATE checkup_records SET status = 'ai_processing', updated_at = ?1 WHERE id = ?2",
-            rusqlite::params![now, record_id],
-        ).ok();
+    db: tauri::State<Database>,
+    queue: tauri::State<JobQueue>,
+) -> AppResult<Job> {
+    RecordRepo::new(&*db.read()?).ensure_active(&record_id)?;
+    let job = jobs::enqueue(&db, JobKind::AiAnalysis, &record_id)?;
+    queue.wake();
+    Ok(job)
+}
 
-        (config, model, ai_prompt, prompt_data, analysis_id)
+/// 取消进行中的 AI 分析，已生成的内容保留在状态为 cancelled 的分析记录中
+#[tauri::command]
+pub fn cancel_ai_analysis(
+    analysis_id: String,
+    db: tauri::State<Database>,
+    queue: tauri::State<JobQueue>,
+) -> AppResult<Job> {
+    let job = {
+        let conn = db.read()?;
+        let job = JobRepo::new(&conn)
+            .active_for_result(&analysis_id)?
+            .ok_or_else(|| AppError::NotFound("该分析没有进行中的任务".into()))?;
+        RecordRepo::new(&conn).ensure_active(&job.record_id)?;
+        job
     };
-
-    let record_id_clone = record_id.clone();
-    let analysis_id_clone = analysis_id.clone();
-
-    // 2. 异步发送流式请求
-    tokio::spawn(async move {
-        let client = match http_client::build_client(&config) {
-            Ok(c) => c,
-            Err(e) => {
-                log::error!("AI 创建客户端失败: {}", e);
-                update_ai_error(&app, &analysis_id_clone, &record_id_clone, &e);
-                return;
-            }
-        };
-
-        let full_prompt = format!("{}\n\n{}", ai_prompt, prompt_data);
-
-        let request_body = serde_json::json!({
-            "model": model,
-            "messages": [
-                {
-                    "role": "system",
-                    "content": "你是一位专业的医疗健康分析助手。请根据用户提供的检查报告数据，给出全面、专业的健康分析和建议。"
-                },
-                {
-                    "role": "user",
-                    "content": full_prompt
-                }
-            ],
-            "stream": true,
-            "max_tokens": 8192,
-        });
-
-        let response = match client
-            .post(&config.api_url)
-            .header("Authorization", format!("Bearer {}", config.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
-            .await
-        {
-            Ok(r) => r,
-            Err(e) => {
-                let err_msg = format!("AI 请求失败: {}", e);
-                update_ai_error(&app, &analysis_id_clone, &record_id_clone, &err_msg);
-                return;
-            }
-        };
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            let err_msg = format!("AI API 错误 ({}): {}", status, body);
-            update_ai_error(&app, &analysis_id_clone, &record_id_clone, &err_msg);
-            return;
-        }
-
-        // 流式读取 SSE 响应
-        let mut full_content = String::new();
-        let mut stream = response.bytes_stream();
-
-        let mut buffer = String::new();
-
-        while let Some(chunk_result) = stream.next().await {
-            let chunk = match chunk_result {
-                Ok(c) => c,
-                Err(e) => {
-                    log::error!("流式读取错误: {}", e);
-                    break;
-                }
-            };
-
-            let chunk_str = String::from_utf8_lossy(&chunk);
-            buffer.push_str(&chunk_str);
-
-            // 处理 SSE 数据行
-            while let Some(line_end) = buffer.find('\n') {
-                let line = buffer[..line_end].trim().to_string();
-                buffer = buffer[line_end + 1..].to_string();
-
-                if line.is_empty() || line == "data: [DONE]" {
-                    continue;
-                }
-
-                if let Some(json_str) = line.strip_prefix("data: ") {
-                    if let Ok(data) = serde_json::from_str::<serde_json::Value>(json_str) {
-                        if let Some(content) = data["choices"][0]["delta"]["content"].as_str() {
-                            full_content.push_str(content);
-                            // 发送流式 chunk 事件
-                            app.emit("ai_stream_chunk", serde_json::json!({
-                                "record_id": record_id_clone,
-                                "analysis_id": analysis_id_clone,
-                                "content": content,
-                            })).ok();
-                        }
-                    }
-                }
-            }
-        }
-
-        // 保存完成的分析结果
-        if let Some(db_state) = app.try_state::<Database>() {
-            if let Ok(conn) = db_state.conn.lock() {
-                let now = chrono::Local::now().to_rfc3339();
-
-                let _: Result<usize, _> = conn.execute(
-                    "UPDATE ai_analyses SET response_content = ?1, status = 'success', error_message = '' WHERE id = ?2",
-                    rusqlite::params![full_content, analysis_id_clone],
-                );
-
-                let _: Result<usize, _> = conn.execute(
-                    "UPDATE checkup_records SET status = 'ai_done', updated_at = ?1 WHERE id = ?2",
-                    rusqlite::params![now, record_id_clone],
-                );
-            }
-        }
-
-        // 发送完成事件
-        app.emit("ai_stream_done", serde_json::json!({
-            "record_id": record_id_clone,
-            "analysis_id": analysis_id_clone,
-        })).ok();
-    });
-
-    Ok(analysis_id)
+    jobs::cancel(&db, &queue, &job)
 }
 
 /// 获取 AI 分析结果
 #[tauri::command]
-pub fn get_ai_analysis(record_id: String, db: tauri::State<Database>) -> Result<Vec<AiAnalysis>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, record_id, request_prompt, response_content, model_used, status, error_message, created_at
-             FROM ai_analyses WHERE record_id = ?1
-             ORDER BY created_at DESC"
-        )
-        .map_err(|e| format!("查询AI分析结果失败: {}", e))?;
-
-    let results = stmt
-        .query_map([&record_id], |row| {
-            Ok(AiAnalysis {
-                id: row.get(0)?,
-                record_id: row.get(1)?,
-                request_prompt: row.get(2)?,
-                response_content: row.get(3)?,
-                model_used: row.get(4)?,
-                status: row.get(5)?,
-                error_message: row.get(6)?,
-                created_at: row.get(7)?,
-            })
-        })
-        .map_err(|e| format!("查询失败: {}", e))?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("解析数据失败: {}", e))?;
-
-    Ok(results)
-}
-
-/// 更新 AI 分析错误状态
-fn update_ai_error(app: &tauri::AppHandle, analysis_id: &str, record_id: &str, error: &str) {
-    use tauri::Emitter;
-
-    if let Some(db_state) = app.try_state::<Database>() {
-        if let Ok(conn) = db_state.conn.lock() {
-            let now = chrono::Local::now().to_rfc3339();
-
-            let _: Result<usize, _> = conn.execute(
-                "UPDATE ai_analyses SET status = 'failed', error_message = ?1 WHERE id = ?2",
-                rusqlite::params![error, analysis_id],
-            );
-
-            let _: Result<usize, _> = conn.execute(
-                "UPDATE checkup_records SET status = 'ocr_done', updated_at = ?1 WHERE id = ?2",
-                rusqlite::params![now, record_id],
-            );
-        }
-    }
-
-    app.emit("ai_stream_error", serde_json::json!({
-        "record_id": record_id,
-        "analysis_id": analysis_id,
-        "error": error,
-    })).ok();
+pub fn get_ai_analysis(record_id: String, db: tauri::State<Database>) -> AppResult<Vec<AiAnalysis>> {
+    let conn = db.read()?;
+    RecordRepo::new(&conn).ensure_active(&record_id)?;
+    AiRepo::new(&conn).list(&record_id)
 }
@@ -0,0 +1,46 @@
+use tauri::State;
+use crate::db::Database;
+use crate::error::AppResult;
+use crate::repo::AiProfileRepo;
+
+pub use crate::repo::ai_profile::{AiProfile, AiTask, AiTaskProfiles, CreateAiProfileInput, UpdateAiProfileInput};
+
+#[tauri::command]
+pub fn list_ai_profiles(db: State<Database>) -> AppResult<Vec<AiProfile>> {
+    let conn = db.read()?;
+    AiProfileRepo::new(&conn).list()
+}
+
+#[tauri::command]
+pub fn create_ai_profile(input: CreateAiProfileInput, db: State<Database>) -> AppResult<AiProfile> {
+    let conn = db.write()?;
+    AiProfileRepo::new(&conn).create(input)
+}
+
+#[tauri::command]
+pub fn update_ai_profile(input: UpdateAiProfileInput, db: State<Database>) -> AppResult<AiProfile> {
+    let conn = db.write()?;
+    AiProfileRepo::new(&conn).update(input)
+}
+
+#[tauri::command]
+pub fn delete_ai_profile(id: String, db: State<Database>) -> AppResult<bool> {
+    let conn = db.write()?;
+    AiProfileRepo::new(&conn).delete(&id)?;
+    Ok(true)
+}
+
+/// OCR 识别与 AI 分析各自使用的配置
+#[tauri::command]
+pub fn get_ai_task_profiles(db: State<Database>) -> AppResult<AiTaskProfiles> {
+    let conn = db.read()?;
+    AiProfileRepo::new(&conn).task_profiles()
+}
+
+#[tauri::command]
+pub fn set_ai_task_profile(task: AiTask, profile_id: String, db: State<Database>) -> AppResult<AiTaskProfiles> {
+    let conn = db.write()?;
+    let repo = AiProfileRepo::new(&conn);
+    repo.set_task_profile(task, &profile_id)?;
+    repo.task_profiles()
+}
@@ -0,0 +1,65 @@
+use tauri::State;
+use crate::api::{ApiServer, ApiServerStatus, ApiSettings, ENABLED_KEY, PORT_KEY};
+use crate::db::Database;
+use crate::error::AppResult;
+use crate::repo::ConfigRepo;
+
+/// 查询本地 API 服务的设置与运行状态
+#[tauri::command]
+pub fn get_api_server_status(db: State<Database>, server: State<ApiServer>) -> AppResult<ApiServerStatus> {
+    let conn = db.write()?;
+    Ok(server.status(&ApiSettings::load(&conn)?))
+}
+
+/// 启动本地 API 服务，并设为随程序启动
+#[tauri::command]
+pub async fn start_api_server(
+    port: Option<u16>,
+    app: tauri::AppHandle,
+    db: State<'_, Database>,
+    server: State<'_, ApiServer>,
+) -> AppResult<ApiServerStatus> {
+    let settings = {
+        let conn = db.write()?;
+        let config = ConfigRepo::new(&conn);
+        if let Some(port) = port {
+            config.set(PORT_KEY, &port.to_string())?;
+        }
+        config.set(ENABLED_KEY, "true")?;
+        ApiSettings::load(&conn)?
+    };
+
+    server.start(app, settings.port, settings.token.clone()).await?;
+    Ok(server.status(&settings))
+}
+
+/// 停止本地 API 服务，并取消随程序启动
+#[tauri::command]
+pub async fn stop_api_server(db: State<'_, Database>, server: State<'_, ApiServer>) -> AppResult<ApiServerStatus> {
+    let settings = {
+        let conn = db.write()?;
+        ConfigRepo::new(&conn).set(ENABLED_KEY, "false")?;
+        ApiSettings::load(&conn)?
+    };
+    server.stop().await;
+    Ok(server.status(&settings))
+}
+
+/// 重新生成访问令牌，服务运行中时以新令牌重启
+#[tauri::command]
+pub async fn reset_api_token(
+    app: tauri::AppHandle,
+    db: State<'_, Database>,
+    server: State<'_, ApiServer>,
+) -> AppResult<ApiServerStatus> {
+    let settings = {
+        let conn = db.write()?;
+        ApiSettings::reset_token(&conn)?;
+        ApiSettings::load(&conn)?
+    };
+
+    if server.address().is_some() {
+        server.start(app, settings.port, settings.token.clone()).await?;
+    }
+    Ok(server.status(&settings))
+}
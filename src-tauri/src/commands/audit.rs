@@ -0,0 +1,20 @@
+use tauri::State;
+use crate::db::Database;
+use crate::error::AppResult;
+use crate::repo::AuditRepo;
+
+pub use crate::repo::audit::{AuditEntry, AuditFilter};
+
+/// 查询审计日志（默认最近 100 条）
+#[tauri::command]
+pub fn list_audit_log(filter: Option<AuditFilter>, db: State<Database>) -> AppResult<Vec<AuditEntry>> {
+    let conn = db.read()?;
+    AuditRepo::new(&conn).list(&filter.unwrap_or_default())
+}
+
+/// 撤销单条变更，返回撤销产生的新审计记录
+#[tauri::command]
+pub fn undo_change(audit_id: String, db: State<Database>) -> AppResult<AuditEntry> {
+    let conn = db.write()?;
+    AuditRepo::new(&conn).undo(&audit_id)
+}
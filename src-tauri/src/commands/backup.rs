@@ -0,0 +1,55 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use tauri::State;
+use crate::db::{Database, DatabaseStatus};
+use crate::error::AppResult;
+use crate::services::backup::{self, BackupManifest};
+use super::AppDir;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CreatedBackup {
+    pub path: String,
+    pub manifest: BackupManifest,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RestoredBackup {
+    pub manifest: BackupManifest,
+    /// 恢复后的数据库状态（备份为加密且口令不同时需重新解锁）
+    pub database: DatabaseStatus,
+}
+
+/// 创建完整备份（数据库 + 图片），未指定路径时保存到 backups/ 目录
+#[tauri::command]
+pub async fn create_backup(
+    dest_path: Option<String>,
+    db: State<'_, Database>,
+    app_dir: State<'_, AppDir>,
+) -> AppResult<CreatedBackup> {
+    let dest = match dest_path.filter(|p| !p.is_empty()) {
+        Some(p) => PathBuf::from(p),
+        None => app_dir.0.join("backups").join(format!(
+            "health_guard_backup_{}.zip",
+            chrono::Local::now().format("%Y%m%d%H%M%S")
+        )),
+    };
+
+    let manifest = backup::create_backup(&db, &app_dir.0, &dest)?;
+    log::info!("备份完成: {}", dest.display());
+
+    Ok(CreatedBackup {
+        path: dest.to_string_lossy().to_string(),
+        manifest,
+    })
+}
+
+/// 从备份归档恢复，校验清单通过后替换当前数据
+#[tauri::command]
+pub async fn restore_backup(
+    archive_path: String,
+    db: State<'_, Database>,
+    app_dir: State<'_, AppDir>,
+) -> AppResult<RestoredBackup> {
+    let (manifest, database) = backup::restore_backup(&db, &app_dir.0, &PathBuf::from(archive_path))?;
+    Ok(RestoredBackup { manifest, database })
+}
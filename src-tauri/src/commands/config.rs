@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use tauri::State;
 use crate::db::Database;
+use crate::error::AppResult;
+use crate::repo::ConfigRepo;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ConfigValue {
@@ -9,35 +11,14 @@ pub struct ConfigValue {
 }
 
 #[tauri::command]
-pub fn get_config(key: String, db: State<Database>) -> Result<String, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    let result = conn.query_row(
-        "SELECT config_value FROM system_config WHERE config_key = ?1",
-        [&key],
-        |row| row.get::<_, String>(0),
-    );
-    match result {
-        Ok(value) => Ok(value),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(String::new()),
-        Err(e) => Err(format!("读取配置失败: {}", e)),
-    }
+pub fn get_config(key: String, db: State<Database>) -> AppResult<String> {
+    let conn = db.read()?;
+    Ok(ConfigRepo::new(&conn).get(&key)?.unwrap_or_default())
 }
 
 #[tauri::command]
-pub fn save_config(key: String, value: String, db: State<Database>) -> Result<bool, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    let now = chrono::Local::now().to_rfc3339();
-    let id = uuid::Uuid::new_v4().to_string();
-
-    conn.execute(
-        "INSERT INTO system_config (id, config_key, config_value, updated_at)
-         VALUES (?1, ?2, ?3, ?4)
-         ON CONFLICT(config_key) DO UPDATE SET
-            config_value = excluded.config_value,
-            updated_at = excluded.updated_at",
-        rusqlite::params![id, key, value, now],
-    )
-    .map_err(|e| format!("保存配置失败: {}", e))?;
-
+pub fn save_config(key: String, value: String, db: State<Database>) -> AppResult<bool> {
+    let conn = db.write()?;
+    ConfigRepo::new(&conn).set(&key, &value)?;
     Ok(true)
 }
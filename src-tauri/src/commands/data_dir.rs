@@ -0,0 +1,33 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use tauri::State;
+use crate::db::Database;
+use crate::error::AppResult;
+use crate::services::data_dir::{self, DataDirInfo};
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RelocatedDataDir {
+    pub data_dir: String,
+    /// 新目录在重启后生效
+    pub restart_required: bool,
+}
+
+/// 查询当前数据目录及其来源
+#[tauri::command]
+pub fn get_data_dir(info: State<DataDirInfo>) -> DataDirInfo {
+    info.inner().clone()
+}
+
+/// 将数据库、图片与备份迁移到新目录；完成后数据库保持关闭，前端需调用 restart_app 重启生效
+#[tauri::command]
+pub async fn relocate_data_dir(
+    new_dir: String,
+    db: State<'_, Database>,
+    info: State<'_, DataDirInfo>,
+) -> AppResult<RelocatedDataDir> {
+    let target = data_dir::relocate(&db, &info, &PathBuf::from(new_dir))?;
+    Ok(RelocatedDataDir {
+        data_dir: target.to_string_lossy().to_string(),
+        restart_required: true,
+    })
+}
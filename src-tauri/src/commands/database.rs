@@ -0,0 +1,37 @@
+use tauri::State;
+use crate::db::{Database, DatabaseStatus};
+use crate::error::AppResult;
+use crate::services::jobs::JobQueue;
+
+/// 查询数据库加密/锁定状态（锁定时前端需先弹出解锁框）
+#[tauri::command]
+pub fn get_database_status(db: State<Database>) -> AppResult<DatabaseStatus> {
+    db.status()
+}
+
+/// 使用口令解锁数据库
+#[tauri::command]
+pub fn unlock_database(passphrase: String, db: State<Database>, queue: State<JobQueue>) -> AppResult<bool> {
+    db.unlock(&passphrase)?;
+    // 锁定期间无法恢复和执行后台任务
+    queue.wake();
+    Ok(true)
+}
+
+/// 加密当前明文数据库
+#[tauri::command]
+pub fn encrypt_database(passphrase: String, db: State<Database>) -> AppResult<bool> {
+    db.encrypt(&passphrase)?;
+    Ok(true)
+}
+
+/// 修改数据库口令（新口令为空时解除加密）
+#[tauri::command]
+pub fn change_database_passphrase(
+    old_passphrase: String,
+    new_passphrase: String,
+    db: State<Database>,
+) -> AppResult<bool> {
+    db.change_passphrase(&old_passphrase, &new_passphrase)?;
+    Ok(true)
+}
@@ -0,0 +1,26 @@
+use tauri::State;
+use crate::db::Database;
+use crate::error::AppResult;
+use crate::services::integrity::{self, IntegrityReport, RepairOptions, RepairResult};
+use super::AppDir;
+
+/// 检查数据库与图片目录的一致性
+#[tauri::command]
+pub async fn check_integrity(db: State<'_, Database>, app_dir: State<'_, AppDir>) -> AppResult<IntegrityReport> {
+    integrity::check(&db, &app_dir.0)
+}
+
+/// 按类别修复一致性问题，返回修复数量和修复后的检查结果
+#[tauri::command]
+pub async fn repair_integrity(
+    options: RepairOptions,
+    db: State<'_, Database>,
+    app_dir: State<'_, AppDir>,
+) -> AppResult<RepairResult> {
+    let result = integrity::repair(&db, &app_dir.0, &options)?;
+    log::info!(
+        "一致性修复完成: 缺失文件 {}，孤立文件 {}，空目录 {}，悬空引用 {}",
+        result.missing_files, result.orphaned_files, result.empty_dirs, result.dangling_references
+    );
+    Ok(result)
+}
@@ -0,0 +1,35 @@
+use tauri::State;
+use crate::db::Database;
+use crate::error::AppResult;
+use crate::repo::{JobRepo, PatientRepo, RecordRepo};
+use crate::services::jobs::{self, JobQueue};
+
+pub use crate::repo::job::{Job, JobFilter};
+
+/// 查询当前成员的后台任务（最新在前），可按记录、类型、状态筛选
+#[tauri::command]
+pub fn list_jobs(filter: Option<JobFilter>, db: State<Database>) -> AppResult<Vec<Job>> {
+    let conn = db.read()?;
+    let patient_id = PatientRepo::new(&conn).active_id()?;
+    JobRepo::new(&conn).list(Some(&patient_id), &filter.unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn get_job(id: String, db: State<Database>) -> AppResult<Job> {
+    let conn = db.read()?;
+    let job = JobRepo::new(&conn).get(&id)?;
+    RecordRepo::new(&conn).ensure_active(&job.record_id)?;
+    Ok(job)
+}
+
+/// 按任务取消：排队中或尚未收到输出的 AI 分析还没有分析记录，只能通过任务 id 取消
+#[tauri::command]
+pub fn cancel_job(id: String, db: State<Database>, queue: State<JobQueue>) -> AppResult<Job> {
+    let job = {
+        let conn = db.read()?;
+        let job = JobRepo::new(&conn).get(&id)?;
+        RecordRepo::new(&conn).ensure_active(&job.record_id)?;
+        job
+    };
+    jobs::cancel(&db, &queue, &job)
+}
@@ -1,4 +1,5 @@
 pub mod config;
+pub mod database;
 pub mod project;
 pub mod indicator;
 pub mod record;
@@ -6,8 +7,29 @@ pub mod file;
 pub mod ocr;
 pub mod ai;
 pub mod trend;
+pub mod backup;
+pub mod data_dir;
+pub mod audit;
+pub mod trash;
+pub mod integrity;
+pub mod patient;
+pub mod api_server;
+pub mod job;
+pub mod ai_profile;
 
 use std::path::PathBuf;
+use tauri::Manager;
+use crate::services::events::{EventHub, EventSink};
 
 /// 应用数据目录，注入到 Tauri 状态中
 pub struct AppDir(pub PathBuf);
+
+/// 后台任务的事件转发给前端，同时广播给本地 API 的 SSE 订阅者
+impl EventSink for tauri::AppHandle {
+    fn emit(&self, event: &str, payload: serde_json::Value) {
+        if let Some(hub) = self.try_state::<EventHub>() {
+            hub.emit(event, payload.clone());
+        }
+        tauri::Emitter::emit(self, event, payload).ok();
+    }
+}
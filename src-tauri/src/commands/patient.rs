@@ -0,0 +1,45 @@
+use tauri::State;
+use crate::db::Database;
+use crate::error::AppResult;
+use crate::repo::PatientRepo;
+
+pub use crate::repo::patient::{CreatePatientInput, Patient, UpdatePatientInput};
+
+#[tauri::command]
+pub fn list_patients(db: State<Database>) -> AppResult<Vec<Patient>> {
+    let conn = db.read()?;
+    PatientRepo::new(&conn).list()
+}
+
+#[tauri::command]
+pub fn create_patient(input: CreatePatientInput, db: State<Database>) -> AppResult<Patient> {
+    let conn = db.write()?;
+    PatientRepo::new(&conn).create(input)
+}
+
+#[tauri::command]
+pub fn update_patient(input: UpdatePatientInput, db: State<Database>) -> AppResult<Patient> {
+    let conn = db.write()?;
+    PatientRepo::new(&conn).update(input)
+}
+
+#[tauri::command]
+pub fn delete_patient(id: String, db: State<Database>) -> AppResult<bool> {
+    let conn = db.write()?;
+    PatientRepo::new(&conn).delete(&id)?;
+    Ok(true)
+}
+
+/// 当前成员，检查记录、趋势与 AI 分析均限定在当前成员范围内
+#[tauri::command]
+pub fn get_active_patient(db: State<Database>) -> AppResult<Patient> {
+    let conn = db.read()?;
+    PatientRepo::new(&conn).active()
+}
+
+/// 切换当前成员
+#[tauri::command]
+pub fn set_active_patient(id: String, db: State<Database>) -> AppResult<Patient> {
+    let conn = db.write()?;
+    PatientRepo::new(&conn).set_active(&id)
+}
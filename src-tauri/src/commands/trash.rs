@@ -0,0 +1,50 @@
+use tauri::State;
+use crate::db::Database;
+use crate::error::{AppError, AppResult};
+use crate::repo::{PatientRepo, TrashRepo};
+use crate::services::trash;
+use super::AppDir;
+
+pub use crate::repo::trash::{TrashItem, TrashKind};
+
+/// 查询当前成员的回收站内容
+#[tauri::command]
+pub fn list_trash(db: State<Database>) -> AppResult<Vec<TrashItem>> {
+    let conn = db.read()?;
+    let patient_id = PatientRepo::new(&conn).active_id()?;
+    TrashRepo::new(&conn).list(&patient_id)
+}
+
+/// 从回收站恢复
+#[tauri::command]
+pub fn restore_from_trash(kind: TrashKind, id: String, db: State<Database>) -> AppResult<bool> {
+    let conn = db.write()?;
+    let repo = TrashRepo::new(&conn);
+    repo.ensure_active(kind, &id)?;
+    repo.restore(kind, &id)?;
+    Ok(true)
+}
+
+/// 彻底删除回收站中的数据；未指定 kind 和 id 时清空当前成员的回收站，返回删除的条数
+#[tauri::command]
+pub fn purge_trash(
+    kind: Option<TrashKind>,
+    id: Option<String>,
+    db: State<Database>,
+    app_dir: State<AppDir>,
+) -> AppResult<usize> {
+    let conn = db.write()?;
+    let repo = TrashRepo::new(&conn);
+    let purged = match (kind, id) {
+        (Some(kind), Some(id)) => {
+            repo.ensure_active(kind, &id)?;
+            repo.purge(kind, &id)?
+        }
+        (None, None) => repo.purge_all(None, Some(&PatientRepo::new(&conn).active_id()?))?,
+        _ => return Err(AppError::Validation("kind 与 id 需同时指定".into())),
+    };
+    drop(conn);
+
+    trash::remove_stored_files(&app_dir.0, &purged);
+    Ok(purged.count)
+}
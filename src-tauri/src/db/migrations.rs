@@ -0,0 +1,580 @@
+use crate::error::{AppError, AppResult, DbResultExt};
+use rusqlite::{Connection, OptionalExtension, Transaction};
+use std::path::Path;
+
+/// 单个版本化迁移，版本号写入 `PRAGMA user_version`
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub up: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+/// 全部迁移，按版本号递增排列，已发布的迁移不可修改
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "创建基础 8 张表",
+        up: v1_init_tables,
+    },
+    Migration {
+        version: 2,
+        description: "新增审计日志表",
+        up: v2_audit_log,
+    },
+    Migration {
+        version: 3,
+        description: "检查记录、文件、项目支持移入回收站",
+        up: v3_soft_delete,
+    },
+    Migration {
+        version: 4,
+        description: "新增成员表，检查记录按成员归属",
+        up: v4_patients,
+    },
+    Migration {
+        version: 5,
+        description: "新增后台任务表",
+        up: v5_jobs,
+    },
+    Migration {
+        version: 6,
+        description: "新增 AI 服务配置表，OCR 与分析可使用不同配置",
+        up: v6_ai_profiles,
+    },
+    Migration {
+        version: 7,
+        description: "AI 服务配置新增接口协议",
+        up: v7_ai_profile_provider,
+    },
+    Migration {
+        version: 8,
+        description: "检查项目可选择本地识别",
+        up: v8_project_ocr_engine,
+    },
+    Migration {
+        version: 9,
+        description: "识别结果支持手动修正",
+        up: v9_ocr_corrected,
+    },
+];
+
+/// 当前程序支持的最新 schema 版本
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// 读取数据库当前的 schema 版本
+pub fn current_version(conn: &Connection) -> rusqlite::Result<u32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// 执行全部未应用的迁移
+///
+/// 数据库中已有数据时，会先在 `backup_dir` 下生成一份快照再开始迁移；
+/// 每个迁移在独立事务中执行，失败时回滚且不会更新版本号。
+pub fn run(conn: &mut Connection, backup_dir: &Path) -> AppResult<()> {
+    let current = current_version(conn).db_context("读取数据库版本失败")?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(AppError::Conflict {
+            message: format!(
+                "数据库版本 (v{}) 高于当前程序支持的版本 (v{})，请升级程序后再打开",
+                current, latest
+            ),
+            details: serde_json::json!({ "database_version": current, "supported_version": latest }),
+        });
+    }
+    if current == latest {
+        return Ok(());
+    }
+
+    // 迁移失败时在错误信息中给出备份位置，便于用户恢复
+    let mut backup_hint = String::new();
+    if has_user_tables(conn).db_context("读取数据库结构失败")? {
+        let backup_path = backup_before_migrate(conn, backup_dir, current)?;
+        log::info!("迁移前已备份数据库: {}", backup_path.display());
+        backup_hint = format!("（迁移前的备份: {}）", backup_path.display());
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        log::info!("执行数据库迁移 v{}: {}", migration.version, migration.description);
+
+        let tx = conn
+            .transaction()
+            .db_context("开启迁移事务失败")?;
+        (migration.up)(&tx)
+            .map_err(|e| AppError::database(&format!("数据库迁移 v{} 失败{}", migration.version, backup_hint), e))?;
+        tx.pragma_update(None, "user_version", migration.version)
+            .db_context("更新数据库版本失败")?;
+        tx.commit()
+            .map_err(|e| AppError::database(&format!("提交数据库迁移 v{} 失败{}", migration.version, backup_hint), e))?;
+    }
+
+    Ok(())
+}
+
+/// 判断数据库中是否已有业务表（全新数据库无需备份）
+fn has_user_tables(conn: &Connection) -> rusqlite::Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// 使用 VACUUM INTO 生成一致性快照: backups/health_guard.v<版本>.<时间戳>.db
+fn backup_before_migrate(
+    conn: &Connection,
+    backup_dir: &Path,
+    version: u32,
+) -> AppResult<std::path::PathBuf> {
+    std::fs::create_dir_all(backup_dir).map_err(|e| AppError::io("创建备份目录失败", e))?;
+
+    // 同一秒内多次启动时文件名加随机后缀区分，VACUUM INTO 不会覆盖已有文件
+    let timestamp = chrono::Local::now().format("%Y%m%d%H%M%S");
+    let suffix = &uuid::Uuid::new_v4().to_string()[..8];
+    let backup_path = backup_dir.join(format!("health_guard.v{}.{}.{}.db", version, timestamp, suffix));
+
+    conn.execute("VACUUM INTO ?1", [backup_path.to_string_lossy()])
+        .db_context("迁移前备份数据库失败")?;
+
+    Ok(backup_path)
+}
+
+/// v1: 基础 8 张表（与早期版本的 init_tables 保持一致，兼容已存在的表）
+fn v1_init_tables(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        -- 1. 检查项目表
+        CREATE TABLE IF NOT EXISTS checkup_projects (
+            id              TEXT PRIMARY KEY,
+            name            TEXT NOT NULL,
+            description     TEXT DEFAULT '',
+            sort_order      INTEGER DEFAULT 0,
+            is_active       INTEGER DEFAULT 1,
+            created_at      TEXT NOT NULL,
+            updated_at      TEXT NOT NULL
+        );
+
+        -- 2. 检查指标表
+        CREATE TABLE IF NOT EXISTS indicators (
+            id              TEXT PRIMARY KEY,
+            project_id      TEXT NOT NULL,
+            name            TEXT NOT NULL,
+            unit            TEXT DEFAULT '',
+            reference_range TEXT DEFAULT '',
+            sort_order      INTEGER DEFAULT 0,
+            is_core         INTEGER DEFAULT 0,
+            created_at      TEXT NOT NULL,
+            FOREIGN KEY (project_id) REFERENCES checkup_projects(id)
+        );
+
+        -- 3. 检查记录表
+        CREATE TABLE IF NOT EXISTS checkup_records (
+            id              TEXT PRIMARY KEY,
+            checkup_date    TEXT NOT NULL,
+            status          TEXT NOT NULL DEFAULT 'pending_ocr',
+            notes           TEXT DEFAULT '',
+            created_at      TEXT NOT NULL,
+            updated_at      TEXT NOT NULL
+        );
+
+        -- 4. 检查文件表
+        CREATE TABLE IF NOT EXISTS checkup_files (
+            id                TEXT PRIMARY KEY,
+            record_id         TEXT NOT NULL,
+            project_id        TEXT NOT NULL,
+            original_filename TEXT NOT NULL,
+            stored_path       TEXT NOT NULL,
+            file_size         INTEGER DEFAULT 0,
+            mime_type         TEXT DEFAULT '',
+            uploaded_at       TEXT NOT NULL,
+            FOREIGN KEY (record_id) REFERENCES checkup_records(id),
+            FOREIGN KEY (project_id) REFERENCES checkup_projects(id)
+        );
+
+        -- 5. OCR 结果表
+        CREATE TABLE IF NOT EXISTS ocr_results (
+            id              TEXT PRIMARY KEY,
+            file_id         TEXT NOT NULL,
+            record_id       TEXT NOT NULL,
+            project_id      TEXT NOT NULL,
+            checkup_date    TEXT NOT NULL,
+            raw_json        TEXT DEFAULT '',
+            parsed_items    TEXT DEFAULT '[]',
+            status          TEXT NOT NULL DEFAULT 'processing',
+            error_message   TEXT DEFAULT '',
+            created_at      TEXT NOT NULL,
+            FOREIGN KEY (file_id) REFERENCES checkup_files(id),
+            FOREIGN KEY (record_id) REFERENCES checkup_records(id),
+            FOREIGN KEY (project_id) REFERENCES checkup_projects(id)
+        );
+
+        -- 6. AI 分析记录表
+        CREATE TABLE IF NOT EXISTS ai_analyses (
+            id                TEXT PRIMARY KEY,
+            record_id         TEXT NOT NULL,
+            request_prompt    TEXT DEFAULT '',
+            response_content  TEXT DEFAULT '',
+            model_used        TEXT DEFAULT '',
+            status            TEXT NOT NULL DEFAULT 'processing',
+            error_message     TEXT DEFAULT '',
+            created_at        TEXT NOT NULL,
+            FOREIGN KEY (record_id) REFERENCES checkup_records(id)
+        );
+
+        -- 7. 指标值表（用于趋势分析）
+        CREATE TABLE IF NOT EXISTS indicator_values (
+            id              TEXT PRIMARY KEY,
+            ocr_result_id   TEXT NOT NULL,
+            record_id       TEXT NOT NULL,
+            project_id      TEXT NOT NULL,
+            indicator_id    TEXT NOT NULL,
+            checkup_date    TEXT NOT NULL,
+            value           REAL,
+            value_text      TEXT DEFAULT '',
+            is_abnormal     INTEGER DEFAULT 0,
+            created_at      TEXT NOT NULL,
+            FOREIGN KEY (ocr_result_id) REFERENCES ocr_results(id),
+            FOREIGN KEY (record_id) REFERENCES checkup_records(id),
+            FOREIGN KEY (project_id) REFERENCES checkup_projects(id),
+            FOREIGN KEY (indicator_id) REFERENCES indicators(id)
+        );
+
+        -- 8. 系统配置表
+        CREATE TABLE IF NOT EXISTS system_config (
+            id              TEXT PRIMARY KEY,
+            config_key      TEXT NOT NULL UNIQUE,
+            config_value    TEXT DEFAULT '',
+            updated_at      TEXT NOT NULL
+        );
+        ",
+    )
+}
+
+/// v2: 审计日志，记录每次数据变更前后的整行快照
+fn v2_audit_log(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE audit_log (
+            id              TEXT PRIMARY KEY,
+            table_name      TEXT NOT NULL,
+            row_id          TEXT NOT NULL,
+            action          TEXT NOT NULL,
+            before_json     TEXT,
+            after_json      TEXT,
+            created_at      TEXT NOT NULL,
+            undone_at       TEXT,
+            undo_of         TEXT
+        );
+
+        CREATE INDEX idx_audit_log_row ON audit_log(table_name, row_id);
+        CREATE INDEX idx_audit_log_created_at ON audit_log(created_at);
+        ",
+    )
+}
+
+/// v3: 软删除，deleted_at 非空表示已移入回收站（UTC 时间，见 `repo::trash::deleted_at`）
+fn v3_soft_delete(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        ALTER TABLE checkup_projects ADD COLUMN deleted_at TEXT;
+        ALTER TABLE checkup_records ADD COLUMN deleted_at TEXT;
+        ALTER TABLE checkup_files ADD COLUMN deleted_at TEXT;
+
+        CREATE INDEX idx_checkup_projects_deleted_at ON checkup_projects(deleted_at);
+        CREATE INDEX idx_checkup_records_deleted_at ON checkup_records(deleted_at);
+        CREATE INDEX idx_checkup_files_deleted_at ON checkup_files(deleted_at);
+        ",
+    )
+}
+
+/// v4: 成员表，已有的检查记录全部归属到默认成员
+fn v4_patients(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE patients (
+            id              TEXT PRIMARY KEY,
+            name            TEXT NOT NULL,
+            gender          TEXT DEFAULT '',
+            birth_date      TEXT DEFAULT '',
+            notes           TEXT DEFAULT '',
+            sort_order      INTEGER DEFAULT 0,
+            created_at      TEXT NOT NULL,
+            updated_at      TEXT NOT NULL
+        );
+
+        ALTER TABLE checkup_records ADD COLUMN patient_id TEXT REFERENCES patients(id);
+        CREATE INDEX idx_checkup_records_patient ON checkup_records(patient_id);
+        ",
+    )?;
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let now = chrono::Local::now().to_rfc3339();
+    tx.execute(
+        "INSERT INTO patients (id, name, created_at, updated_at) VALUES (?1, '本人', ?2, ?2)",
+        rusqlite::params![id, now],
+    )?;
+    tx.execute("UPDATE checkup_records SET patient_id = ?1", [&id])?;
+    Ok(())
+}
+
+/// v5: 持久化的 OCR / AI 分析任务，程序重启后继续执行未完成的任务
+fn v5_jobs(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE jobs (
+            id              TEXT PRIMARY KEY,
+            kind            TEXT NOT NULL,
+            record_id       TEXT NOT NULL,
+            status          TEXT NOT NULL DEFAULT 'queued',
+            attempts        INTEGER NOT NULL DEFAULT 0,
+            progress_done   INTEGER NOT NULL DEFAULT 0,
+            progress_total  INTEGER NOT NULL DEFAULT 0,
+            last_error      TEXT,
+            result_id       TEXT,
+            created_at      TEXT NOT NULL,
+            started_at      TEXT,
+            finished_at     TEXT,
+            updated_at      TEXT NOT NULL,
+            owner           TEXT,
+            heartbeat_at    INTEGER,
+            FOREIGN KEY (record_id) REFERENCES checkup_records(id)
+        );
+
+        CREATE INDEX idx_jobs_status ON jobs(status);
+        CREATE INDEX idx_jobs_record ON jobs(record_id);
+        ",
+    )
+}
+
+/// v6: 多组 AI 服务配置；原有的单组配置迁移为「默认」配置并用于全部任务，
+/// 之后删除 system_config 中的旧配置项（含明文密钥）
+fn v6_ai_profiles(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE ai_profiles (
+            id              TEXT PRIMARY KEY,
+            name            TEXT NOT NULL,
+            api_url         TEXT NOT NULL DEFAULT '',
+            api_key         TEXT NOT NULL DEFAULT '',
+            proxy_enabled   INTEGER NOT NULL DEFAULT 0,
+            proxy_url       TEXT NOT NULL DEFAULT '',
+            proxy_username  TEXT NOT NULL DEFAULT '',
+            proxy_password  TEXT NOT NULL DEFAULT '',
+            model           TEXT NOT NULL,
+            parameters      TEXT NOT NULL DEFAULT '{}',
+            created_at      TEXT NOT NULL,
+            updated_at      TEXT NOT NULL
+        );
+        ",
+    )?;
+
+    migrate_legacy_ai_config(tx)?;
+    tx.execute(
+        "DELETE FROM system_config WHERE config_key IN
+         ('ai_api_url', 'ai_api_key', 'ai_models', 'ai_default_model', 'proxy_enabled', 'proxy_url', 'proxy_username', 'proxy_password')",
+        [],
+    )?;
+    Ok(())
+}
+
+/// 将旧的单组 AI 与代理配置写入 `ai_profiles`，未配置接口地址时跳过
+fn migrate_legacy_ai_config(tx: &Transaction) -> rusqlite::Result<()> {
+    let config = |key: &str| -> rusqlite::Result<String> {
+        tx.query_row(
+            "SELECT config_value FROM system_config WHERE config_key = ?1",
+            [key],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .optional()
+        .map(|value| value.flatten().unwrap_or_default())
+    };
+
+    let api_url = config("ai_api_url")?;
+    if api_url.is_empty() {
+        return Ok(());
+    }
+    let model = match config("ai_default_model")? {
+        model if !model.is_empty() => model,
+        _ => serde_json::from_str::<Vec<String>>(&config("ai_models")?)
+            .ok()
+            .and_then(|models| models.into_iter().next())
+            .unwrap_or_else(|| "gpt-4o-mini".to_string()),
+    };
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let now = chrono::Local::now().to_rfc3339();
+    tx.execute(
+        "INSERT INTO ai_profiles (id, name, api_url, api_key, proxy_enabled, proxy_url, proxy_username, proxy_password,
+                                  model, created_at, updated_at)
+         VALUES (?1, '默认', ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9)",
+        rusqlite::params![
+            id,
+            api_url,
+            config("ai_api_key")?,
+            (config("proxy_enabled")? == "true") as i32,
+            config("proxy_url")?,
+            config("proxy_username")?,
+            config("proxy_password")?,
+            model,
+            now,
+        ],
+    )?;
+    for key in ["ai_profile_ocr", "ai_profile_analysis"] {
+        tx.execute(
+            "INSERT INTO system_config (id, config_key, config_value, updated_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(config_key) DO UPDATE SET config_value = excluded.config_value, updated_at = excluded.updated_at",
+            rusqlite::params![uuid::Uuid::new_v4().to_string(), key, id, now],
+        )?;
+    }
+    Ok(())
+}
+
+/// v7: AI 服务配置区分接口协议，已有配置均为 OpenAI 兼容接口
+fn v7_ai_profile_provider(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch("ALTER TABLE ai_profiles ADD COLUMN provider TEXT NOT NULL DEFAULT 'openai';")
+}
+
+/// v8: 检查项目的识别方式，已有项目继续使用视觉模型
+fn v8_project_ocr_engine(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch("ALTER TABLE checkup_projects ADD COLUMN ocr_engine TEXT NOT NULL DEFAULT 'vision';")
+}
+
+/// v9: 识别结果的手动修正时间，修正过的结果不再被重新识别覆盖
+fn v9_ocr_corrected(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch("ALTER TABLE ocr_results ADD COLUMN corrected_at TEXT;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 早期版本（未使用 user_version）创建的数据库：只有基础表，版本号为 0
+    fn legacy_database() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let tx = conn.transaction().unwrap();
+        v1_init_tables(&tx).unwrap();
+        tx.execute_batch(
+            "INSERT INTO checkup_projects (id, name, created_at, updated_at) VALUES ('p', '血常规', 't', 't');
+             INSERT INTO checkup_records (id, checkup_date, status, created_at, updated_at)
+             VALUES ('r', '2024-01-01', 'pending_ocr', 't', 't');",
+        )
+        .unwrap();
+        tx.commit().unwrap();
+        conn
+    }
+
+    fn temp_dir() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("health-migrations-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn legacy_database_is_backed_up_and_upgraded() {
+        let mut conn = legacy_database();
+        let backup_dir = temp_dir();
+        run(&mut conn, &backup_dir).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+
+        // 原有数据保留，并归属到迁移创建的默认成员
+        let (date, patient): (String, String) = conn
+            .query_row(
+                "SELECT r.checkup_date, p.name FROM checkup_records r JOIN patients p ON r.patient_id = p.id",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((date.as_str(), patient.as_str()), ("2024-01-01", "本人"));
+
+        // 迁移前的快照保持原样
+        let backups: Vec<_> = std::fs::read_dir(&backup_dir).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(backups.len(), 1);
+        let name = backups[0].file_name().unwrap().to_string_lossy().into_owned();
+        assert!(name.starts_with("health_guard.v0."), "{}", name);
+        let backup = Connection::open(&backups[0]).unwrap();
+        assert_eq!(current_version(&backup).unwrap(), 0);
+        let records: i64 = backup.query_row("SELECT COUNT(*) FROM checkup_records", [], |row| row.get(0)).unwrap();
+        assert_eq!(records, 1);
+        assert!(backup.prepare("SELECT * FROM patients").is_err());
+
+        // 已是最新版本时不再迁移也不再备份
+        run(&mut conn, &backup_dir).unwrap();
+        assert_eq!(std::fs::read_dir(&backup_dir).unwrap().count(), 1);
+
+        // 同一秒内的多次备份不会冲突
+        let first = backup_before_migrate(&conn, &backup_dir, 1).unwrap();
+        let second = backup_before_migrate(&conn, &backup_dir, 1).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(std::fs::read_dir(&backup_dir).unwrap().count(), 3);
+        std::fs::remove_dir_all(&backup_dir).ok();
+    }
+
+    #[test]
+    fn new_database_is_not_backed_up() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let backup_dir = temp_dir();
+        run(&mut conn, &backup_dir).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        assert!(!backup_dir.exists());
+    }
+
+    #[test]
+    fn newer_database_is_refused() {
+        let mut conn = legacy_database();
+        let newer = latest_version() + 1;
+        conn.pragma_update(None, "user_version", newer).unwrap();
+        let backup_dir = temp_dir();
+
+        let error = run(&mut conn, &backup_dir).unwrap_err();
+        assert_eq!(error.code(), "conflict");
+        assert_eq!(current_version(&conn).unwrap(), newer);
+        assert!(conn.prepare("SELECT * FROM patients").is_err());
+        assert!(!backup_dir.exists());
+    }
+
+    #[test]
+    fn legacy_ai_config_is_migrated_to_a_default_profile() {
+        use crate::repo::ai_profile::ProviderKind;
+        use crate::repo::AiProfileRepo;
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        let tx = conn.transaction().unwrap();
+        for migration in &MIGRATIONS[..5] {
+            (migration.up)(&tx).unwrap();
+        }
+        for (key, value) in [
+            ("ai_api_url", "https://example.com/v1/chat/completions"),
+            ("ai_api_key", "key"),
+            ("ai_models", r#"["gpt-4o", "gpt-4o-mini"]"#),
+            ("proxy_enabled", "true"),
+            ("proxy_url", "127.0.0.1:7890"),
+        ] {
+            tx.execute(
+                "INSERT INTO system_config (id, config_key, config_value, updated_at) VALUES (?1, ?1, ?2, '')",
+                [key, value],
+            )
+            .unwrap();
+        }
+        for migration in &MIGRATIONS[5..] {
+            (migration.up)(&tx).unwrap();
+        }
+        tx.commit().unwrap();
+
+        let repo = AiProfileRepo::new(&conn);
+        let profiles = repo.list().unwrap();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!((profiles[0].model.as_str(), profiles[0].proxy_enabled), ("gpt-4o", true));
+        assert_eq!(profiles[0].provider, ProviderKind::Openai);
+        let tasks = repo.task_profiles().unwrap();
+        assert_eq!(tasks.ocr.as_deref(), Some(profiles[0].id.as_str()));
+        assert_eq!(tasks.analysis.as_deref(), Some(profiles[0].id.as_str()));
+
+        // 旧配置项（含明文密钥）已删除
+        let config = crate::repo::ConfigRepo::new(&conn);
+        for key in ["ai_api_url", "ai_api_key", "ai_models", "proxy_enabled", "proxy_url"] {
+            assert_eq!(config.get(key).unwrap(), None, "{}", key);
+        }
+    }
+}
@@ -0,0 +1,389 @@
+pub mod migrations;
+mod pool;
+
+pub use pool::PooledConnection;
+
+use crate::error::{AppError, AppResult, DbResultExt};
+use pool::ConnectionPool;
+use rusqlite::{Connection, DatabaseName, ErrorCode, OpenFlags};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// 只读连接池大小
+const READER_POOL_SIZE: usize = 4;
+
+/// 数据库文件名
+pub const DB_FILE_NAME: &str = "health_guard.db";
+
+/// 数据库访问层：一个串行化的写连接 + 若干并发只读连接（依赖 WAL 模式）
+///
+/// 数据库加密后，启动时处于锁定状态，需调用 `unlock` 提供口令后才能访问。
+pub struct Database {
+    db_path: PathBuf,
+    backup_dir: PathBuf,
+    pools: RwLock<Option<Pools>>,
+    /// 数据已迁往其他目录，重启前不再打开
+    retired: AtomicBool,
+}
+
+struct Pools {
+    writer: Arc<ConnectionPool>,
+    readers: Arc<ConnectionPool>,
+    /// 当前使用的加密口令，未加密时为 None
+    passphrase: Option<String>,
+}
+
+impl Pools {
+    fn in_use(&self) -> bool {
+        self.writer.in_use() || self.readers.in_use()
+    }
+}
+
+/// 数据库加密/锁定状态
+#[derive(Debug, Serialize, Clone)]
+pub struct DatabaseStatus {
+    pub encrypted: bool,
+    pub locked: bool,
+}
+
+impl Database {
+    /// 初始化数据库，在 app_dir 下创建 health_guard.db 并执行未应用的迁移
+    ///
+    /// 若数据库文件已加密，则返回锁定状态的实例，迁移推迟到解锁时执行。
+    pub fn new(app_dir: PathBuf) -> AppResult<Self> {
+        std::fs::create_dir_all(&app_dir).ok();
+        let db = Database {
+            db_path: app_dir.join(DB_FILE_NAME),
+            backup_dir: app_dir.join("backups"),
+            pools: RwLock::new(None),
+            retired: AtomicBool::new(false),
+        };
+
+        if is_encrypted(&db.db_path)? {
+            log::info!("数据库已加密，等待解锁");
+            return Ok(db);
+        }
+
+        let pools = db.open_pools(None)?;
+        *db.pools.write().map_err(|_| AppError::Internal("数据库状态不可用".into()))? = Some(pools);
+        Ok(db)
+    }
+
+    /// 借出一个只读连接，池中无空闲连接时等待归还
+    pub fn read(&self) -> AppResult<PooledConnection> {
+        self.with_pools(|pools| pools.readers.acquire())
+    }
+
+    /// 获取唯一的写连接，所有写操作在此串行执行
+    pub fn write(&self) -> AppResult<PooledConnection> {
+        self.with_pools(|pools| pools.writer.acquire())
+    }
+
+    /// 查询当前加密/锁定状态
+    pub fn status(&self) -> AppResult<DatabaseStatus> {
+        let pools = self.pools.read().map_err(|_| AppError::Internal("数据库状态不可用".into()))?;
+        Ok(status_of(pools.as_ref()))
+    }
+
+    /// 使用口令解锁已加密的数据库，并执行未应用的迁移
+    pub fn unlock(&self, passphrase: &str) -> AppResult<()> {
+        self.ensure_active()?;
+        let mut pools = self.pools.write().map_err(|_| AppError::Internal("数据库状态不可用".into()))?;
+        if pools.is_some() {
+            return Ok(());
+        }
+        *pools = Some(self.open_pools(Some(passphrase))?);
+        log::info!("数据库已解锁");
+        Ok(())
+    }
+
+    /// 一次性将明文数据库加密，并清理迁移时留下的明文快照
+    pub fn encrypt(&self, passphrase: &str) -> AppResult<()> {
+        if passphrase.is_empty() {
+            return Err(AppError::Validation("加密口令不能为空".into()));
+        }
+        if self.status()?.encrypted {
+            return Err(AppError::Validation("数据库已加密，如需修改请使用修改口令功能".into()));
+        }
+
+        self.rekey(None, Some(passphrase))?;
+        remove_plaintext_snapshots(&self.backup_dir);
+        log::info!("数据库已加密");
+        Ok(())
+    }
+
+    /// 修改加密口令，新口令为空时解除加密
+    pub fn change_passphrase(&self, old_passphrase: &str, new_passphrase: &str) -> AppResult<()> {
+        let current = {
+            let pools = self.pools.read().map_err(|_| AppError::Internal("数据库状态不可用".into()))?;
+            match pools.as_ref() {
+                Some(p) => p.passphrase.clone(),
+                None => return Err(AppError::Locked(LOCKED_MESSAGE.into())),
+            }
+        };
+        match current.as_deref() {
+            None => return Err(AppError::Validation("数据库未加密".into())),
+            Some(current) if current != old_passphrase => return Err(AppError::Validation("原口令错误".into())),
+            _ => {}
+        }
+
+        let new_passphrase = Some(new_passphrase).filter(|p| !p.is_empty());
+        self.rekey(Some(old_passphrase), new_passphrase)
+    }
+
+    /// 使用 SQLite 在线备份 API 将当前数据库写出为一致性快照（加密数据库沿用当前口令）
+    ///
+    /// 返回打开快照文件的连接，便于读取与快照内容一致的数据。
+    pub fn snapshot(&self, dest: &Path) -> AppResult<Connection> {
+        let (src, passphrase) = self.with_pools(|pools| {
+            Ok((pools.readers.acquire()?, pools.passphrase.clone()))
+        })?;
+
+        std::fs::remove_file(dest).ok();
+        let mut dst = Connection::open(dest).db_context("创建快照文件失败")?;
+        if let Some(key) = passphrase.as_deref() {
+            dst.pragma_update(None, "key", key)
+                .db_context("设置快照口令失败")?;
+        }
+
+        // 单步复制全部页面，期间源库的读事务保证快照一致
+        let backup = rusqlite::backup::Backup::new(&src, &mut dst)
+            .db_context("创建数据库快照失败")?;
+        match backup.step(-1) {
+            Ok(rusqlite::backup::StepResult::Done) => {}
+            Ok(other) => return Err(AppError::database("创建数据库快照失败", format!("{:?}", other))),
+            Err(e) => return Err(AppError::database("创建数据库快照失败", e)),
+        }
+        drop(backup);
+        Ok(dst)
+    }
+
+    /// 用另一个数据库文件替换当前数据库并重新打开
+    ///
+    /// 新文件为明文时直接打开；加密时先尝试当前口令，失败则进入锁定状态等待解锁。
+    pub fn replace_with(&self, new_db: &Path) -> AppResult<DatabaseStatus> {
+        self.ensure_active()?;
+        let mut pools = self.pools.write().map_err(|_| AppError::Internal("数据库状态不可用".into()))?;
+        if pools.as_ref().is_some_and(Pools::in_use) {
+            return Err(AppError::Busy("数据库正在使用中，请等待当前任务完成后重试".into()));
+        }
+        let passphrase = pools.as_ref().and_then(|p| p.passphrase.clone());
+
+        // 释放全部连接后再替换文件
+        *pools = None;
+        for suffix in ["-wal", "-shm"] {
+            std::fs::remove_file(sidecar_path(&self.db_path, suffix)).ok();
+        }
+        std::fs::rename(new_db, &self.db_path)
+            .or_else(|_| std::fs::copy(new_db, &self.db_path).map(|_| ()))
+            .map_err(|e| AppError::io("替换数据库文件失败", e))?;
+
+        if !is_encrypted(&self.db_path)? {
+            *pools = Some(self.open_pools(None)?);
+        } else if let Some(key) = passphrase.as_deref() {
+            match self.open_pools(Some(key)) {
+                Ok(p) => *pools = Some(p),
+                Err(e) => log::info!("新数据库无法使用当前口令打开，等待解锁: {}", e),
+            }
+        }
+
+        Ok(status_of(pools.as_ref()))
+    }
+
+    /// 关闭全部连接后以数据库文件路径调用 `f`（如复制文件到新的数据目录）
+    ///
+    /// `f` 成功后数据库保持关闭，此后的读写都返回错误直到重启，避免写入留在旧目录中丢失；
+    /// 失败时按原口令重新打开。
+    pub fn retire_with<T>(&self, f: impl FnOnce(&Path) -> AppResult<T>) -> AppResult<T> {
+        self.ensure_active()?;
+        let mut pools = self.pools.write().map_err(|_| AppError::Internal("数据库状态不可用".into()))?;
+        if pools.as_ref().is_some_and(Pools::in_use) {
+            return Err(AppError::Busy("数据库正在使用中，请等待当前任务完成后重试".into()));
+        }
+        let passphrase = pools.take().map(|p| p.passphrase);
+
+        match f(&self.db_path) {
+            Ok(value) => {
+                self.retired.store(true, Ordering::SeqCst);
+                Ok(value)
+            }
+            Err(e) => {
+                if let Some(passphrase) = passphrase {
+                    *pools = Some(self.open_pools(passphrase.as_deref())?);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    fn ensure_active(&self) -> AppResult<()> {
+        if self.retired.load(Ordering::SeqCst) {
+            return Err(AppError::Busy(RETIRED_MESSAGE.into()));
+        }
+        Ok(())
+    }
+
+    fn with_pools<T>(&self, f: impl FnOnce(&Pools) -> AppResult<T>) -> AppResult<T> {
+        self.ensure_active()?;
+        let pools = self.pools.read().map_err(|_| AppError::Internal("数据库状态不可用".into()))?;
+        match pools.as_ref() {
+            Some(p) => f(p),
+            None => Err(AppError::Locked(LOCKED_MESSAGE.into())),
+        }
+    }
+
+    /// 打开写连接、执行迁移，再打开只读连接
+    fn open_pools(&self, passphrase: Option<&str>) -> AppResult<Pools> {
+        let mut writer = open_connection(&self.db_path, passphrase, false)?;
+        migrations::run(&mut writer, &self.backup_dir)?;
+
+        // 迁移完成后再打开只读连接，保证读到最新的表结构
+        let readers = (0..READER_POOL_SIZE)
+            .map(|_| open_connection(&self.db_path, passphrase, true))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Pools {
+            writer: ConnectionPool::new(vec![writer]),
+            readers: ConnectionPool::new(readers),
+            passphrase: passphrase.map(str::to_string),
+        })
+    }
+
+    /// 关闭全部连接，用新口令导出整个数据库后替换原文件，再重新打开
+    fn rekey(&self, from: Option<&str>, to: Option<&str>) -> AppResult<()> {
+        self.ensure_active()?;
+        let mut pools = self.pools.write().map_err(|_| AppError::Internal("数据库状态不可用".into()))?;
+        match pools.as_ref() {
+            None => return Err(AppError::Locked(LOCKED_MESSAGE.into())),
+            Some(p) if p.in_use() => return Err(AppError::Busy("数据库正在使用中，请等待当前任务完成后重试".into())),
+            _ => {}
+        }
+
+        // 释放全部连接后再替换文件
+        *pools = None;
+
+        if let Err(e) = export_database(&self.db_path, from, to) {
+            *pools = Some(self.open_pools(from)?);
+            return Err(e);
+        }
+
+        *pools = Some(self.open_pools(to)?);
+        Ok(())
+    }
+}
+
+const LOCKED_MESSAGE: &str = "数据库已加密，请先输入口令解锁";
+const RETIRED_MESSAGE: &str = "数据目录已迁移，请重启程序后继续使用";
+
+fn status_of(pools: Option<&Pools>) -> DatabaseStatus {
+    match pools {
+        Some(p) => DatabaseStatus { encrypted: p.passphrase.is_some(), locked: false },
+        None => DatabaseStatus { encrypted: true, locked: true },
+    }
+}
+
+/// 打开连接并设置口令；口令错误时读取 sqlite_master 会失败
+fn open_connection(db_path: &Path, passphrase: Option<&str>, read_only: bool) -> AppResult<Connection> {
+    let conn = if read_only {
+        Connection::open_with_flags(
+            db_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX | OpenFlags::SQLITE_OPEN_URI,
+        )
+    } else {
+        Connection::open(db_path)
+    }
+    .db_context("打开数据库失败")?;
+
+    if let Some(key) = passphrase {
+        conn.pragma_update(None, "key", key)
+            .db_context("设置数据库口令失败")?;
+    }
+
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))
+        .map_err(|e| match e.sqlite_error_code() {
+            Some(ErrorCode::NotADatabase) => AppError::Validation("数据库口令错误".into()),
+            _ => AppError::database("打开数据库失败", e),
+        })?;
+
+    if !read_only {
+        // 启用 WAL 模式，读连接不会被写事务阻塞
+        conn.execute_batch("PRAGMA journal_mode=WAL;")
+            .db_context("设置 WAL 模式失败")?;
+        conn.execute_batch("PRAGMA foreign_keys=ON;")
+            .db_context("启用外键约束失败")?;
+    }
+    conn.busy_timeout(std::time::Duration::from_secs(5))
+        .db_context("设置数据库超时失败")?;
+
+    Ok(conn)
+}
+
+/// 不带口令无法读取 sqlite_master 时，视为已加密
+fn is_encrypted(db_path: &Path) -> AppResult<bool> {
+    if !db_path.exists() {
+        return Ok(false);
+    }
+    let conn = Connection::open(db_path).db_context("打开数据库失败")?;
+    match conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0)) {
+        Ok(_) => Ok(false),
+        Err(e) if e.sqlite_error_code() == Some(ErrorCode::NotADatabase) => Ok(true),
+        Err(e) => Err(AppError::database("读取数据库失败", e)),
+    }
+}
+
+/// 通过 sqlcipher_export 将数据库导出为使用新口令（或明文）的副本，并替换原文件
+fn export_database(db_path: &Path, from: Option<&str>, to: Option<&str>) -> AppResult<()> {
+    let export_path = db_path.with_extension("db.rekey");
+    std::fs::remove_file(&export_path).ok();
+
+    {
+        let conn = open_connection(db_path, from, false)?;
+        let version = migrations::current_version(&conn)
+            .db_context("读取数据库版本失败")?;
+
+        conn.execute(
+            "ATTACH DATABASE ?1 AS rekeyed KEY ?2",
+            rusqlite::params![export_path.to_string_lossy(), to.unwrap_or("")],
+        )
+        .db_context("创建加密副本失败")?;
+        conn.query_row("SELECT sqlcipher_export('rekeyed')", [], |_| Ok(()))
+            .db_context("导出数据库失败")?;
+        conn.pragma_update(Some(DatabaseName::Attached("rekeyed")), "user_version", version)
+            .db_context("写入数据库版本失败")?;
+        conn.execute("DETACH DATABASE rekeyed", [])
+            .db_context("导出数据库失败")?;
+        conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);")
+            .db_context("写入数据库失败")?;
+    }
+
+    // 所有连接已关闭，清理 WAL 文件后替换
+    for suffix in ["-wal", "-shm"] {
+        std::fs::remove_file(sidecar_path(db_path, suffix)).ok();
+    }
+    std::fs::rename(&export_path, db_path).map_err(|e| AppError::io("替换数据库文件失败", e))
+}
+
+/// 数据库 WAL/SHM 附属文件路径
+pub fn sidecar_path(db_path: &Path, suffix: &str) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// 删除迁移前生成的明文快照（加密后这些快照会泄露数据）
+fn remove_plaintext_snapshots(backup_dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(backup_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with("health_guard.v") && name.ends_with(".db") {
+            match std::fs::remove_file(entry.path()) {
+                Ok(()) => log::info!("已删除明文快照: {}", name),
+                Err(e) => log::warn!("删除明文快照失败 {}: {}", name, e),
+            }
+        }
+    }
+}
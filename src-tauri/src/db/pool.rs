@@ -0,0 +1,113 @@
+use crate::error::{AppError, AppResult};
+use rusqlite::Connection;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex};
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+/// 固定大小的连接池，借出的连接在离开作用域时自动归还
+pub struct ConnectionPool {
+    conns: Mutex<Vec<Connection>>,
+    available: Condvar,
+}
+
+/// 从连接池借出的连接
+pub struct PooledConnection {
+    pool: Arc<ConnectionPool>,
+    conn: Option<Connection>,
+}
+
+impl ConnectionPool {
+    pub fn new(conns: Vec<Connection>) -> Arc<Self> {
+        Arc::new(ConnectionPool {
+            conns: Mutex::new(conns),
+            available: Condvar::new(),
+        })
+    }
+
+    /// 借出一个连接，池中无空闲连接时等待归还
+    ///
+    /// 异步命令与后台任务也会调用此方法，等待期间让出所在的 tokio 工作线程，见 [`wait_blocking`]。
+    pub fn acquire(self: &Arc<Self>) -> AppResult<PooledConnection> {
+        let mut conns = self.conns.lock().map_err(|_| AppError::Internal("数据库连接池不可用".into()))?;
+        loop {
+            if let Some(conn) = conns.pop() {
+                return Ok(PooledConnection {
+                    pool: Arc::clone(self),
+                    conn: Some(conn),
+                });
+            }
+            conns = wait_blocking(|| self.available.wait(conns))
+                .map_err(|_| AppError::Internal("数据库连接池不可用".into()))?;
+        }
+    }
+
+    /// 连接池是否仍有连接被借出
+    pub fn in_use(self: &Arc<Self>) -> bool {
+        Arc::strong_count(self) > 1
+    }
+}
+
+/// 执行会阻塞的等待；在 tokio 多线程运行时的工作线程上时先通知运行时，
+/// 由其他线程接管该线程上排队的异步任务（包括即将归还连接的任务），避免等待期间异步任务停滞
+fn wait_blocking<T>(f: impl FnOnce() -> T) -> T {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => tokio::task::block_in_place(f),
+        _ => f(),
+    }
+}
+
+impl Deref for PooledConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("连接已归还")
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().expect("连接已归还")
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take()
+            && let Ok(mut conns) = self.pool.conns.lock()
+        {
+            conns.push(conn);
+            self.pool.available.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn waiting_for_a_connection_does_not_stall_the_runtime() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_time()
+            .build()
+            .unwrap();
+        let pool = ConnectionPool::new(vec![Connection::open_in_memory().unwrap()]);
+        let held = pool.acquire().unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        // 唯一的工作线程在等待连接时，负责归还连接的任务仍能执行
+        runtime.spawn(async move {
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                drop(held);
+            });
+            tx.send(pool.acquire().is_ok()).unwrap();
+        });
+        let acquired = rx.recv_timeout(Duration::from_secs(5));
+        // 出现停滞时工作线程无法退出，不等待运行时关闭
+        runtime.shutdown_background();
+        assert_eq!(acquired, Ok(true));
+    }
+}
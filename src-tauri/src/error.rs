@@ -0,0 +1,130 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+use std::fmt;
+
+/// 全局错误类型，序列化为 `{code, message, details}` 供前端按 code 分支处理
+#[derive(Debug)]
+pub enum AppError {
+    /// 数据不存在
+    NotFound(String),
+    /// 参数校验失败
+    Validation(String),
+    /// 与现有数据冲突（如仍有关联数据无法删除），details 描述冲突来源
+    Conflict { message: String, details: Value },
+    /// 数据库读写失败
+    Database(String),
+    /// 文件读写失败
+    Io(String),
+    /// AI 服务请求失败，status 为 HTTP 状态码（网络错误时为 None）
+    AiProvider { message: String, status: Option<u16> },
+    /// 缺少必要配置，key 为 system_config 中的配置键
+    ConfigMissing { message: String, key: String },
+    /// 数据库已加密且尚未解锁
+    Locked(String),
+    /// 资源正在使用中，稍后重试
+    Busy(String),
+    /// 其他内部错误
+    Internal(String),
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    /// 稳定的错误码，前端据此判断错误类型
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "not_found",
+            AppError::Validation(_) => "validation",
+            AppError::Conflict { .. } => "conflict",
+            AppError::Database(_) => "database",
+            AppError::Io(_) => "io",
+            AppError::AiProvider { .. } => "ai_provider",
+            AppError::ConfigMissing { .. } => "config_missing",
+            AppError::Locked(_) => "database_locked",
+            AppError::Busy(_) => "busy",
+            AppError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::NotFound(m)
+            | AppError::Validation(m)
+            | AppError::Database(m)
+            | AppError::Io(m)
+            | AppError::Locked(m)
+            | AppError::Busy(m)
+            | AppError::Internal(m) => m,
+            AppError::Conflict { message, .. }
+            | AppError::AiProvider { message, .. }
+            | AppError::ConfigMissing { message, .. } => message,
+        }
+    }
+
+    pub fn details(&self) -> Value {
+        match self {
+            AppError::Conflict { details, .. } => details.clone(),
+            AppError::AiProvider { status, .. } => serde_json::json!({ "status": status }),
+            AppError::ConfigMissing { key, .. } => serde_json::json!({ "key": key }),
+            _ => Value::Null,
+        }
+    }
+
+    /// 数据库错误，消息格式为 "<上下文>: <原始错误>"
+    pub fn database(context: &str, err: impl fmt::Display) -> Self {
+        AppError::Database(format!("{}: {}", context, err))
+    }
+
+    /// 文件读写错误，消息格式为 "<上下文>: <原始错误>"
+    pub fn io(context: &str, err: impl fmt::Display) -> Self {
+        AppError::Io(format!("{}: {}", context, err))
+    }
+
+    /// 仍有关联数据时的冲突错误
+    pub fn has_dependents(message: String, dependent: &str, count: i64) -> Self {
+        AppError::Conflict {
+            message,
+            details: serde_json::json!({ "dependent": dependent, "count": count }),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AppError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", self.message())?;
+        state.serialize_field("details", &self.details())?;
+        state.end()
+    }
+}
+
+/// 为 rusqlite 结果附加上下文，区分"不存在"与其他数据库错误
+pub trait DbResultExt<T> {
+    /// 任意失败都视为数据库错误
+    fn db_context(self, context: &str) -> AppResult<T>;
+    /// 查询无结果时返回 NotFound，其余为数据库错误
+    fn or_not_found(self, message: &str) -> AppResult<T>;
+}
+
+impl<T> DbResultExt<T> for rusqlite::Result<T> {
+    fn db_context(self, context: &str) -> AppResult<T> {
+        self.map_err(|e| AppError::database(context, e))
+    }
+
+    fn or_not_found(self, message: &str) -> AppResult<T> {
+        self.map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::NotFound(message.to_string()),
+            e => AppError::database(message, e),
+        })
+    }
+}
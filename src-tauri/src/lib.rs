@@ -1,17 +1,16 @@
 mod db;
+mod error;
+mod repo;
 mod commands;
 mod services;
+mod api;
+pub mod cli;
 
+use error::AppResult;
 use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
 use std::path::PathBuf;
 
-/// 获取应用数据目录（程序所在目录）
-fn get_app_data_dir(_app: &AppHandle) -> PathBuf {
-    // 使用程序所在目录作为数据存储根目录
-    let exe_path = std::env::current_exe().unwrap_or_default();
-    exe_path.parent().unwrap_or(&PathBuf::from(".")).to_path_buf()
-}
-
 /// 确保 pictures 目录存在
 fn ensure_pictures_dir(app_dir: &PathBuf) {
     let pictures_dir = app_dir.join("pictures");
@@ -28,8 +27,24 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             quit,
             test_ai_connection,
+            commands::database::get_database_status,
+            commands::database::unlock_database,
+            commands::database::encrypt_database,
+            commands::database::change_database_passphrase,
             commands::config::get_config,
             commands::config::save_config,
+            commands::ai_profile::list_ai_profiles,
+            commands::ai_profile::create_ai_profile,
+            commands::ai_profile::update_ai_profile,
+            commands::ai_profile::delete_ai_profile,
+            commands::ai_profile::get_ai_task_profiles,
+            commands::ai_profile::set_ai_task_profile,
+            commands::patient::list_patients,
+            commands::patient::create_patient,
+            commands::patient::update_patient,
+            commands::patient::delete_patient,
+            commands::patient::get_active_patient,
+            commands::patient::set_active_patient,
             commands::project::list_projects,
             commands::project::create_project,
             commands::project::update_project,
@@ -50,12 +65,34 @@ pub fn run() {
             commands::file::read_file_base64,
             commands::file::delete_file,
             commands::ocr::start_ocr,
+            commands::ocr::cancel_ocr,
             commands::ocr::get_ocr_status,
             commands::ocr::get_ocr_results,
+            commands::ocr::update_ocr_items,
             commands::ai::start_ai_analysis,
+            commands::ai::cancel_ai_analysis,
             commands::ai::get_ai_analysis,
+            commands::job::list_jobs,
+            commands::job::get_job,
+            commands::job::cancel_job,
             commands::trend::get_project_trends,
             commands::trend::get_all_trends,
+            commands::backup::create_backup,
+            commands::backup::restore_backup,
+            commands::data_dir::get_data_dir,
+            commands::data_dir::relocate_data_dir,
+            commands::audit::list_audit_log,
+            commands::audit::undo_change,
+            commands::trash::list_trash,
+            commands::trash::restore_from_trash,
+            commands::trash::purge_trash,
+            commands::integrity::check_integrity,
+            commands::integrity::repair_integrity,
+            commands::api_server::get_api_server_status,
+            commands::api_server::start_api_server,
+            commands::api_server::stop_api_server,
+            commands::api_server::reset_api_token,
+            restart_app,
         ])
         .setup(|app| {
             // 初始化日志（仅调试模式）
@@ -68,15 +105,52 @@ pub fn run() {
             }
 
             // 初始化数据库
-            let app_dir = get_app_data_dir(app.handle());
+            let data_dir = services::data_dir::resolve();
+            log::info!("数据目录: {} ({:?})", data_dir.path.display(), data_dir.source);
+            let app_dir = data_dir.path.clone();
             ensure_pictures_dir(&app_dir);
 
-            let database = db::Database::new(app_dir.clone())
-                .expect("数据库初始化失败");
+            // 数据库版本高于当前程序或迁移失败时提示原因（含迁移前备份的位置），确认后退出
+            let database = match db::Database::new(app_dir.clone()) {
+                Ok(database) => database,
+                Err(e) => {
+                    log::error!("数据库初始化失败: {}", e);
+                    app.dialog()
+                        .message(e.to_string())
+                        .title("无法打开数据库")
+                        .kind(MessageDialogKind::Error)
+                        .show(|_| std::process::exit(1));
+                    return Ok(());
+                }
+            };
+            services::data_dir::finish_relocation(&data_dir);
+
+            // 清理超过保留天数的回收站数据
+            match services::trash::auto_purge(&database, &app_dir) {
+                Ok(0) => {}
+                Ok(count) => log::info!("已自动清理回收站中 {} 条过期数据", count),
+                Err(e) => log::warn!("自动清理回收站失败: {}", e),
+            }
 
             // 将数据库实例和 app_dir 注入到 Tauri 状态
             app.manage(database);
             app.manage(commands::AppDir(app_dir));
+            app.manage(data_dir);
+            app.manage(services::events::EventHub::new());
+            app.manage(api::ApiServer::default());
+            app.manage(services::jobs::JobQueue::default());
+
+            // 后台任务执行器：恢复上次中断的任务并执行新入队的任务
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let database = handle.state::<db::Database>();
+                let queue = handle.state::<services::jobs::JobQueue>();
+                let app_dir = handle.state::<commands::AppDir>().0.clone();
+                services::jobs::run_worker(&database, &app_dir, &handle, &queue).await;
+            });
+
+            // 按设置启动本地 API 服务
+            api::start_if_enabled(app.handle());
 
             log::info!("健康管家系统初始化完成");
             Ok(())
@@ -95,50 +169,46 @@ fn show_window(app: &AppHandle) {
         .expect("Can't Bring Window to Focus");
 }
 
+/// 测试指定的 AI 配置；未指定时测试任务（默认为 AI 分析）当前使用的配置
 #[tauri::command]
-async fn test_ai_connection(db: tauri::State<'_, db::Database>) -> Result<String, String> {
+async fn test_ai_connection(
+    profile_id: Option<String>,
+    task: Option<repo::ai_profile::AiTask>,
+    db: tauri::State<'_, db::Database>,
+) -> AppResult<String> {
     // 读取配置
-    let (config, model) = {
-        let conn = db.conn.lock().map_err(|e| e.to_string())?;
-        let config = services::http_client::load_ai_config(&conn)?;
-        let model = services::http_client::get_default_model(&conn);
-        (config, model)
-    };
-
-    // 构建 HTTP 客户端
-    let client = services::http_client::build_client(&config)?;
-
-    // 发送简单的测试请求
-    let request_body = serde_json::json!({
-        "model": model,
-        "messages": [
-            {
-                "role": "user",
-                "content": "Hi, this is a connection test. Reply with 'OK' only."
+    let mut config = {
+        let conn = db.read()?;
+        match profile_id {
+            Some(id) => {
+                let profile = repo::AiProfileRepo::new(&conn).get(&id)?;
+                services::http_client::client_config(&conn, profile)?
             }
-        ],
-        "max_tokens": 10,
-    });
-
-    let response = client
-        .post(&config.api_url)
-        .header("Authorization", format!("Bearer {}", config.api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| format!("连接失败: {}", e))?;
-
-    let status = response.status();
-    if status.is_success() {
-        Ok(format!("连接成功！模型: {}", model))
-    } else {
-        let body = response.text().await.unwrap_or_default();
-        Err(format!("API 返回错误 ({}): {}", status.as_u16(), body))
-    }
+            None => services::http_client::load_ai_config(&conn, task.unwrap_or(repo::ai_profile::AiTask::Analysis))?,
+        }
+    };
+    // 连接测试不重试，尽快反馈配置问题；只检查状态码，不要求模型输出内容
+    config.retry = services::http_client::RetryPolicy { max_attempts: 1 };
+    let client = services::provider::ChatClient::new(&config, config.timeouts.connection_test)?;
+    let request = services::provider::ChatRequest {
+        system: None,
+        messages: vec![services::provider::ChatMessage::user(
+            "Hi, this is a connection test. Reply with 'OK' only.",
+        )],
+        max_tokens: 10,
+        schema: None,
+    };
+    client.send(&request, false, |_| {}).await?;
+    Ok(format!("连接成功！模型: {}", config.model))
 }
 
 #[tauri::command]
 fn quit() {
     std::process::exit(0);
+}
+
+/// 重启程序（迁移数据目录后调用）
+#[tauri::command]
+fn restart_app(app: AppHandle) {
+    app.restart();
 }
\ No newline at end of file
@@ -0,0 +1,107 @@
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use crate::error::{AppResult, DbResultExt};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiAnalysis {
+    pub id: String,
+    pub record_id: String,
+    pub request_prompt: String,
+    pub response_content: String,
+    pub model_used: String,
+    pub status: String,
+    pub error_message: String,
+    pub created_at: String,
+}
+
+pub struct AiRepo<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> AiRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// 预创建一条处理中的分析记录
+    pub fn create(&self, record_id: &str, request_prompt: &str, model: &str) -> AppResult<String> {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.conn
+            .execute(
+                "INSERT INTO ai_analyses (id, record_id, request_prompt, response_content, model_used, status, error_message, created_at)
+                 VALUES (?1, ?2, ?3, '', ?4, 'processing', '', ?5)",
+                rusqlite::params![id, record_id, request_prompt, model, super::now()],
+            )
+            .db_context("创建分析记录失败")?;
+        Ok(id)
+    }
+
+    pub fn complete(&self, id: &str, response_content: &str) -> AppResult<()> {
+        self.conn
+            .execute(
+                "UPDATE ai_analyses SET response_content = ?1, status = 'success', error_message = '' WHERE id = ?2",
+                rusqlite::params![response_content, id],
+            )
+            .db_context("保存分析结果失败")?;
+        Ok(())
+    }
+
+    pub fn fail(&self, id: &str, error_message: &str) -> AppResult<()> {
+        self.conn
+            .execute(
+                "UPDATE ai_analyses SET status = 'failed', error_message = ?1 WHERE id = ?2",
+                rusqlite::params![error_message, id],
+            )
+            .db_context("保存分析结果失败")?;
+        Ok(())
+    }
+
+    /// 取消分析，保留已生成的部分内容
+    pub fn cancel(&self, id: &str, partial_content: &str) -> AppResult<()> {
+        self.conn
+            .execute(
+                "UPDATE ai_analyses SET response_content = ?1, status = 'cancelled', error_message = '已取消' WHERE id = ?2",
+                rusqlite::params![partial_content, id],
+            )
+            .db_context("保存分析结果失败")?;
+        Ok(())
+    }
+
+    /// 将记录上仍处于处理中的分析标记为失败（任务中断后调用）
+    pub fn fail_processing(&self, record_id: &str, error_message: &str) -> AppResult<usize> {
+        self.conn
+            .execute(
+                "UPDATE ai_analyses SET status = 'failed', error_message = ?1 WHERE record_id = ?2 AND status = 'processing'",
+                rusqlite::params![error_message, record_id],
+            )
+            .db_context("保存分析结果失败")
+    }
+
+    /// 某次检查记录的全部分析（最新在前）
+    pub fn list(&self, record_id: &str) -> AppResult<Vec<AiAnalysis>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT id, record_id, request_prompt, response_content, model_used, status, error_message, created_at
+                 FROM ai_analyses WHERE record_id = ?1
+                 ORDER BY created_at DESC"
+            )
+            .db_context("查询AI分析结果失败")?;
+
+        stmt.query_map([record_id], |row| {
+            Ok(AiAnalysis {
+                id: row.get(0)?,
+                record_id: row.get(1)?,
+                request_prompt: row.get(2)?,
+                response_content: row.get(3)?,
+                model_used: row.get(4)?,
+                status: row.get(5)?,
+                error_message: row.get(6)?,
+                created_at: row.get(7)?,
+            })
+        })
+        .db_context("查询失败")?
+        .collect::<Result<Vec<_>, _>>()
+        .db_context("解析数据失败")
+    }
+}
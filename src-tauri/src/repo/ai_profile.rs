@@ -0,0 +1,323 @@
+use rusqlite::{Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use crate::error::{AppError, AppResult, DbResultExt};
+use super::{audit, ConfigRepo};
+
+/// 使用 AI 的任务，每个任务对应一个服务配置
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AiTask {
+    /// 视觉模型识别检查报告
+    Ocr,
+    /// 文本模型综合分析
+    Analysis,
+}
+
+impl AiTask {
+    pub const ALL: [AiTask; 2] = [AiTask::Ocr, AiTask::Analysis];
+
+    /// 保存该任务所用配置 id 的配置项
+    pub fn config_key(self) -> &'static str {
+        match self {
+            AiTask::Ocr => "ai_profile_ocr",
+            AiTask::Analysis => "ai_profile_analysis",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AiTask::Ocr => "OCR 识别",
+            AiTask::Analysis => "AI 分析",
+        }
+    }
+}
+
+/// AI 服务的接口协议
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    /// OpenAI Chat Completions 及兼容接口
+    #[default]
+    Openai,
+    /// Anthropic Messages
+    Anthropic,
+    /// Google Gemini generateContent
+    Gemini,
+    /// Ollama 本地模型 /api/chat
+    Ollama,
+}
+
+impl ProviderKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProviderKind::Openai => "openai",
+            ProviderKind::Anthropic => "anthropic",
+            ProviderKind::Gemini => "gemini",
+            ProviderKind::Ollama => "ollama",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "anthropic" => ProviderKind::Anthropic,
+            "gemini" => ProviderKind::Gemini,
+            "ollama" => ProviderKind::Ollama,
+            _ => ProviderKind::Openai,
+        }
+    }
+}
+
+/// 一组 AI 服务配置：接口协议、接口地址、密钥、代理、模型与额外的请求参数
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiProfile {
+    pub id: String,
+    pub name: String,
+    pub provider: ProviderKind,
+    pub api_url: String,
+    pub api_key: String,
+    pub proxy_enabled: bool,
+    pub proxy_url: String,
+    pub proxy_username: String,
+    pub proxy_password: String,
+    pub model: String,
+    /// 合并到请求体的参数，如 temperature、max_tokens
+    pub parameters: serde_json::Map<String, serde_json::Value>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAiProfileInput {
+    pub name: String,
+    pub provider: Option<ProviderKind>,
+    pub api_url: String,
+    pub api_key: String,
+    pub proxy_enabled: Option<bool>,
+    pub proxy_url: Option<String>,
+    pub proxy_username: Option<String>,
+    pub proxy_password: Option<String>,
+    pub model: String,
+    pub parameters: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAiProfileInput {
+    pub id: String,
+    pub name: Option<String>,
+    pub provider: Option<ProviderKind>,
+    pub api_url: Option<String>,
+    pub api_key: Option<String>,
+    pub proxy_enabled: Option<bool>,
+    pub proxy_url: Option<String>,
+    pub proxy_username: Option<String>,
+    pub proxy_password: Option<String>,
+    pub model: Option<String>,
+    pub parameters: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// 各任务当前使用的配置 id
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct AiTaskProfiles {
+    pub ocr: Option<String>,
+    pub analysis: Option<String>,
+}
+
+const PROFILE_COLUMNS: &str = "id, name, api_url, api_key, proxy_enabled, proxy_url, proxy_username, proxy_password, \
+                               model, parameters, created_at, updated_at, provider";
+
+fn map_profile(row: &Row) -> rusqlite::Result<AiProfile> {
+    let parameters: String = row.get(9)?;
+    Ok(AiProfile {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        provider: ProviderKind::parse(&row.get::<_, String>(12)?),
+        api_url: row.get(2)?,
+        api_key: row.get(3)?,
+        proxy_enabled: row.get::<_, i32>(4)? != 0,
+        proxy_url: row.get(5)?,
+        proxy_username: row.get(6)?,
+        proxy_password: row.get(7)?,
+        model: row.get(8)?,
+        parameters: serde_json::from_str(&parameters).unwrap_or_default(),
+        created_at: row.get(10)?,
+        updated_at: row.get(11)?,
+    })
+}
+
+/// 请求体中由程序决定、不允许通过参数覆盖的字段，包括各接口的内容与结构化输出字段
+pub const RESERVED_PARAMETERS: &[&str] =
+    &["model", "messages", "contents", "stream", "response_format", "format", "tools", "tool_choice"];
+
+fn validate(name: &str, model: &str, parameters: &serde_json::Map<String, serde_json::Value>) -> AppResult<()> {
+    if name.trim().is_empty() {
+        return Err(AppError::Validation("配置名称不能为空".into()));
+    }
+    if model.trim().is_empty() {
+        return Err(AppError::Validation("模型名称不能为空".into()));
+    }
+    if let Some(key) = RESERVED_PARAMETERS.iter().find(|key| parameters.contains_key(**key)) {
+        return Err(AppError::Validation(format!("请求参数中不能包含 {}", key)));
+    }
+    Ok(())
+}
+
+pub struct AiProfileRepo<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> AiProfileRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    pub fn list(&self) -> AppResult<Vec<AiProfile>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT {} FROM ai_profiles ORDER BY created_at ASC", PROFILE_COLUMNS))
+            .db_context("查询 AI 配置失败")?;
+
+        stmt.query_map([], map_profile)
+            .db_context("查询 AI 配置失败")?
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("解析 AI 配置失败")
+    }
+
+    pub fn get(&self, id: &str) -> AppResult<AiProfile> {
+        self.find(id)?.ok_or_else(|| AppError::NotFound("AI 配置不存在".into()))
+    }
+
+    fn find(&self, id: &str) -> AppResult<Option<AiProfile>> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM ai_profiles WHERE id = ?1", PROFILE_COLUMNS),
+                [id],
+                map_profile,
+            )
+            .optional()
+            .db_context("查询 AI 配置失败")
+    }
+
+    /// 新建配置；还没有任何任务使用配置时，第一个配置同时用于全部任务
+    pub fn create(&self, input: CreateAiProfileInput) -> AppResult<AiProfile> {
+        let parameters = input.parameters.unwrap_or_default();
+        validate(&input.name, &input.model, &parameters)?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let now = super::now();
+        let tx = super::transaction(self.conn)?;
+        self.conn
+            .execute(
+                "INSERT INTO ai_profiles (id, name, provider, api_url, api_key, proxy_enabled, proxy_url, proxy_username,
+                                          proxy_password, model, parameters, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?12)",
+                rusqlite::params![
+                    id,
+                    input.name.trim(),
+                    input.provider.unwrap_or_default().as_str(),
+                    input.api_url.trim(),
+                    input.api_key.trim(),
+                    input.proxy_enabled.unwrap_or(false) as i32,
+                    input.proxy_url.unwrap_or_default(),
+                    input.proxy_username.unwrap_or_default(),
+                    input.proxy_password.unwrap_or_default(),
+                    input.model.trim(),
+                    serde_json::Value::Object(parameters).to_string(),
+                    now,
+                ],
+            )
+            .db_context("创建 AI 配置失败")?;
+        audit::record_create(self.conn, "ai_profiles", &id)?;
+
+        for task in AiTask::ALL {
+            if self.task_profile_id(task)?.is_none() {
+                self.set_task_profile(task, &id)?;
+            }
+        }
+        tx.commit()?;
+        self.get(&id)
+    }
+
+    pub fn update(&self, input: UpdateAiProfileInput) -> AppResult<AiProfile> {
+        let tx = super::transaction(self.conn)?;
+        let existing = self.get(&input.id)?;
+        let before = audit::snapshot(self.conn, "ai_profiles", &input.id)?;
+
+        let name = input.name.unwrap_or(existing.name);
+        let provider = input.provider.unwrap_or(existing.provider);
+        let api_url = input.api_url.unwrap_or(existing.api_url);
+        let api_key = input.api_key.unwrap_or(existing.api_key);
+        let proxy_enabled = input.proxy_enabled.unwrap_or(existing.proxy_enabled);
+        let proxy_url = input.proxy_url.unwrap_or(existing.proxy_url);
+        let proxy_username = input.proxy_username.unwrap_or(existing.proxy_username);
+        let proxy_password = input.proxy_password.unwrap_or(existing.proxy_password);
+        let model = input.model.unwrap_or(existing.model);
+        let parameters = input.parameters.unwrap_or(existing.parameters);
+        validate(&name, &model, &parameters)?;
+
+        self.conn
+            .execute(
+                "UPDATE ai_profiles SET name=?1, api_url=?2, api_key=?3, proxy_enabled=?4, proxy_url=?5, proxy_username=?6,
+                                        proxy_password=?7, model=?8, parameters=?9, updated_at=?10, provider=?12
+                 WHERE id=?11",
+                rusqlite::params![
+                    name.trim(),
+                    api_url.trim(),
+                    api_key.trim(),
+                    proxy_enabled as i32,
+                    proxy_url,
+                    proxy_username,
+                    proxy_password,
+                    model.trim(),
+                    serde_json::Value::Object(parameters).to_string(),
+                    super::now(),
+                    input.id,
+                    provider.as_str(),
+                ],
+            )
+            .db_context("更新 AI 配置失败")?;
+        audit::record_update(self.conn, "ai_profiles", &input.id, before)?;
+        tx.commit()?;
+        self.get(&input.id)
+    }
+
+    /// 删除配置；仍被任务使用时拒绝
+    pub fn delete(&self, id: &str) -> AppResult<()> {
+        let tx = super::transaction(self.conn)?;
+        let profile = self.get(id)?;
+        for task in AiTask::ALL {
+            if self.task_profile_id(task)?.as_deref() == Some(id) {
+                return Err(AppError::Validation(format!(
+                    "「{}」正在用于{}，请先为该任务选择其他配置",
+                    profile.name,
+                    task.label()
+                )));
+            }
+        }
+        audit::delete_rows(self.conn, "ai_profiles", "id = ?1", [id])?;
+        tx.commit()
+    }
+
+    fn task_profile_id(&self, task: AiTask) -> AppResult<Option<String>> {
+        Ok(ConfigRepo::new(self.conn).get(task.config_key())?.filter(|id| !id.is_empty()))
+    }
+
+    pub fn task_profiles(&self) -> AppResult<AiTaskProfiles> {
+        Ok(AiTaskProfiles {
+            ocr: self.task_profile_id(AiTask::Ocr)?,
+            analysis: self.task_profile_id(AiTask::Analysis)?,
+        })
+    }
+
+    /// 任务当前使用的配置；未选择或配置已被删除时返回 None
+    pub fn for_task(&self, task: AiTask) -> AppResult<Option<AiProfile>> {
+        match self.task_profile_id(task)? {
+            Some(id) => self.find(&id),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_task_profile(&self, task: AiTask, profile_id: &str) -> AppResult<()> {
+        self.get(profile_id)?;
+        ConfigRepo::new(self.conn).set(task.config_key(), profile_id)
+    }
+}
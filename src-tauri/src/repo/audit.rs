@@ -0,0 +1,404 @@
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{Connection, ErrorCode, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use crate::error::{AppError, AppResult, DbResultExt};
+
+pub const ACTION_CREATE: &str = "create";
+pub const ACTION_UPDATE: &str = "update";
+pub const ACTION_DELETE: &str = "delete";
+
+/// 写入审计日志的表，撤销时只允许操作这些表
+const AUDITED_TABLES: &[&str] = &[
+    "checkup_projects",
+    "indicators",
+    "checkup_records",
+    "checkup_files",
+    "ocr_results",
+    "ai_analyses",
+    "indicator_values",
+    "system_config",
+    "patients",
+    "ai_profiles",
+];
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AuditEntry {
+    pub id: String,
+    pub table_name: String,
+    pub row_id: String,
+    pub action: String,
+    /// 变更前的整行数据（新建时为空）
+    pub before: Option<Value>,
+    /// 变更后的整行数据（删除时为空）
+    pub after: Option<Value>,
+    pub created_at: String,
+    pub undone_at: Option<String>,
+    /// 由撤销操作产生时，指向被撤销的条目
+    pub undo_of: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct AuditFilter {
+    pub table_name: Option<String>,
+    pub row_id: Option<String>,
+    pub action: Option<String>,
+    /// 起始时间（含），RFC 3339
+    pub since: Option<String>,
+    /// 截止时间（不含），RFC 3339
+    pub until: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+const DEFAULT_LIMIT: u32 = 100;
+
+/// 快照中代替密钥的占位值，审计日志不保存明文密钥
+///
+/// 因此只修改密钥的保存不产生更新记录，撤销其他修改时也保留当前密钥。
+pub const REDACTED: &str = "***";
+/// 保存密钥的列
+const SECRET_COLUMNS: &[(&str, &str)] = &[("ai_profiles", "api_key"), ("ai_profiles", "proxy_password")];
+/// 值为密钥的配置项（ai_api_key 与 proxy_password 为旧版单组 AI 配置）
+pub const SECRET_CONFIG_KEYS: &[&str] = &["ai_api_key", "proxy_password", "api_server_token"];
+
+pub struct AuditRepo<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> AuditRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// 按条件查询审计日志（最新在前）
+    pub fn list(&self, filter: &AuditFilter) -> AppResult<Vec<AuditEntry>> {
+        let mut sql = String::from(
+            "SELECT id, table_name, row_id, action, before_json, after_json, created_at, undone_at, undo_of
+             FROM audit_log WHERE 1 = 1",
+        );
+        let mut params: Vec<SqlValue> = Vec::new();
+        let mut push = |clause: &str, value: &Option<String>| {
+            if let Some(v) = value.as_ref().filter(|v| !v.is_empty()) {
+                params.push(SqlValue::Text(v.clone()));
+                sql.push_str(&format!(" AND {} ?{}", clause, params.len()));
+            }
+        };
+        push("table_name =", &filter.table_name);
+        push("row_id =", &filter.row_id);
+        push("action =", &filter.action);
+        push("created_at >=", &filter.since);
+        push("created_at <", &filter.until);
+        sql.push_str(&format!(
+            " ORDER BY created_at DESC, rowid DESC LIMIT {} OFFSET {}",
+            filter.limit.unwrap_or(DEFAULT_LIMIT),
+            filter.offset.unwrap_or(0)
+        ));
+
+        let mut stmt = self.conn.prepare(&sql).db_context("查询审计日志失败")?;
+        stmt.query_map(rusqlite::params_from_iter(params), map_entry)
+            .db_context("查询审计日志失败")?
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("解析审计日志失败")
+    }
+
+    pub fn get(&self, id: &str) -> AppResult<AuditEntry> {
+        self.conn
+            .query_row(
+                "SELECT id, table_name, row_id, action, before_json, after_json, created_at, undone_at, undo_of
+                 FROM audit_log WHERE id = ?1",
+                [id],
+                map_entry,
+            )
+            .or_not_found("审计记录不存在")
+    }
+
+    /// 撤销单条变更，仅当该行此后未被再次修改时允许；撤销本身也会写入一条审计记录
+    pub fn undo(&self, id: &str) -> AppResult<AuditEntry> {
+        let entry = self.get(id)?;
+        if entry.undone_at.is_some() {
+            return Err(AppError::Conflict {
+                message: "该变更已撤销".into(),
+                details: serde_json::json!({ "audit_id": entry.id }),
+            });
+        }
+        let table = audited_table(&entry.table_name)?;
+
+        let tx = self.conn.unchecked_transaction().db_context("开启事务失败")?;
+        let current = snapshot(&tx, table, &entry.row_id)?;
+
+        let (action, before, after) = match entry.action.as_str() {
+            ACTION_CREATE | ACTION_UPDATE if current.is_none() => {
+                return Err(conflict("该数据已被删除，无法撤销", &entry));
+            }
+            ACTION_CREATE | ACTION_UPDATE
+                if without_updated_at(current.as_ref()) != without_updated_at(entry.after.as_ref()) => {
+                return Err(conflict("该数据在此之后已被修改，请先撤销后续变更", &entry));
+            }
+            ACTION_CREATE => {
+                tx.execute(&format!("DELETE FROM {} WHERE id = ?1", table), [&entry.row_id])
+                    .map_err(|e| constraint_conflict(e, "该数据已有关联数据，无法撤销创建", &entry))?;
+                (ACTION_DELETE, current, None)
+            }
+            ACTION_UPDATE => {
+                // 快照中没有密钥明文，撤销时保留当前的密钥
+                let mut restored = entry.before.clone().ok_or_else(|| corrupted(&entry))?;
+                unredact(table, &mut restored, true);
+                write_row(&tx, table, &restored, true)
+                    .map_err(|e| constraint_conflict(e, "撤销后数据与现有数据冲突", &entry))?;
+                (ACTION_UPDATE, current, snapshot(&tx, table, &entry.row_id)?)
+            }
+            ACTION_DELETE if current.is_some() => {
+                return Err(conflict("该数据已存在，无法撤销删除", &entry));
+            }
+            ACTION_DELETE => {
+                // 恢复的行没有密钥，需要重新填写
+                let mut restored = entry.before.clone().ok_or_else(|| corrupted(&entry))?;
+                unredact(table, &mut restored, false);
+                write_row(&tx, table, &restored, false)
+                    .map_err(|e| constraint_conflict(e, "关联的上级数据已不存在，请先撤销其删除", &entry))?;
+                (ACTION_CREATE, None, snapshot(&tx, table, &entry.row_id)?)
+            }
+            _ => return Err(corrupted(&entry)),
+        };
+
+        let undo_id = insert(&tx, table, &entry.row_id, action, before.as_ref(), after.as_ref(), Some(&entry.id))?;
+        tx.execute(
+            "UPDATE audit_log SET undone_at = ?1 WHERE id = ?2",
+            rusqlite::params![super::now(), entry.id],
+        )
+        .db_context("更新审计日志失败")?;
+        tx.commit().db_context("提交撤销失败")?;
+
+        self.get(&undo_id)
+    }
+}
+
+/// 读取整行数据为 JSON 对象，密钥以 [`REDACTED`] 代替
+pub(crate) fn snapshot(conn: &Connection, table: &str, row_id: &str) -> AppResult<Option<Value>> {
+    let row = conn
+        .query_row(&format!("SELECT * FROM {} WHERE id = ?1", table), [row_id], row_to_json)
+        .optional()
+        .db_context("读取数据快照失败")?;
+    Ok(row.map(|row| redact(table, row)))
+}
+
+/// 快照中需要隐藏的字段：密钥列，以及密钥配置项的值
+fn secret_fields(table: &str, row: &Value) -> Vec<&'static str> {
+    let mut fields: Vec<&'static str> =
+        SECRET_COLUMNS.iter().filter(|(t, _)| *t == table).map(|(_, column)| *column).collect();
+    if table == "system_config"
+        && row["config_key"].as_str().is_some_and(|key| SECRET_CONFIG_KEYS.contains(&key))
+    {
+        fields.push("config_value");
+    }
+    fields
+}
+
+/// 将非空的密钥替换为占位值，空值保持不变以便看出是否填写
+fn redact(table: &str, mut row: Value) -> Value {
+    for field in secret_fields(table, &row) {
+        if let Some(value) = row.get_mut(field)
+            && value.as_str().is_some_and(|s| !s.is_empty())
+        {
+            *value = Value::from(REDACTED);
+        }
+    }
+    row
+}
+
+/// 撤销前处理快照中的占位值：更新时不写这些字段（保留当前密钥），插入时写为空
+fn unredact(table: &str, row: &mut Value, update: bool) {
+    for field in secret_fields(table, row) {
+        let Some(object) = row.as_object_mut() else {
+            return;
+        };
+        if object.get(field).and_then(Value::as_str) != Some(REDACTED) {
+            continue;
+        }
+        if update {
+            object.remove(field);
+        } else {
+            object.insert(field.to_string(), Value::from(""));
+        }
+    }
+}
+
+/// 记录新建的行
+pub(crate) fn record_create(conn: &Connection, table: &str, row_id: &str) -> AppResult<()> {
+    let after = snapshot(conn, table, row_id)?;
+    insert(conn, table, row_id, ACTION_CREATE, None, after.as_ref(), None)?;
+    Ok(())
+}
+
+/// 记录更新，`before` 为更新前的快照；内容未变化时不记录
+pub(crate) fn record_update(conn: &Connection, table: &str, row_id: &str, before: Option<Value>) -> AppResult<()> {
+    let after = snapshot(conn, table, row_id)?;
+    if without_updated_at(before.as_ref()) != without_updated_at(after.as_ref()) {
+        insert(conn, table, row_id, ACTION_UPDATE, before.as_ref(), after.as_ref(), None)?;
+    }
+    Ok(())
+}
+
+/// 仅 updated_at 变化的保存不算修改
+fn without_updated_at(row: Option<&Value>) -> Option<Value> {
+    let mut row = row.cloned()?;
+    if let Some(obj) = row.as_object_mut() {
+        obj.remove("updated_at");
+    }
+    Some(row)
+}
+
+/// 删除满足条件的行，并为每一行写入删除记录，返回删除的行数
+pub(crate) fn delete_rows(
+    conn: &Connection,
+    table: &str,
+    condition: &str,
+    params: impl rusqlite::Params + Clone,
+) -> AppResult<usize> {
+    let rows = {
+        let mut stmt = conn
+            .prepare(&format!("SELECT * FROM {} WHERE {}", table, condition))
+            .db_context("读取数据快照失败")?;
+        stmt.query_map(params.clone(), row_to_json)
+            .db_context("读取数据快照失败")?
+            .map(|row| row.map(|row| redact(table, row)))
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("读取数据快照失败")?
+    };
+
+    conn.execute(&format!("DELETE FROM {} WHERE {}", table, condition), params)
+        .map_err(|e| AppError::database(&format!("删除 {} 失败", table), e))?;
+
+    for row in &rows {
+        let row_id = row["id"].as_str().unwrap_or_default();
+        insert(conn, table, row_id, ACTION_DELETE, Some(row), None, None)?;
+    }
+    Ok(rows.len())
+}
+
+fn insert(
+    conn: &Connection,
+    table: &str,
+    row_id: &str,
+    action: &str,
+    before: Option<&Value>,
+    after: Option<&Value>,
+    undo_of: Option<&str>,
+) -> AppResult<String> {
+    let id = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO audit_log (id, table_name, row_id, action, before_json, after_json, created_at, undo_of)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        rusqlite::params![
+            id,
+            table,
+            row_id,
+            action,
+            before.map(Value::to_string),
+            after.map(Value::to_string),
+            super::now(),
+            undo_of,
+        ],
+    )
+    .db_context("写入审计日志失败")?;
+    Ok(id)
+}
+
+/// 按快照写回整行：`update` 为 true 时按 id 更新，否则插入
+fn write_row(conn: &Connection, table: &str, row: &Value, update: bool) -> rusqlite::Result<usize> {
+    let columns: Vec<(&String, SqlValue)> = row
+        .as_object()
+        .map(|obj| obj.iter().map(|(k, v)| (k, json_to_sql(v))).collect())
+        .unwrap_or_default();
+    let names: Vec<&str> = columns.iter().map(|(k, _)| k.as_str()).collect();
+    let values = columns.iter().map(|(_, v)| v);
+
+    let sql = if update {
+        let sets: Vec<String> = names.iter().enumerate().map(|(i, n)| format!("{} = ?{}", n, i + 1)).collect();
+        format!(
+            "UPDATE {} SET {} WHERE id = ?{}",
+            table,
+            sets.join(", "),
+            names.iter().position(|n| *n == "id").map(|i| i + 1).unwrap_or(0)
+        )
+    } else {
+        let placeholders: Vec<String> = (1..=names.len()).map(|i| format!("?{}", i)).collect();
+        format!("INSERT INTO {} ({}) VALUES ({})", table, names.join(", "), placeholders.join(", "))
+    };
+    conn.execute(&sql, rusqlite::params_from_iter(values))
+}
+
+fn row_to_json(row: &Row) -> rusqlite::Result<Value> {
+    let mut obj = Map::new();
+    for (i, name) in row.as_ref().column_names().iter().enumerate() {
+        let value = match row.get_ref(i)? {
+            ValueRef::Null => Value::Null,
+            ValueRef::Integer(n) => Value::from(n),
+            ValueRef::Real(f) => Value::from(f),
+            ValueRef::Text(t) => Value::from(String::from_utf8_lossy(t).into_owned()),
+            ValueRef::Blob(b) => Value::from(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, b)),
+        };
+        obj.insert(name.to_string(), value);
+    }
+    Ok(Value::Object(obj))
+}
+
+fn json_to_sql(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => n
+            .as_i64()
+            .map(SqlValue::Integer)
+            .unwrap_or_else(|| SqlValue::Real(n.as_f64().unwrap_or_default())),
+        Value::String(s) => SqlValue::Text(s.clone()),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+fn map_entry(row: &Row) -> rusqlite::Result<AuditEntry> {
+    let parse = |json: Option<String>| json.and_then(|s| serde_json::from_str(&s).ok());
+    Ok(AuditEntry {
+        id: row.get(0)?,
+        table_name: row.get(1)?,
+        row_id: row.get(2)?,
+        action: row.get(3)?,
+        before: parse(row.get(4)?),
+        after: parse(row.get(5)?),
+        created_at: row.get(6)?,
+        undone_at: row.get(7)?,
+        undo_of: row.get(8)?,
+    })
+}
+
+fn audited_table(name: &str) -> AppResult<&'static str> {
+    AUDITED_TABLES
+        .iter()
+        .copied()
+        .find(|t| *t == name)
+        .ok_or_else(|| AppError::Validation(format!("不支持撤销的数据表: {}", name)))
+}
+
+fn conflict(message: &str, entry: &AuditEntry) -> AppError {
+    AppError::Conflict {
+        message: message.to_string(),
+        details: serde_json::json!({
+            "audit_id": entry.id,
+            "table_name": entry.table_name,
+            "row_id": entry.row_id,
+        }),
+    }
+}
+
+/// 约束冲突（外键、唯一键）视为无法撤销，其余为数据库错误
+fn constraint_conflict(err: rusqlite::Error, message: &str, entry: &AuditEntry) -> AppError {
+    match err.sqlite_error_code() {
+        Some(ErrorCode::ConstraintViolation) => conflict(message, entry),
+        _ => AppError::database("撤销变更失败", err),
+    }
+}
+
+fn corrupted(entry: &AuditEntry) -> AppError {
+    AppError::Internal(format!("审计记录数据不完整: {}", entry.id))
+}
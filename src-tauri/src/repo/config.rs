@@ -0,0 +1,70 @@
+use rusqlite::{Connection, OptionalExtension};
+use crate::error::{AppResult, DbResultExt};
+use super::audit;
+
+pub struct ConfigRepo<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> ConfigRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// 读取配置，未设置时返回 None
+    pub fn get(&self, key: &str) -> AppResult<Option<String>> {
+        self.conn
+            .query_row(
+                "SELECT config_value FROM system_config WHERE config_key = ?1",
+                [key],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .db_context("读取配置失败")
+    }
+
+    /// 读取配置，未设置或为空时使用默认值
+    pub fn get_or(&self, key: &str, default: &str) -> AppResult<String> {
+        Ok(self
+            .get(key)?
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| default.to_string()))
+    }
+
+    pub fn set(&self, key: &str, value: &str) -> AppResult<()> {
+        let tx = super::transaction(self.conn)?;
+        let existing_id = self.id_of(key)?;
+        let before = match &existing_id {
+            Some(id) => audit::snapshot(self.conn, "system_config", id)?,
+            None => None,
+        };
+
+        self.conn
+            .execute(
+                "INSERT INTO system_config (id, config_key, config_value, updated_at)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(config_key) DO UPDATE SET
+                    config_value = excluded.config_value,
+                    updated_at = excluded.updated_at",
+                rusqlite::params![uuid::Uuid::new_v4().to_string(), key, value, super::now()],
+            )
+            .db_context("保存配置失败")?;
+
+        match existing_id {
+            Some(id) => audit::record_update(self.conn, "system_config", &id, before)?,
+            None => {
+                if let Some(id) = self.id_of(key)? {
+                    audit::record_create(self.conn, "system_config", &id)?;
+                }
+            }
+        }
+        tx.commit()
+    }
+
+    fn id_of(&self, key: &str) -> AppResult<Option<String>> {
+        self.conn
+            .query_row("SELECT id FROM system_config WHERE config_key = ?1", [key], |row| row.get(0))
+            .optional()
+            .db_context("读取配置失败")
+    }
+}
@@ -0,0 +1,196 @@
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use crate::error::{AppError, AppResult, DbResultExt};
+use super::{audit, trash};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CheckupFile {
+    pub id: String,
+    pub record_id: String,
+    pub project_id: String,
+    pub project_name: Option<String>,
+    pub original_filename: String,
+    pub stored_path: String,
+    pub file_size: i64,
+    pub mime_type: String,
+    pub uploaded_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UploadFileInput {
+    pub record_id: String,
+    pub project_id: String,
+    pub checkup_date: String,
+    /// Base64 编码的文件内容
+    pub file_data: String,
+    pub filename: String,
+}
+
+/// 待登记的文件（物理文件已写入数据目录）
+pub struct NewCheckupFile {
+    pub id: String,
+    pub record_id: String,
+    pub project_id: String,
+    pub project_name: String,
+    pub original_filename: String,
+    /// 相对数据目录的路径
+    pub stored_path: String,
+    pub file_size: i64,
+    pub mime_type: String,
+}
+
+pub struct FileRepo<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> FileRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// 登记文件，并将记录状态从 pending_upload 推进到 pending_ocr
+    pub fn insert(&self, file: NewCheckupFile) -> AppResult<CheckupFile> {
+        let now = super::now();
+
+        let tx = super::transaction(self.conn)?;
+        self.conn
+            .execute(
+                "INSERT INTO checkup_files (id, record_id, project_id, original_filename, stored_path, file_size, mime_type, uploaded_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                rusqlite::params![file.id, file.record_id, file.project_id, file.original_filename, file.stored_path, file.file_size, file.mime_type, now],
+            )
+            .db_context("保存文件记录失败")?;
+        audit::record_create(self.conn, "checkup_files", &file.id)?;
+
+        super::RecordRepo::new(self.conn).advance_status(&file.record_id, "pending_upload", "pending_ocr")?;
+        tx.commit()?;
+
+        Ok(CheckupFile {
+            id: file.id,
+            record_id: file.record_id,
+            project_id: file.project_id,
+            project_name: Some(file.project_name),
+            original_filename: file.original_filename,
+            stored_path: file.stored_path,
+            file_size: file.file_size,
+            mime_type: file.mime_type,
+            uploaded_at: now,
+        })
+    }
+
+    /// 获取某次检查记录的所有文件
+    pub fn list(&self, record_id: &str) -> AppResult<Vec<CheckupFile>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT f.id, f.record_id, f.project_id, p.name, f.original_filename, f.stored_path, f.file_size, f.mime_type, f.uploaded_at
+                 FROM checkup_files f
+                 LEFT JOIN checkup_projects p ON f.project_id = p.id
+                 WHERE f.record_id = ?1 AND f.deleted_at IS NULL
+                 ORDER BY p.name ASC, f.uploaded_at ASC"
+            )
+            .db_context("查询文件失败")?;
+
+        stmt.query_map([record_id], |row| {
+            Ok(CheckupFile {
+                id: row.get(0)?,
+                record_id: row.get(1)?,
+                project_id: row.get(2)?,
+                project_name: row.get(3)?,
+                original_filename: row.get(4)?,
+                stored_path: row.get(5)?,
+                file_size: row.get(6)?,
+                mime_type: row.get(7)?,
+                uploaded_at: row.get(8)?,
+            })
+        })
+        .db_context("查询文件失败")?
+        .collect::<Result<Vec<_>, _>>()
+        .db_context("解析文件数据失败")
+    }
+
+    /// 检查记录中是否已有同名且大小相同的文件
+    pub fn contains(&self, record_id: &str, project_id: &str, original_filename: &str, file_size: i64) -> AppResult<bool> {
+        self.conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM checkup_files
+                 WHERE record_id = ?1 AND project_id = ?2 AND original_filename = ?3 AND file_size = ?4 AND deleted_at IS NULL)",
+                rusqlite::params![record_id, project_id, original_filename, file_size],
+                |row| row.get(0),
+            )
+            .db_context("查询文件失败")
+    }
+
+    /// 确认文件所属的检查记录属于当前成员，其他成员的文件按不存在处理
+    pub fn ensure_active(&self, file_id: &str) -> AppResult<()> {
+        let patient_id: String = self
+            .conn
+            .query_row(
+                "SELECT r.patient_id FROM checkup_files f
+                 JOIN checkup_records r ON r.id = f.record_id
+                 WHERE f.id = ?1 AND f.deleted_at IS NULL AND r.deleted_at IS NULL",
+                [file_id],
+                |row| row.get(0),
+            )
+            .or_not_found("文件不存在")?;
+        if patient_id != super::PatientRepo::new(self.conn).active_id()? {
+            return Err(AppError::NotFound("文件不存在".into()));
+        }
+        Ok(())
+    }
+
+    /// 文件的存储路径与 MIME 类型
+    pub fn location(&self, file_id: &str) -> AppResult<(String, String)> {
+        self.conn
+            .query_row(
+                "SELECT stored_path, mime_type FROM checkup_files WHERE id = ?1 AND deleted_at IS NULL",
+                [file_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .or_not_found("文件不存在")
+    }
+
+    /// 将位于 `base_dir` 下的绝对路径改写为相对路径，返回改写的条数
+    pub fn relativize_paths(&self, base_dir: &str) -> AppResult<usize> {
+        let base = base_dir.trim_end_matches(['/', '\\']);
+        if base.is_empty() {
+            return Ok(0);
+        }
+        // substr 按字符计数
+        let len = base.chars().count() as i64;
+        self.conn
+            .execute(
+                "UPDATE checkup_files SET stored_path = substr(stored_path, ?1 + 2)
+                 WHERE substr(stored_path, 1, ?1) = ?2 AND substr(stored_path, ?1 + 1, 1) IN ('/', '\\')",
+                rusqlite::params![len, base],
+            )
+            .db_context("改写文件路径失败")
+    }
+
+    /// 将文件移入回收站，物理文件保留到彻底删除时
+    pub fn delete(&self, file_id: &str) -> AppResult<()> {
+        if !trash::set_deleted(self.conn, "checkup_files", file_id, true)? {
+            return Err(AppError::NotFound("文件不存在".into()));
+        }
+        Ok(())
+    }
+
+    /// 彻底删除文件记录及其 OCR 结果与指标值，返回存储路径供调用方删除物理文件
+    pub(crate) fn purge(&self, file_id: &str) -> AppResult<String> {
+        let stored_path: String = self
+            .conn
+            .query_row("SELECT stored_path FROM checkup_files WHERE id = ?1", [file_id], |row| row.get(0))
+            .or_not_found("文件不存在")?;
+
+        audit::delete_rows(
+            self.conn,
+            "indicator_values",
+            "ocr_result_id IN (SELECT id FROM ocr_results WHERE file_id = ?1)",
+            [file_id],
+        )?;
+        audit::delete_rows(self.conn, "ocr_results", "file_id = ?1", [file_id])?;
+        audit::delete_rows(self.conn, "checkup_files", "id = ?1", [file_id])?;
+
+        Ok(stored_path)
+    }
+}
@@ -0,0 +1,186 @@
+use rusqlite::{Connection, Row};
+use serde::{Deserialize, Serialize};
+use crate::error::{AppError, AppResult, DbResultExt};
+use super::audit;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Indicator {
+    pub id: String,
+    pub project_id: String,
+    pub name: String,
+    pub unit: String,
+    pub reference_range: String,
+    pub sort_order: i32,
+    pub is_core: bool,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateIndicatorInput {
+    pub project_id: String,
+    pub name: String,
+    pub unit: Option<String>,
+    pub reference_range: Option<String>,
+    pub is_core: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateIndicatorInput {
+    pub id: String,
+    pub name: Option<String>,
+    pub unit: Option<String>,
+    pub reference_range: Option<String>,
+    pub is_core: Option<bool>,
+    pub sort_order: Option<i32>,
+}
+
+const INDICATOR_COLUMNS: &str = "id, project_id, name, unit, reference_range, sort_order, is_core, created_at";
+
+fn map_indicator(row: &Row) -> rusqlite::Result<Indicator> {
+    Ok(Indicator {
+        id: row.get(0)?,
+        project_id: row.get(1)?,
+        name: row.get(2)?,
+        unit: row.get(3)?,
+        reference_range: row.get(4)?,
+        sort_order: row.get(5)?,
+        is_core: row.get::<_, i32>(6)? == 1,
+        created_at: row.get(7)?,
+    })
+}
+
+pub struct IndicatorRepo<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> IndicatorRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    pub fn list(&self, project_id: &str) -> AppResult<Vec<Indicator>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {} FROM indicators WHERE project_id = ?1 ORDER BY sort_order ASC, created_at ASC",
+                INDICATOR_COLUMNS
+            ))
+            .db_context("查询指标失败")?;
+
+        stmt.query_map([project_id], map_indicator)
+            .db_context("查询指标失败")?
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("解析指标数据失败")
+    }
+
+    /// 全部项目的指标（OCR 结果匹配指标时使用）
+    pub fn list_all(&self) -> AppResult<Vec<Indicator>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT {} FROM indicators", INDICATOR_COLUMNS))
+            .db_context("查询指标失败")?;
+
+        stmt.query_map([], map_indicator)
+            .db_context("查询指标失败")?
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("解析指标数据失败")
+    }
+
+    pub fn get(&self, id: &str) -> AppResult<Indicator> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM indicators WHERE id = ?1", INDICATOR_COLUMNS),
+                [id],
+                map_indicator,
+            )
+            .or_not_found("指标不存在")
+    }
+
+    pub fn create(&self, input: CreateIndicatorInput) -> AppResult<Indicator> {
+        let now = super::now();
+        let id = uuid::Uuid::new_v4().to_string();
+        let unit = input.unit.unwrap_or_default();
+        let reference_range = input.reference_range.unwrap_or_default();
+        let is_core = input.is_core.unwrap_or(false);
+
+        let tx = super::transaction(self.conn)?;
+        self.conn
+            .execute(
+                "INSERT INTO indicators (id, project_id, name, unit, reference_range, sort_order, is_core, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6, ?7)",
+                rusqlite::params![id, input.project_id, input.name, unit, reference_range, is_core as i32, now],
+            )
+            .db_context("创建指标失败")?;
+        audit::record_create(self.conn, "indicators", &id)?;
+        tx.commit()?;
+
+        Ok(Indicator {
+            id,
+            project_id: input.project_id,
+            name: input.name,
+            unit,
+            reference_range,
+            sort_order: 0,
+            is_core,
+            created_at: now,
+        })
+    }
+
+    pub fn update(&self, input: UpdateIndicatorInput) -> AppResult<Indicator> {
+        let tx = super::transaction(self.conn)?;
+        let existing = self.get(&input.id)?;
+        let before = audit::snapshot(self.conn, "indicators", &input.id)?;
+
+        let name = input.name.unwrap_or(existing.name);
+        let unit = input.unit.unwrap_or(existing.unit);
+        let reference_range = input.reference_range.unwrap_or(existing.reference_range);
+        let is_core = input.is_core.unwrap_or(existing.is_core);
+        let sort_order = input.sort_order.unwrap_or(existing.sort_order);
+
+        self.conn
+            .execute(
+                "UPDATE indicators SET name=?1, unit=?2, reference_range=?3, is_core=?4, sort_order=?5 WHERE id=?6",
+                rusqlite::params![name, unit, reference_range, is_core as i32, sort_order, input.id],
+            )
+            .db_context("更新指标失败")?;
+        audit::record_update(self.conn, "indicators", &input.id, before)?;
+        tx.commit()?;
+
+        Ok(Indicator {
+            id: input.id,
+            project_id: existing.project_id,
+            name,
+            unit,
+            reference_range,
+            sort_order,
+            is_core,
+            created_at: existing.created_at,
+        })
+    }
+
+    /// 删除指标；已有历史数据时拒绝删除
+    pub fn delete(&self, id: &str) -> AppResult<()> {
+        let tx = super::transaction(self.conn)?;
+        let value_count: i64 = self
+            .conn
+            .query_row(
+                "SELECT COUNT(*) FROM indicator_values WHERE indicator_id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .db_context("查询失败")?;
+
+        if value_count > 0 {
+            return Err(AppError::has_dependents(
+                format!("该指标有 {} 条历史数据，无法删除。", value_count),
+                "indicator_values",
+                value_count,
+            ));
+        }
+
+        audit::delete_rows(self.conn, "indicators", "id = ?1", [id])?;
+        tx.commit()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use rusqlite::Connection;
+use serde::Serialize;
+use crate::error::{AppResult, DbResultExt};
+use super::audit;
+
+/// 引用了不存在的上级数据的行（来自 `PRAGMA foreign_key_check`）
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct DanglingReference {
+    pub table_name: String,
+    pub row_id: String,
+    /// 外键列，如 indicator_values.indicator_id
+    pub column: String,
+    pub parent_table: String,
+    pub parent_id: String,
+}
+
+/// 数据库中登记的文件（含回收站中的文件）
+#[derive(Debug, Serialize, Clone)]
+pub struct StoredFile {
+    pub id: String,
+    pub record_id: String,
+    pub original_filename: String,
+    pub stored_path: String,
+    pub trashed: bool,
+}
+
+pub struct IntegrityRepo<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> IntegrityRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// `PRAGMA integrity_check` 发现的问题，数据库正常时为空
+    pub fn sqlite_check(&self) -> AppResult<Vec<String>> {
+        let mut stmt = self.conn.prepare("PRAGMA integrity_check").db_context("数据库完整性检查失败")?;
+        let messages = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .db_context("数据库完整性检查失败")?
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("数据库完整性检查失败")?;
+        Ok(messages.into_iter().filter(|m| m != "ok").collect())
+    }
+
+    /// 重建全部索引，用于修复索引损坏
+    pub fn reindex(&self) -> AppResult<()> {
+        self.conn.execute_batch("REINDEX").db_context("重建索引失败")
+    }
+
+    pub fn dangling_references(&self) -> AppResult<Vec<DanglingReference>> {
+        dangling_references(self.conn)
+    }
+
+    /// 删除引用缺失数据的行，连同因此失去上级的下级数据，返回删除的行数
+    pub fn delete_dangling(&self) -> AppResult<usize> {
+        let tx = self.conn.unchecked_transaction().db_context("开启事务失败")?;
+        // 外键检查推迟到提交时，删除顺序不受父子关系限制
+        tx.pragma_update(None, "defer_foreign_keys", true).db_context("开启事务失败")?;
+
+        let mut deleted = 0;
+        loop {
+            let dangling = dangling_references(&tx)?;
+            if dangling.is_empty() {
+                break;
+            }
+            for reference in &dangling {
+                deleted += audit::delete_rows(&tx, &reference.table_name, "id = ?1", [&reference.row_id])?;
+            }
+        }
+
+        tx.commit().db_context("提交修复失败")?;
+        Ok(deleted)
+    }
+
+    /// 全部登记的文件
+    pub fn stored_files(&self) -> AppResult<Vec<StoredFile>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT id, record_id, original_filename, stored_path, deleted_at IS NOT NULL
+                 FROM checkup_files ORDER BY uploaded_at ASC"
+            )
+            .db_context("查询文件失败")?;
+
+        stmt.query_map([], |row| {
+            Ok(StoredFile {
+                id: row.get(0)?,
+                record_id: row.get(1)?,
+                original_filename: row.get(2)?,
+                stored_path: row.get(3)?,
+                trashed: row.get(4)?,
+            })
+        })
+        .db_context("查询文件失败")?
+        .collect::<Result<Vec<_>, _>>()
+        .db_context("解析文件数据失败")
+    }
+
+    /// 全部项目名称（含回收站中的项目），对应 pictures/ 下的一级目录
+    pub fn project_names(&self) -> AppResult<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT name FROM checkup_projects").db_context("查询项目失败")?;
+        stmt.query_map([], |row| row.get(0))
+            .db_context("查询项目失败")?
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("解析项目数据失败")
+    }
+}
+
+fn dangling_references(conn: &Connection) -> AppResult<Vec<DanglingReference>> {
+    let violations = {
+        let mut stmt = conn.prepare("PRAGMA foreign_key_check").db_context("外键检查失败")?;
+        stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(2)?, row.get::<_, i64>(3)?))
+        })
+        .db_context("外键检查失败")?
+        .collect::<Result<Vec<_>, _>>()
+        .db_context("外键检查失败")?
+    };
+
+    violations
+        .into_iter()
+        .map(|(table, rowid, parent_table, fk_id)| {
+            let column: String = conn
+                .query_row(
+                    "SELECT \"from\" FROM pragma_foreign_key_list(?1) WHERE id = ?2",
+                    rusqlite::params![table, fk_id],
+                    |row| row.get(0),
+                )
+                .db_context("读取外键定义失败")?;
+            let (row_id, parent_id): (String, String) = conn
+                .query_row(
+                    &format!("SELECT id, {} FROM {} WHERE rowid = ?1", column, table),
+                    [rowid],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .db_context("读取数据失败")?;
+            Ok(DanglingReference {
+                table_name: table,
+                row_id,
+                column,
+                parent_table,
+                parent_id,
+            })
+        })
+        .collect()
+}
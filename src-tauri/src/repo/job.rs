@@ -0,0 +1,346 @@
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use crate::error::{AppError, AppResult, DbResultExt};
+
+/// 程序中断后最多重新执行的次数，超过后标记为失败，避免反复崩溃
+pub const MAX_ATTEMPTS: i32 = 3;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Ocr,
+    AiAnalysis,
+}
+
+impl JobKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobKind::Ocr => "ocr",
+            JobKind::AiAnalysis => "ai_analysis",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "ocr" => JobKind::Ocr,
+            _ => JobKind::AiAnalysis,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "queued" => JobStatus::Queued,
+            "running" => JobStatus::Running,
+            "succeeded" => JobStatus::Succeeded,
+            "cancelled" => JobStatus::Cancelled,
+            _ => JobStatus::Failed,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Job {
+    pub id: String,
+    pub kind: JobKind,
+    pub record_id: String,
+    pub status: JobStatus,
+    /// 已开始执行的次数（含被中断的执行）
+    pub attempts: i32,
+    pub progress_done: i64,
+    pub progress_total: i64,
+    pub last_error: Option<String>,
+    /// AI 分析任务对应的 ai_analyses.id
+    pub result_id: Option<String>,
+    pub created_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct JobFilter {
+    pub record_id: Option<String>,
+    pub kind: Option<JobKind>,
+    pub status: Option<JobStatus>,
+    pub limit: Option<u32>,
+}
+
+const DEFAULT_LIMIT: u32 = 100;
+
+const JOB_COLUMNS: &str = "id, kind, record_id, status, attempts, progress_done, progress_total, last_error, result_id, \
+                           created_at, started_at, finished_at, updated_at";
+
+fn map_job(row: &Row) -> rusqlite::Result<Job> {
+    Ok(Job {
+        id: row.get(0)?,
+        kind: JobKind::parse(&row.get::<_, String>(1)?),
+        record_id: row.get(2)?,
+        status: JobStatus::parse(&row.get::<_, String>(3)?),
+        attempts: row.get(4)?,
+        progress_done: row.get(5)?,
+        progress_total: row.get(6)?,
+        last_error: row.get(7)?,
+        result_id: row.get(8)?,
+        created_at: row.get(9)?,
+        started_at: row.get(10)?,
+        finished_at: row.get(11)?,
+        updated_at: row.get(12)?,
+    })
+}
+
+/// 任务属于运行状态，不写审计日志
+pub struct JobRepo<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> JobRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// 新建排队中的任务；同一记录已有未完成的同类任务时拒绝
+    pub fn enqueue(&self, kind: JobKind, record_id: &str) -> AppResult<Job> {
+        if let Some(active) = self.active_for(kind, record_id)? {
+            return Err(AppError::Busy(format!(
+                "该检查记录已有未完成的{}任务（{}）",
+                if kind == JobKind::Ocr { "识别" } else { "分析" },
+                active.id
+            )));
+        }
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let now = super::now();
+        self.conn
+            .execute(
+                "INSERT INTO jobs (id, kind, record_id, status, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, 'queued', ?4, ?4)",
+                rusqlite::params![id, kind.as_str(), record_id, now],
+            )
+            .db_context("创建任务失败")?;
+        self.get(&id)
+    }
+
+    pub fn get(&self, id: &str) -> AppResult<Job> {
+        self.conn
+            .query_row(&format!("SELECT {} FROM jobs WHERE id = ?1", JOB_COLUMNS), [id], map_job)
+            .or_not_found("任务不存在")
+    }
+
+    /// 按条件查询任务（最新在前）；指定 `patient_id` 时只查询该成员检查记录上的任务
+    pub fn list(&self, patient_id: Option<&str>, filter: &JobFilter) -> AppResult<Vec<Job>> {
+        let mut sql = format!("SELECT {} FROM jobs WHERE 1 = 1", JOB_COLUMNS);
+        let mut params: Vec<SqlValue> = Vec::new();
+        if let Some(patient_id) = patient_id {
+            params.push(SqlValue::Text(patient_id.to_string()));
+            sql.push_str(" AND record_id IN (SELECT id FROM checkup_records WHERE patient_id = ?1)");
+        }
+        let mut push = |clause: &str, value: Option<String>| {
+            if let Some(v) = value {
+                params.push(SqlValue::Text(v));
+                sql.push_str(&format!(" AND {} ?{}", clause, params.len()));
+            }
+        };
+        push("record_id =", filter.record_id.clone().filter(|v| !v.is_empty()));
+        push("kind =", filter.kind.map(|k| k.as_str().to_string()));
+        push("status =", filter.status.map(|s| s.as_str().to_string()));
+        sql.push_str(&format!(
+            " ORDER BY created_at DESC, rowid DESC LIMIT {}",
+            filter.limit.unwrap_or(DEFAULT_LIMIT)
+        ));
+
+        let mut stmt = self.conn.prepare(&sql).db_context("查询任务失败")?;
+        stmt.query_map(rusqlite::params_from_iter(params), map_job)
+            .db_context("查询任务失败")?
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("解析任务数据失败")
+    }
+
+    /// 记录上排队中或执行中的同类任务
+    pub fn active_for(&self, kind: JobKind, record_id: &str) -> AppResult<Option<Job>> {
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM jobs WHERE kind = ?1 AND record_id = ?2 AND status IN ('queued', 'running')",
+                    JOB_COLUMNS
+                ),
+                rusqlite::params![kind.as_str(), record_id],
+                map_job,
+            )
+            .optional()
+            .db_context("查询任务失败")
+    }
+
+    /// AI 分析对应的未完成任务
+    pub fn active_for_result(&self, result_id: &str) -> AppResult<Option<Job>> {
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM jobs WHERE result_id = ?1 AND status IN ('queued', 'running')",
+                    JOB_COLUMNS
+                ),
+                [result_id],
+                map_job,
+            )
+            .optional()
+            .db_context("查询任务失败")
+    }
+
+    /// 该类型中最早排队的任务
+    pub fn next_queued(&self, kind: JobKind) -> AppResult<Option<Job>> {
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM jobs WHERE status = 'queued' AND kind = ?1 ORDER BY created_at ASC, rowid ASC LIMIT 1",
+                    JOB_COLUMNS
+                ),
+                [kind.as_str()],
+                map_job,
+            )
+            .optional()
+            .db_context("查询任务失败")
+    }
+
+    /// 将排队中的任务置为执行中并累加执行次数，记录执行者 `owner` 与心跳；已被其他执行者领取时返回 false
+    pub fn claim(&self, id: &str, owner: &str) -> AppResult<bool> {
+        let now = super::now();
+        let changed = self
+            .conn
+            .execute(
+                "UPDATE jobs SET status = 'running', attempts = attempts + 1, started_at = ?1, updated_at = ?1,
+                                 owner = ?2, heartbeat_at = ?3
+                 WHERE id = ?4 AND status = 'queued'",
+                rusqlite::params![now, owner, chrono::Utc::now().timestamp(), id],
+            )
+            .db_context("更新任务状态失败")?;
+        Ok(changed == 1)
+    }
+
+    /// 刷新执行中任务的心跳（Unix 时间戳，秒）
+    pub fn heartbeat(&self, id: &str) -> AppResult<()> {
+        self.conn
+            .execute(
+                "UPDATE jobs SET heartbeat_at = ?1 WHERE id = ?2 AND status = 'running'",
+                rusqlite::params![chrono::Utc::now().timestamp(), id],
+            )
+            .db_context("更新任务心跳失败")?;
+        Ok(())
+    }
+
+    pub fn set_progress(&self, id: &str, done: i64, total: i64) -> AppResult<()> {
+        self.conn
+            .execute(
+                "UPDATE jobs SET progress_done = ?1, progress_total = ?2, updated_at = ?3 WHERE id = ?4",
+                rusqlite::params![done, total, super::now(), id],
+            )
+            .db_context("更新任务进度失败")?;
+        Ok(())
+    }
+
+    pub fn set_result(&self, id: &str, result_id: &str) -> AppResult<()> {
+        self.conn
+            .execute(
+                "UPDATE jobs SET result_id = ?1, updated_at = ?2 WHERE id = ?3",
+                rusqlite::params![result_id, super::now(), id],
+            )
+            .db_context("更新任务失败")?;
+        Ok(())
+    }
+
+    /// 取消排队中的任务；已被领取或已结束时返回 false
+    pub fn cancel_queued(&self, id: &str) -> AppResult<bool> {
+        let changed = self
+            .conn
+            .execute(
+                "UPDATE jobs SET status = 'cancelled', finished_at = ?1, updated_at = ?1 WHERE id = ?2 AND status = 'queued'",
+                rusqlite::params![super::now(), id],
+            )
+            .db_context("更新任务状态失败")?;
+        Ok(changed == 1)
+    }
+
+    /// 结束任务；`error` 在成功时记录部分失败的原因。已取消的任务保持取消状态
+    pub fn finish(&self, id: &str, status: JobStatus, error: Option<&str>) -> AppResult<()> {
+        let now = super::now();
+        self.conn
+            .execute(
+                "UPDATE jobs SET status = ?1, last_error = ?2, finished_at = ?3, updated_at = ?3
+                 WHERE id = ?4 AND status != 'cancelled'",
+                rusqlite::params![status.as_str(), error, now, id],
+            )
+            .db_context("更新任务状态失败")?;
+        Ok(())
+    }
+
+    /// 将中断的执行中任务重新排队；执行次数已达上限的标记为失败
+    ///
+    /// 只处理心跳已超过 `stale_secs` 秒未更新的任务，其他进程（如命令行工具）中仍在执行的任务保持不变。
+    /// 返回 (重新排队数, 放弃数)
+    pub fn requeue_interrupted(&self, stale_secs: i64) -> AppResult<(usize, usize)> {
+        let now = super::now();
+        let stale_before = chrono::Utc::now().timestamp() - stale_secs;
+        let interrupted = "status = 'running' AND (heartbeat_at IS NULL OR heartbeat_at <= ?2)";
+        let abandoned = self
+            .conn
+            .execute(
+                &format!(
+                    "UPDATE jobs SET status = 'failed', last_error = ?3, finished_at = ?1, updated_at = ?1
+                     WHERE {} AND attempts >= ?4",
+                    interrupted
+                ),
+                rusqlite::params![now, stale_before, format!("任务已中断 {} 次，不再自动重试", MAX_ATTEMPTS), MAX_ATTEMPTS],
+            )
+            .db_context("恢复任务失败")?;
+        let requeued = self
+            .conn
+            .execute(
+                &format!(
+                    "UPDATE jobs SET status = 'queued', owner = NULL, heartbeat_at = NULL, updated_at = ?1 WHERE {}",
+                    interrupted
+                ),
+                rusqlite::params![now, stale_before],
+            )
+            .db_context("恢复任务失败")?;
+        Ok((requeued, abandoned))
+    }
+
+    /// 状态为 `status` 但没有未完成任务的检查记录（旧版本中断后遗留）
+    pub fn records_without_active_job(&self, kind: JobKind, status: &str) -> AppResult<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT r.id FROM checkup_records r
+                 WHERE r.status = ?1 AND NOT EXISTS (
+                     SELECT 1 FROM jobs j
+                     WHERE j.record_id = r.id AND j.kind = ?2 AND j.status IN ('queued', 'running')
+                 )",
+            )
+            .db_context("查询检查记录失败")?;
+        stmt.query_map(rusqlite::params![status, kind.as_str()], |row| row.get(0))
+            .db_context("查询检查记录失败")?
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("解析检查记录失败")
+    }
+}
@@ -0,0 +1,60 @@
+//! 数据访问层：每个 Repo 只依赖 `&Connection`，不依赖 Tauri，便于复用与测试
+
+use crate::error::{AppResult, DbResultExt};
+use rusqlite::Connection;
+
+pub mod audit;
+pub mod config;
+pub mod project;
+pub mod indicator;
+pub mod record;
+pub mod file;
+pub mod ocr;
+pub mod ai;
+pub mod trash;
+pub mod integrity;
+pub mod patient;
+pub mod job;
+pub mod ai_profile;
+
+pub use audit::AuditRepo;
+pub use config::ConfigRepo;
+pub use project::ProjectRepo;
+pub use indicator::IndicatorRepo;
+pub use record::RecordRepo;
+pub use file::FileRepo;
+pub use ocr::OcrRepo;
+pub use ai::AiRepo;
+pub use trash::TrashRepo;
+pub use patient::PatientRepo;
+pub use job::JobRepo;
+pub use ai_profile::AiProfileRepo;
+
+#[cfg(test)]
+mod tests;
+
+/// 当前时间（RFC 3339），各表的时间字段统一使用此格式
+pub(crate) fn now() -> String {
+    chrono::Local::now().to_rfc3339()
+}
+
+/// 写操作及其审计日志所在的事务，离开作用域前未提交则回滚
+///
+/// 已处于外层事务中时（如新建 AI 配置时写入任务配置）不再嵌套，由外层事务提交。
+pub(crate) struct Tx<'a>(Option<rusqlite::Transaction<'a>>);
+
+pub(crate) fn transaction(conn: &Connection) -> AppResult<Tx<'_>> {
+    if !conn.is_autocommit() {
+        return Ok(Tx(None));
+    }
+    Ok(Tx(Some(conn.unchecked_transaction().db_context("开启事务失败")?)))
+}
+
+impl Tx<'_> {
+    pub(crate) fn commit(self) -> AppResult<()> {
+        match self.0 {
+            Some(tx) => tx.commit().db_context("提交事务失败"),
+            None => Ok(()),
+        }
+    }
+}
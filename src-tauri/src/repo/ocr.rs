@@ -0,0 +1,432 @@
+use rusqlite::{Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use crate::error::{AppError, AppResult, DbResultExt};
+use super::audit;
+use super::indicator::Indicator;
+use super::project::OcrEngineKind;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OcrResult {
+    pub id: String,
+    pub file_id: String,
+    pub record_id: String,
+    pub project_id: String,
+    pub checkup_date: String,
+    pub raw_json: String,
+    pub parsed_items: String,
+    pub status: String,
+    pub error_message: String,
+    pub created_at: String,
+    /// 手动修正的时间，为空表示未修正
+    pub corrected_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OcrParsedItem {
+    pub name: String,
+    pub value: String,
+    pub unit: String,
+    pub reference_range: String,
+    pub is_abnormal: bool,
+    /// PDF 文件中所在的页码（从 1 开始），图片文件为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+}
+
+/// 待识别的文件
+#[derive(Debug, Clone)]
+pub struct OcrSourceFile {
+    pub file_id: String,
+    pub project_id: String,
+    pub original_filename: String,
+    pub stored_path: String,
+    pub mime_type: String,
+    /// 所属项目的识别方式
+    pub ocr_engine: OcrEngineKind,
+}
+
+/// 一条 OCR 结果归属的文件与检查记录
+pub struct OcrTarget<'t> {
+    pub file_id: &'t str,
+    pub record_id: &'t str,
+    pub project_id: &'t str,
+    pub checkup_date: &'t str,
+}
+
+/// 某次检查记录的 OCR 统计
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OcrCounts {
+    pub total_files: i64,
+    pub total_ocr: i64,
+    pub success_ocr: i64,
+    pub failed_ocr: i64,
+}
+
+/// 识别成功的解析结果（AI 分析的输入）
+#[derive(Debug, Clone)]
+pub struct ParsedOcrData {
+    pub parsed_items: String,
+    pub project_name: String,
+    pub checkup_date: String,
+}
+
+/// 删除文件此前失败或取消的结果，每个文件只保留最近一次的结果，重试与重新识别不会累积记录
+///
+/// 这些结果没有关联的指标值，插入时也不写审计日志，因此直接删除。
+fn clear_unsuccessful(conn: &Connection, file_id: &str) -> AppResult<()> {
+    conn.execute("DELETE FROM ocr_results WHERE file_id = ?1 AND status != 'success'", [file_id])
+        .db_context("清理OCR结果失败")?;
+    Ok(())
+}
+
+/// 文件已有手动修正的结果时返回其 id，之后的识别结果不再保存
+fn corrected_result(conn: &Connection, file_id: &str) -> AppResult<Option<String>> {
+    conn.query_row(
+        "SELECT id FROM ocr_results WHERE file_id = ?1 AND corrected_at IS NOT NULL",
+        [file_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .db_context("查询OCR结果失败")
+}
+
+/// 将能匹配到指标定义的条目写入 indicator_values
+fn insert_indicator_values(
+    conn: &Connection,
+    ocr_id: &str,
+    target: &OcrTarget,
+    items: &[OcrParsedItem],
+    indicators: &[Indicator],
+) -> AppResult<()> {
+    let now = super::now();
+    for item in items {
+        let indicator = indicators
+            .iter()
+            .find(|ind| ind.project_id == target.project_id && name_fuzzy_match(&ind.name, &item.name));
+
+        if let Some(indicator) = indicator {
+            let value: Option<f64> = item.value.parse().ok();
+            let value_id = uuid::Uuid::new_v4().to_string();
+            conn.execute(
+                "INSERT INTO indicator_values (id, ocr_result_id, record_id, project_id, indicator_id, checkup_date, value, value_text, is_abnormal, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                rusqlite::params![
+                    value_id, ocr_id, target.record_id, target.project_id, indicator.id,
+                    target.checkup_date, value, item.value, item.is_abnormal as i32, now
+                ],
+            )
+            .db_context("保存指标值失败")?;
+            audit::record_create(conn, "indicator_values", &value_id)?;
+        }
+    }
+    Ok(())
+}
+
+const RESULT_COLUMNS: &str = "o.id, o.file_id, o.record_id, o.project_id, o.checkup_date, o.raw_json, o.parsed_items, \
+                              o.status, o.error_message, o.created_at, o.corrected_at";
+
+fn map_result(row: &Row) -> rusqlite::Result<OcrResult> {
+    Ok(OcrResult {
+        id: row.get(0)?,
+        file_id: row.get(1)?,
+        record_id: row.get(2)?,
+        project_id: row.get(3)?,
+        checkup_date: row.get(4)?,
+        raw_json: row.get(5)?,
+        parsed_items: row.get(6)?,
+        status: row.get(7)?,
+        error_message: row.get(8)?,
+        created_at: row.get(9)?,
+        corrected_at: row.get(10)?,
+    })
+}
+
+/// 识别结果所属文件不在回收站中（查询中 ocr_results 的别名须为 o）
+const LIVE_FILE: &str = "o.file_id IN (SELECT id FROM checkup_files WHERE deleted_at IS NULL)";
+
+pub struct OcrRepo<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> OcrRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// 记录下尚未识别成功的文件
+    pub fn pending_files(&self, record_id: &str) -> AppResult<Vec<OcrSourceFile>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT f.id, f.project_id, f.original_filename, f.stored_path, f.mime_type,
+                        COALESCE(p.ocr_engine, 'vision')
+                 FROM checkup_files f
+                 LEFT JOIN checkup_projects p ON f.project_id = p.id
+                 WHERE f.record_id = ?1 AND f.deleted_at IS NULL
+                   AND NOT EXISTS (
+                       SELECT 1 FROM ocr_results o
+                       WHERE o.file_id = f.id AND o.status = 'success'
+                   )
+                 ORDER BY p.name ASC, f.uploaded_at ASC"
+            )
+            .db_context("查询文件失败")?;
+
+        stmt.query_map([record_id], |row| {
+            Ok(OcrSourceFile {
+                file_id: row.get(0)?,
+                project_id: row.get(1)?,
+                original_filename: row.get(2)?,
+                stored_path: row.get(3)?,
+                mime_type: row.get(4)?,
+                ocr_engine: OcrEngineKind::parse(&row.get::<_, String>(5)?),
+            })
+        })
+        .db_context("查询文件失败")?
+        .collect::<Result<Vec<_>, _>>()
+        .db_context("解析文件数据失败")
+    }
+
+    /// 保存识别成功的结果，并将能匹配到指标定义的条目写入 indicator_values
+    pub fn save_success(
+        &self,
+        target: &OcrTarget,
+        raw_content: &str,
+        items: &[OcrParsedItem],
+        indicators: &[Indicator],
+    ) -> AppResult<String> {
+        let ocr_id = uuid::Uuid::new_v4().to_string();
+        let now = super::now();
+        let parsed_items = serde_json::to_string(items).unwrap_or("[]".to_string());
+
+        // 识别结果与指标值一并写入，避免并发识别时留下只有一半的结果
+        let tx = self.conn.unchecked_transaction().db_context("开启事务失败")?;
+        if let Some(corrected) = corrected_result(&tx, target.file_id)? {
+            return Ok(corrected);
+        }
+        clear_unsuccessful(&tx, target.file_id)?;
+        tx.execute(
+            "INSERT INTO ocr_results (id, file_id, record_id, project_id, checkup_date, raw_json, parsed_items, status, error_message, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 'success', '', ?8)",
+            rusqlite::params![ocr_id, target.file_id, target.record_id, target.project_id, target.checkup_date, raw_content, parsed_items, now],
+        )
+        .db_context("保存OCR结果失败")?;
+        insert_indicator_values(&tx, &ocr_id, target, items, indicators)?;
+
+        tx.commit().db_context("保存OCR结果失败")?;
+        Ok(ocr_id)
+    }
+
+    /// 保存识别失败的结果
+    pub fn save_failure(&self, target: &OcrTarget, error_message: &str) -> AppResult<String> {
+        self.save_unsuccessful(target, "failed", "", &[], error_message)
+    }
+
+    /// 识别被取消时，为未完成的文件保存一条已取消的结果，再次识别时仍会处理这些文件
+    pub fn save_cancelled(&self, target: &OcrTarget) -> AppResult<String> {
+        self.save_unsuccessful(target, "cancelled", "", &[], "已取消")
+    }
+
+    /// 模型输出未通过校验时保存原始输出与已解析的条目（PDF 其他页），不写入指标值，再次识别时仍会处理
+    pub fn save_parse_failed(
+        &self,
+        target: &OcrTarget,
+        raw_content: &str,
+        items: &[OcrParsedItem],
+        error_message: &str,
+    ) -> AppResult<String> {
+        self.save_unsuccessful(target, "parse_failed", raw_content, items, error_message)
+    }
+
+    fn save_unsuccessful(
+        &self,
+        target: &OcrTarget,
+        status: &str,
+        raw_content: &str,
+        items: &[OcrParsedItem],
+        error_message: &str,
+    ) -> AppResult<String> {
+        let parsed_items = serde_json::to_string(items).unwrap_or("[]".to_string());
+        let ocr_id = uuid::Uuid::new_v4().to_string();
+        let tx = self.conn.unchecked_transaction().db_context("开启事务失败")?;
+        if let Some(corrected) = corrected_result(&tx, target.file_id)? {
+            return Ok(corrected);
+        }
+        clear_unsuccessful(&tx, target.file_id)?;
+        tx.execute(
+            "INSERT INTO ocr_results (id, file_id, record_id, project_id, checkup_date, raw_json, parsed_items, status, error_message, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            rusqlite::params![
+                ocr_id, target.file_id, target.record_id, target.project_id, target.checkup_date,
+                raw_content, parsed_items, status, error_message, super::now()
+            ],
+        )
+        .db_context("保存OCR结果失败")?;
+        tx.commit().db_context("保存OCR结果失败")?;
+        Ok(ocr_id)
+    }
+
+    /// 手动修正识别结果：替换解析条目并重新生成关联的指标值，结果标记为已修正
+    ///
+    /// 解析失败或识别失败的结果修正后记为成功；修正过的结果不会被之后的识别覆盖。
+    pub fn update_items(&self, id: &str, items: &[OcrParsedItem], indicators: &[Indicator]) -> AppResult<OcrResult> {
+        if let Some(i) = items.iter().position(|item| item.name.trim().is_empty()) {
+            return Err(AppError::Validation(format!("第 {} 项的指标名称不能为空", i + 1)));
+        }
+        let current = self.get(id)?;
+        let parsed_items = serde_json::to_string(items).unwrap_or("[]".to_string());
+
+        let tx = super::transaction(self.conn)?;
+        let before = audit::snapshot(self.conn, "ocr_results", id)?;
+        self.conn
+            .execute(
+                "UPDATE ocr_results SET parsed_items = ?1, status = 'success', error_message = '', corrected_at = ?2 WHERE id = ?3",
+                rusqlite::params![parsed_items, super::now(), id],
+            )
+            .db_context("修正OCR结果失败")?;
+
+        audit::delete_rows(self.conn, "indicator_values", "ocr_result_id = ?1", [id])?;
+        let target = OcrTarget {
+            file_id: &current.file_id,
+            record_id: &current.record_id,
+            project_id: &current.project_id,
+            checkup_date: &current.checkup_date,
+        };
+        insert_indicator_values(self.conn, id, &target, items, indicators)?;
+        audit::record_update(self.conn, "ocr_results", id, before)?;
+
+        // 此前没有成功结果的记录，修正后即可进行 AI 分析
+        super::RecordRepo::new(self.conn).advance_status(&current.record_id, "pending_ocr", "ocr_done")?;
+        tx.commit()?;
+        self.get(id)
+    }
+
+    pub fn get(&self, id: &str) -> AppResult<OcrResult> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM ocr_results o WHERE o.id = ?1 AND {}", RESULT_COLUMNS, LIVE_FILE),
+                [id],
+                map_result,
+            )
+            .optional()
+            .db_context("查询OCR结果失败")?
+            .ok_or_else(|| AppError::NotFound("OCR 结果不存在".into()))
+    }
+
+    pub fn counts(&self, record_id: &str) -> AppResult<OcrCounts> {
+        let count = |sql: &str| -> AppResult<i64> {
+            self.conn
+                .query_row(sql, [record_id], |row| row.get(0))
+                .db_context("查询失败")
+        };
+
+        Ok(OcrCounts {
+            total_files: count("SELECT COUNT(*) FROM checkup_files WHERE record_id = ?1 AND deleted_at IS NULL")?,
+            total_ocr: count(&format!("SELECT COUNT(*) FROM ocr_results o WHERE o.record_id = ?1 AND {}", LIVE_FILE))?,
+            success_ocr: count(&format!(
+                "SELECT COUNT(*) FROM ocr_results o WHERE o.record_id = ?1 AND o.status = 'success' AND {}",
+                LIVE_FILE
+            ))?,
+            failed_ocr: count(&format!(
+                "SELECT COUNT(*) FROM ocr_results o WHERE o.record_id = ?1 AND o.status IN ('failed', 'parse_failed') AND {}",
+                LIVE_FILE
+            ))?,
+        })
+    }
+
+    pub fn list(&self, record_id: &str) -> AppResult<Vec<OcrResult>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {} FROM ocr_results o WHERE o.record_id = ?1 AND {}
+                 ORDER BY o.created_at ASC",
+                RESULT_COLUMNS, LIVE_FILE
+            ))
+            .db_context("查询OCR结果失败")?;
+
+        stmt.query_map([record_id], map_result)
+            .db_context("查询失败")?
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("解析数据失败")
+    }
+
+    /// 当前记录识别成功的结果（按项目名排序）
+    pub fn parsed_for_record(&self, record_id: &str) -> AppResult<Vec<ParsedOcrData>> {
+        self.query_parsed(
+            &format!(
+                "SELECT o.parsed_items, p.name, o.checkup_date
+                 FROM ocr_results o
+                 LEFT JOIN checkup_projects p ON o.project_id = p.id
+                 WHERE o.record_id = ?1 AND o.status = 'success' AND {}
+                 ORDER BY p.name ASC",
+                LIVE_FILE
+            ),
+            record_id,
+        )
+    }
+
+    /// 同一成员其他记录识别成功的结果（按检查日期倒序）
+    pub fn parsed_history(&self, exclude_record_id: &str) -> AppResult<Vec<ParsedOcrData>> {
+        self.query_parsed(
+            &format!(
+                "SELECT o.parsed_items, p.name, r.checkup_date
+                 FROM ocr_results o
+                 JOIN checkup_records r ON o.record_id = r.id
+                 LEFT JOIN checkup_projects p ON o.project_id = p.id
+                 WHERE o.record_id != ?1 AND o.status = 'success' AND r.deleted_at IS NULL AND {}
+                   AND r.patient_id = (SELECT patient_id FROM checkup_records WHERE id = ?1)
+                 ORDER BY r.checkup_date DESC",
+                LIVE_FILE
+            ),
+            exclude_record_id,
+        )
+    }
+
+    fn query_parsed(&self, sql: &str, record_id: &str) -> AppResult<Vec<ParsedOcrData>> {
+        let mut stmt = self.conn.prepare(sql).db_context("查询OCR结果失败")?;
+        stmt.query_map([record_id], |row| {
+            Ok(ParsedOcrData {
+                parsed_items: row.get(0)?,
+                project_name: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                checkup_date: row.get(2)?,
+            })
+        })
+        .db_context("查询失败")?
+        .collect::<Result<Vec<_>, _>>()
+        .db_context("解析数据失败")
+    }
+}
+
+/// 模糊匹配指标名称
+pub fn name_fuzzy_match(indicator_name: &str, ocr_name: &str) -> bool {
+    let a = indicator_name.trim().to_lowercase();
+    let b = ocr_name.trim().to_lowercase();
+
+    if a == b {
+        return true;
+    }
+
+    // 包含匹配
+    if a.contains(&b) || b.contains(&a) {
+        return true;
+    }
+
+    // 去掉括号内容后匹配
+    let strip_parens = |s: &str| -> String {
+        let mut result = String::new();
+        let mut depth = 0;
+        for ch in s.chars() {
+            match ch {
+                '(' | '（' => depth += 1,
+                ')' | '）' => { depth -= 1; }
+                _ if depth == 0 => result.push(ch),
+                _ => {}
+            }
+        }
+        result.trim().to_string()
+    };
+
+    let a_stripped = strip_parens(&a);
+    let b_stripped = strip_parens(&b);
+
+    a_stripped == b_stripped || a_stripped.contains(&b_stripped) || b_stripped.contains(&a_stripped)
+}
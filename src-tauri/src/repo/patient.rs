@@ -0,0 +1,214 @@
+use rusqlite::{Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use crate::error::{AppError, AppResult, DbResultExt};
+use super::{audit, ConfigRepo};
+
+/// 保存当前成员的配置项
+pub const ACTIVE_PATIENT_KEY: &str = "active_patient_id";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Patient {
+    pub id: String,
+    pub name: String,
+    pub gender: String,
+    pub birth_date: String,
+    pub notes: String,
+    pub sort_order: i32,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePatientInput {
+    pub name: String,
+    pub gender: Option<String>,
+    pub birth_date: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdatePatientInput {
+    pub id: String,
+    pub name: Option<String>,
+    pub gender: Option<String>,
+    pub birth_date: Option<String>,
+    pub notes: Option<String>,
+    pub sort_order: Option<i32>,
+}
+
+const PATIENT_COLUMNS: &str = "id, name, gender, birth_date, notes, sort_order, created_at, updated_at";
+
+fn map_patient(row: &Row) -> rusqlite::Result<Patient> {
+    Ok(Patient {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        gender: row.get(2)?,
+        birth_date: row.get(3)?,
+        notes: row.get(4)?,
+        sort_order: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+pub struct PatientRepo<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> PatientRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    pub fn list(&self) -> AppResult<Vec<Patient>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {} FROM patients ORDER BY sort_order ASC, created_at ASC",
+                PATIENT_COLUMNS
+            ))
+            .db_context("查询成员失败")?;
+
+        stmt.query_map([], map_patient)
+            .db_context("查询成员失败")?
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("解析成员数据失败")
+    }
+
+    pub fn get(&self, id: &str) -> AppResult<Patient> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM patients WHERE id = ?1", PATIENT_COLUMNS),
+                [id],
+                map_patient,
+            )
+            .or_not_found("成员不存在")
+    }
+
+    pub fn create(&self, input: CreatePatientInput) -> AppResult<Patient> {
+        let now = super::now();
+        let id = uuid::Uuid::new_v4().to_string();
+        let gender = input.gender.unwrap_or_default();
+        let birth_date = input.birth_date.unwrap_or_default();
+        let notes = input.notes.unwrap_or_default();
+
+        let tx = super::transaction(self.conn)?;
+        self.conn
+            .execute(
+                "INSERT INTO patients (id, name, gender, birth_date, notes, sort_order, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6, ?7)",
+                rusqlite::params![id, input.name, gender, birth_date, notes, now, now],
+            )
+            .db_context("创建成员失败")?;
+        audit::record_create(self.conn, "patients", &id)?;
+        tx.commit()?;
+
+        Ok(Patient {
+            id,
+            name: input.name,
+            gender,
+            birth_date,
+            notes,
+            sort_order: 0,
+            created_at: now.clone(),
+            updated_at: now,
+        })
+    }
+
+    pub fn update(&self, input: UpdatePatientInput) -> AppResult<Patient> {
+        let tx = super::transaction(self.conn)?;
+        let existing = self.get(&input.id)?;
+        let before = audit::snapshot(self.conn, "patients", &input.id)?;
+        let now = super::now();
+
+        let name = input.name.unwrap_or(existing.name);
+        let gender = input.gender.unwrap_or(existing.gender);
+        let birth_date = input.birth_date.unwrap_or(existing.birth_date);
+        let notes = input.notes.unwrap_or(existing.notes);
+        let sort_order = input.sort_order.unwrap_or(existing.sort_order);
+
+        self.conn
+            .execute(
+                "UPDATE patients SET name=?1, gender=?2, birth_date=?3, notes=?4, sort_order=?5, updated_at=?6 WHERE id=?7",
+                rusqlite::params![name, gender, birth_date, notes, sort_order, now, input.id],
+            )
+            .db_context("更新成员失败")?;
+        audit::record_update(self.conn, "patients", &input.id, before)?;
+        tx.commit()?;
+
+        Ok(Patient {
+            id: input.id,
+            name,
+            gender,
+            birth_date,
+            notes,
+            sort_order,
+            created_at: existing.created_at,
+            updated_at: now,
+        })
+    }
+
+    /// 删除成员；仍有检查记录（含回收站中的记录）或为最后一位成员时拒绝
+    pub fn delete(&self, id: &str) -> AppResult<()> {
+        let tx = super::transaction(self.conn)?;
+        self.get(id)?;
+
+        let record_count: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM checkup_records WHERE patient_id = ?1", [id], |row| row.get(0))
+            .db_context("查询关联记录失败")?;
+        if record_count > 0 {
+            return Err(AppError::has_dependents(
+                format!("该成员下有 {} 条检查记录，无法删除。请先删除相关检查记录并清空回收站。", record_count),
+                "checkup_records",
+                record_count,
+            ));
+        }
+
+        let patient_count: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM patients", [], |row| row.get(0))
+            .db_context("查询成员失败")?;
+        if patient_count <= 1 {
+            return Err(AppError::Validation("至少需要保留一位成员".into()));
+        }
+
+        audit::delete_rows(self.conn, "patients", "id = ?1", [id])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 当前成员；未设置或已被删除时使用排序第一的成员
+    pub fn active(&self) -> AppResult<Patient> {
+        if let Some(id) = ConfigRepo::new(self.conn).get(ACTIVE_PATIENT_KEY)? {
+            let patient = self
+                .conn
+                .query_row(
+                    &format!("SELECT {} FROM patients WHERE id = ?1", PATIENT_COLUMNS),
+                    [&id],
+                    map_patient,
+                )
+                .optional()
+                .db_context("查询成员失败")?;
+            if let Some(patient) = patient {
+                return Ok(patient);
+            }
+        }
+
+        self.list()?
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::NotFound("尚未创建任何成员".into()))
+    }
+
+    /// 当前成员的 id
+    pub fn active_id(&self) -> AppResult<String> {
+        Ok(self.active()?.id)
+    }
+
+    pub fn set_active(&self, id: &str) -> AppResult<Patient> {
+        let patient = self.get(id)?;
+        ConfigRepo::new(self.conn).set(ACTIVE_PATIENT_KEY, id)?;
+        Ok(patient)
+    }
+}
@@ -0,0 +1,259 @@
+use rusqlite::{Connection, Row};
+use serde::{Deserialize, Serialize};
+use crate::error::{AppError, AppResult, DbResultExt};
+use super::{audit, trash};
+
+/// 项目报告的识别方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OcrEngineKind {
+    /// 上传到 AI 视觉模型识别
+    #[default]
+    Vision,
+    /// 本机 Tesseract 识别，图片不离开本机
+    Tesseract,
+}
+
+impl OcrEngineKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OcrEngineKind::Vision => "vision",
+            OcrEngineKind::Tesseract => "tesseract",
+        }
+    }
+
+    pub(crate) fn parse(value: &str) -> Self {
+        match value {
+            "tesseract" => OcrEngineKind::Tesseract,
+            _ => OcrEngineKind::Vision,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Project {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub sort_order: i32,
+    pub is_active: bool,
+    pub ocr_engine: OcrEngineKind,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateProjectInput {
+    pub name: String,
+    pub description: Option<String>,
+    pub ocr_engine: Option<OcrEngineKind>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateProjectInput {
+    pub id: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub is_active: Option<bool>,
+    pub sort_order: Option<i32>,
+    pub ocr_engine: Option<OcrEngineKind>,
+}
+
+const PROJECT_COLUMNS: &str = "id, name, description, sort_order, is_active, created_at, updated_at, ocr_engine";
+
+fn map_project(row: &Row) -> rusqlite::Result<Project> {
+    Ok(Project {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        sort_order: row.get(3)?,
+        is_active: row.get::<_, i32>(4)? == 1,
+        ocr_engine: OcrEngineKind::parse(&row.get::<_, String>(7)?),
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+pub struct ProjectRepo<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> ProjectRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    pub fn list(&self) -> AppResult<Vec<Project>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {} FROM checkup_projects WHERE deleted_at IS NULL ORDER BY sort_order ASC, created_at ASC",
+                PROJECT_COLUMNS
+            ))
+            .db_context("查询项目失败")?;
+
+        stmt.query_map([], map_project)
+            .db_context("查询项目失败")?
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("解析项目数据失败")
+    }
+
+    pub fn get(&self, id: &str) -> AppResult<Project> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM checkup_projects WHERE id = ?1 AND deleted_at IS NULL", PROJECT_COLUMNS),
+                [id],
+                map_project,
+            )
+            .or_not_found("项目不存在")
+    }
+
+    /// 项目名称（用于文件目录结构）
+    pub fn name_of(&self, id: &str) -> AppResult<String> {
+        self.conn
+            .query_row(
+                "SELECT name FROM checkup_projects WHERE id = ?1 AND deleted_at IS NULL",
+                [id],
+                |row| row.get(0),
+            )
+            .or_not_found("项目不存在")
+    }
+
+    pub fn create(&self, input: CreateProjectInput) -> AppResult<Project> {
+        let now = super::now();
+        let id = uuid::Uuid::new_v4().to_string();
+        let description = input.description.unwrap_or_default();
+        let ocr_engine = input.ocr_engine.unwrap_or_default();
+
+        let tx = super::transaction(self.conn)?;
+        self.conn
+            .execute(
+                "INSERT INTO checkup_projects (id, name, description, sort_order, is_active, ocr_engine, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, 0, 1, ?4, ?5, ?6)",
+                rusqlite::params![id, input.name, description, ocr_engine.as_str(), now, now],
+            )
+            .db_context("创建项目失败")?;
+        audit::record_create(self.conn, "checkup_projects", &id)?;
+        tx.commit()?;
+
+        Ok(Project {
+            id,
+            name: input.name,
+            description,
+            sort_order: 0,
+            is_active: true,
+            ocr_engine,
+            created_at: now.clone(),
+            updated_at: now,
+        })
+    }
+
+    pub fn update(&self, input: UpdateProjectInput) -> AppResult<Project> {
+        let tx = super::transaction(self.conn)?;
+        let existing = self.get(&input.id)?;
+        let before = audit::snapshot(self.conn, "checkup_projects", &input.id)?;
+        let now = super::now();
+
+        let name = input.name.unwrap_or(existing.name);
+        let description = input.description.unwrap_or(existing.description);
+        let is_active = input.is_active.unwrap_or(existing.is_active);
+        let sort_order = input.sort_order.unwrap_or(existing.sort_order);
+        let ocr_engine = input.ocr_engine.unwrap_or(existing.ocr_engine);
+
+        self.conn
+            .execute(
+                "UPDATE checkup_projects SET name=?1, description=?2, is_active=?3, sort_order=?4, ocr_engine=?5, updated_at=?6
+                 WHERE id=?7",
+                rusqlite::params![name, description, is_active as i32, sort_order, ocr_engine.as_str(), now, input.id],
+            )
+            .db_context("更新项目失败")?;
+        audit::record_update(self.conn, "checkup_projects", &input.id, before)?;
+        tx.commit()?;
+
+        Ok(Project {
+            id: input.id,
+            name,
+            description,
+            sort_order,
+            is_active,
+            ocr_engine,
+            created_at: existing.created_at,
+            updated_at: now,
+        })
+    }
+
+    /// 将项目移入回收站；仍有未删除的关联文件时拒绝
+    pub fn delete(&self, id: &str) -> AppResult<()> {
+        self.ensure_no_live_files(id, "删除")?;
+        if !trash::set_deleted(self.conn, "checkup_projects", id, true)? {
+            return Err(AppError::NotFound("项目不存在".into()));
+        }
+        Ok(())
+    }
+
+    /// 彻底删除项目、指标及回收站中属于该项目的文件，返回文件存储路径
+    pub(crate) fn purge(&self, id: &str) -> AppResult<Vec<String>> {
+        self.ensure_no_live_files(id, "彻底删除")?;
+
+        let file_ids = {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT id FROM checkup_files WHERE project_id = ?1")
+                .db_context("查询关联文件失败")?;
+            stmt.query_map([id], |row| row.get::<_, String>(0))
+                .db_context("查询关联文件失败")?
+                .collect::<Result<Vec<_>, _>>()
+                .db_context("解析文件数据失败")?
+        };
+        let file_repo = super::FileRepo::new(self.conn);
+        let stored_paths = file_ids
+            .iter()
+            .map(|file_id| file_repo.purge(file_id))
+            .collect::<AppResult<Vec<_>>>()?;
+
+        audit::delete_rows(self.conn, "indicators", "project_id = ?1", [id])?;
+        audit::delete_rows(self.conn, "checkup_projects", "id = ?1", [id])?;
+
+        Ok(stored_paths)
+    }
+
+    /// 项目下仍有不在回收站中的文件（文件及其检查记录均未删除）时拒绝操作
+    fn ensure_no_live_files(&self, id: &str, action: &str) -> AppResult<()> {
+        let file_count: i64 = self
+            .conn
+            .query_row(
+                "SELECT COUNT(*) FROM checkup_files f
+                 JOIN checkup_records r ON f.record_id = r.id
+                 WHERE f.project_id = ?1 AND f.deleted_at IS NULL AND r.deleted_at IS NULL",
+                [id],
+                |row| row.get(0),
+            )
+            .db_context("查询关联文件失败")?;
+
+        if file_count > 0 {
+            return Err(AppError::has_dependents(
+                format!("该项目下有 {} 个关联文件，无法{}。请先删除相关检查记录。", file_count, action),
+                "checkup_files",
+                file_count,
+            ));
+        }
+        Ok(())
+    }
+
+    /// 启用中的项目（id, 名称）
+    pub fn list_active(&self) -> AppResult<Vec<(String, String)>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT id, name FROM checkup_projects
+                 WHERE is_active = 1 AND deleted_at IS NULL
+                 ORDER BY sort_order ASC"
+            )
+            .db_context("查询项目失败")?;
+
+        stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .db_context("查询项目失败")?
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("解析项目数据失败")
+    }
+}
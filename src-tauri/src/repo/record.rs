@@ -0,0 +1,283 @@
+use rusqlite::{Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use crate::error::{AppError, AppResult, DbResultExt};
+use super::{audit, trash};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CheckupRecord {
+    pub id: String,
+    pub patient_id: String,
+    pub checkup_date: String,
+    pub status: String,
+    pub notes: String,
+    pub created_at: String,
+    pub updated_at: String,
+    /// 关联的文件数量（查询时填充）
+    pub file_count: Option<i32>,
+    /// 关联的项目名称列表（查询时填充）
+    pub project_names: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRecordInput {
+    pub checkup_date: String,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRecordInput {
+    pub id: String,
+    pub checkup_date: Option<String>,
+    pub notes: Option<String>,
+    pub status: Option<String>,
+}
+
+fn map_record(row: &Row) -> rusqlite::Result<CheckupRecord> {
+    Ok(CheckupRecord {
+        id: row.get(0)?,
+        patient_id: row.get(1)?,
+        checkup_date: row.get(2)?,
+        status: row.get(3)?,
+        notes: row.get(4)?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+        file_count: Some(row.get(7)?),
+        project_names: None,
+    })
+}
+
+pub struct RecordRepo<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> RecordRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// 查询成员的全部检查记录（倒序）
+    pub fn list(&self, patient_id: &str) -> AppResult<Vec<CheckupRecord>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT r.id, r.patient_id, r.checkup_date, r.status, r.notes, r.created_at, r.updated_at,
+                        (SELECT COUNT(*) FROM checkup_files WHERE record_id = r.id AND deleted_at IS NULL) as file_count
+                 FROM checkup_records r
+                 WHERE r.patient_id = ?1 AND r.deleted_at IS NULL
+                 ORDER BY r.checkup_date DESC, r.created_at DESC"
+            )
+            .db_context("查询检查记录失败")?;
+
+        let records = stmt
+            .query_map([patient_id], map_record)
+            .db_context("查询失败")?
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("解析失败")?;
+
+        records
+            .into_iter()
+            .map(|mut record| {
+                record.project_names = Some(self.project_names(&record.id)?);
+                Ok(record)
+            })
+            .collect()
+    }
+
+    /// 获取单条检查记录详情
+    pub fn get(&self, id: &str) -> AppResult<CheckupRecord> {
+        let mut record = self
+            .conn
+            .query_row(
+                "SELECT id, patient_id, checkup_date, status, notes, created_at, updated_at,
+                        (SELECT COUNT(*) FROM checkup_files WHERE record_id = ?1 AND deleted_at IS NULL) as file_count
+                 FROM checkup_records WHERE id = ?1 AND deleted_at IS NULL",
+                [id],
+                map_record,
+            )
+            .or_not_found("记录不存在")?;
+
+        record.project_names = Some(self.project_names(id)?);
+        Ok(record)
+    }
+
+    /// 确认检查记录属于当前成员，其他成员的记录按不存在处理
+    pub fn ensure_active(&self, id: &str) -> AppResult<()> {
+        let patient_id: String = self
+            .conn
+            .query_row(
+                "SELECT patient_id FROM checkup_records WHERE id = ?1 AND deleted_at IS NULL",
+                [id],
+                |row| row.get(0),
+            )
+            .or_not_found("记录不存在")?;
+        if patient_id != super::PatientRepo::new(self.conn).active_id()? {
+            return Err(AppError::NotFound("记录不存在".into()));
+        }
+        Ok(())
+    }
+
+    /// 按检查日期查找成员的记录（不含回收站中的记录）
+    pub fn find_by_date(&self, patient_id: &str, checkup_date: &str) -> AppResult<Option<String>> {
+        self.conn
+            .query_row(
+                "SELECT id FROM checkup_records
+                 WHERE patient_id = ?1 AND checkup_date = ?2 AND deleted_at IS NULL
+                 LIMIT 1",
+                [patient_id, checkup_date],
+                |row| row.get(0),
+            )
+            .optional()
+            .db_context("查询检查记录失败")
+    }
+
+    pub fn checkup_date(&self, id: &str) -> AppResult<String> {
+        self.conn
+            .query_row(
+                "SELECT checkup_date FROM checkup_records WHERE id = ?1 AND deleted_at IS NULL",
+                [id],
+                |row| row.get(0),
+            )
+            .or_not_found("记录不存在")
+    }
+
+    pub fn status(&self, id: &str) -> AppResult<String> {
+        self.conn
+            .query_row(
+                "SELECT status FROM checkup_records WHERE id = ?1 AND deleted_at IS NULL",
+                [id],
+                |row| row.get(0),
+            )
+            .or_not_found("记录不存在")
+    }
+
+    pub fn create(&self, patient_id: &str, input: CreateRecordInput) -> AppResult<CheckupRecord> {
+        let now = super::now();
+        let id = uuid::Uuid::new_v4().to_string();
+        let notes = input.notes.unwrap_or_default();
+
+        let tx = super::transaction(self.conn)?;
+        self.conn
+            .execute(
+                "INSERT INTO checkup_records (id, patient_id, checkup_date, status, notes, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, 'pending_upload', ?4, ?5, ?6)",
+                rusqlite::params![id, patient_id, input.checkup_date, notes, now, now],
+            )
+            .db_context("创建检查记录失败")?;
+        audit::record_create(self.conn, "checkup_records", &id)?;
+        tx.commit()?;
+
+        Ok(CheckupRecord {
+            id,
+            patient_id: patient_id.to_string(),
+            checkup_date: input.checkup_date,
+            status: "pending_upload".to_string(),
+            notes,
+            created_at: now.clone(),
+            updated_at: now,
+            file_count: Some(0),
+            project_names: Some(vec![]),
+        })
+    }
+
+    pub fn update(&self, input: UpdateRecordInput) -> AppResult<()> {
+        let tx = super::transaction(self.conn)?;
+        let existing = self
+            .conn
+            .query_row(
+                "SELECT checkup_date, notes, status FROM checkup_records WHERE id = ?1 AND deleted_at IS NULL",
+                [&input.id],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)),
+            )
+            .or_not_found("记录不存在")?;
+
+        let before = audit::snapshot(self.conn, "checkup_records", &input.id)?;
+        let date = input.checkup_date.unwrap_or(existing.0);
+        let notes = input.notes.unwrap_or(existing.1);
+        let status = input.status.unwrap_or(existing.2);
+
+        self.conn
+            .execute(
+                "UPDATE checkup_records SET checkup_date=?1, notes=?2, status=?3, updated_at=?4 WHERE id=?5",
+                rusqlite::params![date, notes, status, super::now(), input.id],
+            )
+            .db_context("更新记录失败")?;
+        audit::record_update(self.conn, "checkup_records", &input.id, before)?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// 更新记录状态
+    pub fn set_status(&self, id: &str, status: &str) -> AppResult<()> {
+        self.conn
+            .execute(
+                "UPDATE checkup_records SET status = ?1, updated_at = ?2 WHERE id = ?3",
+                rusqlite::params![status, super::now(), id],
+            )
+            .db_context("更新记录状态失败")?;
+        Ok(())
+    }
+
+    /// 仅当记录处于 `from` 状态时才切换到 `to`
+    pub fn advance_status(&self, id: &str, from: &str, to: &str) -> AppResult<()> {
+        self.conn
+            .execute(
+                "UPDATE checkup_records SET status = ?1, updated_at = ?2 WHERE id = ?3 AND status = ?4",
+                rusqlite::params![to, super::now(), id, from],
+            )
+            .db_context("更新记录状态失败")?;
+        Ok(())
+    }
+
+    /// 将检查记录移入回收站，关联数据保持不变
+    pub fn delete(&self, id: &str) -> AppResult<()> {
+        if !trash::set_deleted(self.conn, "checkup_records", id, true)? {
+            return Err(AppError::NotFound("记录不存在".into()));
+        }
+        Ok(())
+    }
+
+    /// 彻底删除检查记录（级联删除关联数据），返回文件存储路径供调用方删除物理文件
+    pub(crate) fn purge(&self, id: &str) -> AppResult<Vec<String>> {
+        let stored_paths = {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT stored_path FROM checkup_files WHERE record_id = ?1")
+                .db_context("查询文件失败")?;
+            stmt.query_map([id], |row| row.get(0))
+                .db_context("查询文件失败")?
+                .collect::<Result<Vec<String>, _>>()
+                .db_context("解析文件数据失败")?
+        };
+
+        // 级联删除：indicator_values -> ocr_results -> ai_analyses -> checkup_files -> checkup_records
+        audit::delete_rows(self.conn, "indicator_values", "record_id = ?1", [id])?;
+        audit::delete_rows(self.conn, "ocr_results", "record_id = ?1", [id])?;
+        audit::delete_rows(self.conn, "ai_analyses", "record_id = ?1", [id])?;
+        audit::delete_rows(self.conn, "checkup_files", "record_id = ?1", [id])?;
+        // 后台任务不记审计日志，直接删除
+        self.conn
+            .execute("DELETE FROM jobs WHERE record_id = ?1", [id])
+            .db_context("删除任务失败")?;
+        audit::delete_rows(self.conn, "checkup_records", "id = ?1", [id])?;
+
+        Ok(stored_paths)
+    }
+
+    /// 记录关联的项目名称
+    fn project_names(&self, record_id: &str) -> AppResult<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT DISTINCT p.name FROM checkup_files f
+                 JOIN checkup_projects p ON f.project_id = p.id
+                 WHERE f.record_id = ?1 AND f.deleted_at IS NULL"
+            )
+            .db_context("查询项目名称失败")?;
+
+        stmt.query_map([record_id], |row| row.get(0))
+            .db_context("查询项目名称失败")?
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("解析项目名称失败")
+    }
+}
@@ -0,0 +1,1148 @@
+use super::ai_profile::CreateAiProfileInput;
+use super::file::NewCheckupFile;
+use super::indicator::{CreateIndicatorInput, UpdateIndicatorInput};
+use super::ocr::{OcrParsedItem, OcrTarget};
+use super::patient::CreatePatientInput;
+use super::project::{CreateProjectInput, UpdateProjectInput};
+use super::record::{CreateRecordInput, UpdateRecordInput};
+use super::trash::TrashKind;
+use super::*;
+use crate::db::migrations;
+use crate::services::TrendService;
+use rusqlite::Connection;
+use std::path::Path;
+
+/// 已执行全部迁移的内存数据库
+fn setup() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", true).unwrap();
+    migrations::run(&mut conn, Path::new("unused")).unwrap();
+    conn
+}
+
+fn count(conn: &Connection, table: &str) -> i64 {
+    conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+        .unwrap()
+}
+
+fn create_project(conn: &Connection, name: &str) -> String {
+    ProjectRepo::new(conn)
+        .create(CreateProjectInput {
+            name: name.to_string(),
+            description: None,
+            ocr_engine: None,
+        })
+        .unwrap()
+        .id
+}
+
+fn create_indicator(conn: &Connection, project_id: &str, name: &str) -> String {
+    IndicatorRepo::new(conn)
+        .create(CreateIndicatorInput {
+            project_id: project_id.to_string(),
+            name: name.to_string(),
+            unit: Some("mmol/L".to_string()),
+            reference_range: Some("3.9-6.1".to_string()),
+            is_core: Some(true),
+        })
+        .unwrap()
+        .id
+}
+
+/// 迁移创建的默认成员
+fn default_patient(conn: &Connection) -> String {
+    PatientRepo::new(conn).active_id().unwrap()
+}
+
+fn create_record(conn: &Connection, date: &str) -> String {
+    create_record_for(conn, &default_patient(conn), date)
+}
+
+fn create_record_for(conn: &Connection, patient_id: &str, date: &str) -> String {
+    RecordRepo::new(conn)
+        .create(patient_id, CreateRecordInput {
+            checkup_date: date.to_string(),
+            notes: None,
+        })
+        .unwrap()
+        .id
+}
+
+fn add_file(conn: &Connection, record_id: &str, project_id: &str, filename: &str) -> String {
+    FileRepo::new(conn)
+        .insert(NewCheckupFile {
+            id: uuid::Uuid::new_v4().to_string(),
+            record_id: record_id.to_string(),
+            project_id: project_id.to_string(),
+            project_name: "血常规".to_string(),
+            original_filename: filename.to_string(),
+            stored_path: format!("pictures/血常规/2024-01-01/{}", filename),
+            file_size: 3,
+            mime_type: "image/jpeg".to_string(),
+        })
+        .unwrap()
+        .id
+}
+
+fn create_ai_profile(conn: &Connection, name: &str, model: &str) -> String {
+    AiProfileRepo::new(conn)
+        .create(CreateAiProfileInput {
+            name: name.into(),
+            provider: None,
+            api_url: "https://example.com/v1/chat/completions".into(),
+            api_key: "key".into(),
+            proxy_enabled: None,
+            proxy_url: None,
+            proxy_username: None,
+            proxy_password: None,
+            model: model.into(),
+            parameters: None,
+        })
+        .unwrap()
+        .id
+}
+
+fn item(name: &str, value: &str) -> OcrParsedItem {
+    OcrParsedItem {
+        name: name.to_string(),
+        value: value.to_string(),
+        unit: String::new(),
+        reference_range: String::new(),
+        is_abnormal: false,
+        page: None,
+    }
+}
+
+/// 为文件保存一条识别成功的 OCR 结果
+fn save_ocr(conn: &Connection, record_id: &str, project_id: &str, file_id: &str, items: &[OcrParsedItem]) -> String {
+    let indicators = IndicatorRepo::new(conn).list_all().unwrap();
+    let checkup_date = RecordRepo::new(conn).checkup_date(record_id).unwrap();
+    let target = OcrTarget {
+        file_id,
+        record_id,
+        project_id,
+        checkup_date: &checkup_date,
+    };
+    OcrRepo::new(conn)
+        .save_success(&target, "[]", items, &indicators)
+        .unwrap()
+}
+
+#[test]
+fn project_crud() {
+    let conn = setup();
+    let repo = ProjectRepo::new(&conn);
+
+    let id = create_project(&conn, "血常规");
+    let updated = repo
+        .update(UpdateProjectInput {
+            id: id.clone(),
+            name: Some("血常规检查".to_string()),
+            description: None,
+            is_active: Some(false),
+            sort_order: Some(3),
+            ocr_engine: None,
+        })
+        .unwrap();
+    assert_eq!(updated.name, "血常规检查");
+    assert!(!updated.is_active);
+
+    let listed = repo.list().unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].sort_order, 3);
+    assert!(repo.list_active().unwrap().is_empty());
+
+    repo.delete(&id).unwrap();
+    assert_eq!(repo.get(&id).unwrap_err().code(), "not_found");
+}
+
+#[test]
+fn delete_project_refuses_with_files_and_purge_removes_indicators() {
+    let conn = setup();
+    let project_id = create_project(&conn, "血常规");
+    create_indicator(&conn, &project_id, "血糖");
+
+    let record_id = create_record(&conn, "2024-01-01");
+    let file_id = add_file(&conn, &record_id, &project_id, "a.jpg");
+
+    let err = ProjectRepo::new(&conn).delete(&project_id).unwrap_err();
+    assert_eq!(err.code(), "conflict");
+    assert_eq!(err.details()["count"], 1);
+
+    FileRepo::new(&conn).delete(&file_id).unwrap();
+    ProjectRepo::new(&conn).delete(&project_id).unwrap();
+    assert!(ProjectRepo::new(&conn).list().unwrap().is_empty());
+    assert_eq!(count(&conn, "checkup_projects"), 1);
+
+    // 彻底删除项目时一并删除回收站中属于该项目的文件
+    let purged = TrashRepo::new(&conn).purge(TrashKind::Project, &project_id).unwrap();
+    assert_eq!(purged.stored_paths, vec!["pictures/血常规/2024-01-01/a.jpg".to_string()]);
+    assert_eq!(count(&conn, "checkup_files"), 0);
+    assert_eq!(count(&conn, "indicators"), 0);
+    assert_eq!(count(&conn, "checkup_projects"), 0);
+}
+
+#[test]
+fn indicator_crud_and_delete_guard() {
+    let conn = setup();
+    let repo = IndicatorRepo::new(&conn);
+    let project_id = create_project(&conn, "血常规");
+    let id = create_indicator(&conn, &project_id, "血糖");
+
+    let updated = repo
+        .update(UpdateIndicatorInput {
+            id: id.clone(),
+            name: None,
+            unit: Some("mg/dL".to_string()),
+            reference_range: None,
+            is_core: Some(false),
+            sort_order: None,
+        })
+        .unwrap();
+    assert_eq!(updated.name, "血糖");
+    assert_eq!(updated.unit, "mg/dL");
+    assert_eq!(updated.reference_range, "3.9-6.1");
+    assert!(!updated.is_core);
+    assert_eq!(repo.list(&project_id).unwrap().len(), 1);
+
+    // 已有历史数据的指标不可删除
+    let record_id = create_record(&conn, "2024-01-01");
+    let file_id = add_file(&conn, &record_id, &project_id, "a.jpg");
+    save_ocr(&conn, &record_id, &project_id, &file_id, &[item("血糖", "5.2")]);
+    assert_eq!(repo.delete(&id).unwrap_err().code(), "conflict");
+
+    let other = create_indicator(&conn, &project_id, "尿酸");
+    repo.delete(&other).unwrap();
+    assert_eq!(repo.get(&other).unwrap_err().code(), "not_found");
+}
+
+#[test]
+fn record_crud() {
+    let conn = setup();
+    let repo = RecordRepo::new(&conn);
+
+    let id = create_record(&conn, "2024-01-01");
+    assert_eq!(repo.find_by_date(&default_patient(&conn), "2024-01-01").unwrap(), Some(id.clone()));
+    assert_eq!(repo.find_by_date(&default_patient(&conn), "2024-02-01").unwrap(), None);
+
+    repo.update(UpdateRecordInput {
+        id: id.clone(),
+        checkup_date: None,
+        notes: Some("空腹".to_string()),
+        status: None,
+    })
+    .unwrap();
+    let record = repo.get(&id).unwrap();
+    assert_eq!(record.notes, "空腹");
+    assert_eq!(record.status, "pending_upload");
+    assert_eq!(record.file_count, Some(0));
+
+    let missing = repo.update(UpdateRecordInput {
+        id: "missing".to_string(),
+        checkup_date: None,
+        notes: None,
+        status: None,
+    });
+    assert_eq!(missing.unwrap_err().code(), "not_found");
+
+    create_record(&conn, "2024-03-01");
+    let listed = repo.list(&default_patient(&conn)).unwrap();
+    assert_eq!(listed.len(), 2);
+    assert_eq!(listed[0].checkup_date, "2024-03-01");
+}
+
+#[test]
+fn upload_advances_record_status_and_fills_project_names() {
+    let conn = setup();
+    let project_id = create_project(&conn, "血常规");
+    let record_id = create_record(&conn, "2024-01-01");
+
+    add_file(&conn, &record_id, &project_id, "a.jpg");
+    add_file(&conn, &record_id, &project_id, "b.jpg");
+
+    let record = RecordRepo::new(&conn).get(&record_id).unwrap();
+    assert_eq!(record.status, "pending_ocr");
+    assert_eq!(record.file_count, Some(2));
+    assert_eq!(record.project_names, Some(vec!["血常规".to_string()]));
+
+    // 已进入后续状态的记录不会被回退
+    RecordRepo::new(&conn).set_status(&record_id, "ocr_done").unwrap();
+    add_file(&conn, &record_id, &project_id, "c.jpg");
+    assert_eq!(RecordRepo::new(&conn).status(&record_id).unwrap(), "ocr_done");
+}
+
+#[test]
+fn purge_record_cascades_to_all_related_rows() {
+    let conn = setup();
+    let project_id = create_project(&conn, "血常规");
+    create_indicator(&conn, &project_id, "血糖");
+
+    let record_id = create_record(&conn, "2024-01-01");
+    let file_id = add_file(&conn, &record_id, &project_id, "a.jpg");
+    save_ocr(&conn, &record_id, &project_id, &file_id, &[item("血糖", "5.2")]);
+    AiRepo::new(&conn).create(&record_id, "prompt", "model").unwrap();
+
+    // 另一条记录的数据不受影响
+    let other_record = create_record(&conn, "2024-02-01");
+    let other_file = add_file(&conn, &other_record, &project_id, "b.jpg");
+    save_ocr(&conn, &other_record, &project_id, &other_file, &[item("血糖", "5.8")]);
+
+    RecordRepo::new(&conn).delete(&record_id).unwrap();
+    assert_eq!(RecordRepo::new(&conn).get(&record_id).unwrap_err().code(), "not_found");
+    assert_eq!(count(&conn, "checkup_records"), 2);
+
+    let purged = TrashRepo::new(&conn).purge(TrashKind::Record, &record_id).unwrap();
+    assert_eq!(purged.stored_paths, vec!["pictures/血常规/2024-01-01/a.jpg".to_string()]);
+    assert_eq!(count(&conn, "checkup_records"), 1);
+    assert_eq!(count(&conn, "checkup_files"), 1);
+    assert_eq!(count(&conn, "ocr_results"), 1);
+    assert_eq!(count(&conn, "indicator_values"), 1);
+    assert_eq!(count(&conn, "ai_analyses"), 0);
+    assert_eq!(count(&conn, "indicators"), 1);
+    assert_eq!(count(&conn, "checkup_projects"), 1);
+}
+
+#[test]
+fn purge_file_cascades_to_its_ocr_results() {
+    let conn = setup();
+    let project_id = create_project(&conn, "血常规");
+    create_indicator(&conn, &project_id, "血糖");
+    let record_id = create_record(&conn, "2024-01-01");
+
+    let file_a = add_file(&conn, &record_id, &project_id, "a.jpg");
+    let file_b = add_file(&conn, &record_id, &project_id, "b.jpg");
+    save_ocr(&conn, &record_id, &project_id, &file_a, &[item("血糖", "5.2")]);
+    save_ocr(&conn, &record_id, &project_id, &file_b, &[item("血糖", "5.4")]);
+
+    FileRepo::new(&conn).delete(&file_a).unwrap();
+    let files = FileRepo::new(&conn).list(&record_id).unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].id, file_b);
+    assert_eq!(OcrRepo::new(&conn).list(&record_id).unwrap().len(), 1);
+
+    let purged = TrashRepo::new(&conn).purge(TrashKind::File, &file_a).unwrap();
+    assert_eq!(purged.stored_paths, vec!["pictures/血常规/2024-01-01/a.jpg".to_string()]);
+    assert_eq!(count(&conn, "ocr_results"), 1);
+    assert_eq!(count(&conn, "indicator_values"), 1);
+    assert_eq!(count(&conn, "checkup_records"), 1);
+
+    assert_eq!(FileRepo::new(&conn).delete(&file_a).unwrap_err().code(), "not_found");
+    assert_eq!(TrashRepo::new(&conn).purge(TrashKind::File, &file_b).unwrap_err().code(), "not_found");
+}
+
+#[test]
+fn ocr_results_match_indicators_and_feed_trends() {
+    let conn = setup();
+    let project_id = create_project(&conn, "血常规");
+    create_indicator(&conn, &project_id, "血糖(GLU)");
+    let record_id = create_record(&conn, "2024-01-01");
+    let file_a = add_file(&conn, &record_id, &project_id, "a.jpg");
+    let file_b = add_file(&conn, &record_id, &project_id, "b.jpg");
+
+    save_ocr(&conn, &record_id, &project_id, &file_a, &[item("血糖", "5.2"), item("未知指标", "1")]);
+    let target = OcrTarget {
+        file_id: &file_b,
+        record_id: &record_id,
+        project_id: &project_id,
+        checkup_date: "2024-01-01",
+    };
+    OcrRepo::new(&conn).save_failure(&target, "请求失败").unwrap();
+
+    let repo = OcrRepo::new(&conn);
+    let counts = repo.counts(&record_id).unwrap();
+    assert_eq!((counts.total_files, counts.total_ocr, counts.success_ocr, counts.failed_ocr), (2, 2, 1, 1));
+
+    // 识别成功的文件不再重复识别
+    let pending = repo.pending_files(&record_id).unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].file_id, file_b);
+
+    let parsed = repo.parsed_for_record(&record_id).unwrap();
+    assert_eq!(parsed.len(), 1);
+    assert_eq!(parsed[0].project_name, "血常规");
+    assert!(repo.parsed_history(&record_id).unwrap().is_empty());
+
+    let trend = TrendService::new(&conn).project_trend(&default_patient(&conn), &project_id).unwrap();
+    assert_eq!(trend.indicators.len(), 1);
+    assert_eq!(trend.indicators[0].data_points.len(), 1);
+    assert_eq!(trend.indicators[0].data_points[0].value, Some(5.2));
+}
+
+#[test]
+fn ai_analysis_lifecycle() {
+    let conn = setup();
+    let record_id = create_record(&conn, "2024-01-01");
+    let repo = AiRepo::new(&conn);
+
+    let failed = repo.create(&record_id, "prompt", "model").unwrap();
+    repo.fail(&failed, "超时").unwrap();
+    let done = repo.create(&record_id, "prompt", "model").unwrap();
+    repo.complete(&done, "## 结论").unwrap();
+
+    let analyses = repo.list(&record_id).unwrap();
+    assert_eq!(analyses.len(), 2);
+    let failed = analyses.iter().find(|a| a.id == failed).unwrap();
+    assert_eq!((failed.status.as_str(), failed.error_message.as_str()), ("failed", "超时"));
+    let done = analyses.iter().find(|a| a.id == done).unwrap();
+    assert_eq!((done.status.as_str(), done.response_content.as_str()), ("success", "## 结论"));
+}
+
+#[test]
+fn config_get_and_set() {
+    let conn = setup();
+    let repo = ConfigRepo::new(&conn);
+
+    assert_eq!(repo.get("ai_api_url").unwrap(), None);
+    assert_eq!(repo.get_or("ai_api_url", "默认").unwrap(), "默认");
+
+    repo.set("ai_api_url", "https://a").unwrap();
+    repo.set("ai_api_url", "https://b").unwrap();
+    assert_eq!(repo.get("ai_api_url").unwrap(), Some("https://b".to_string()));
+    assert_eq!(count(&conn, "system_config"), 1);
+}
+
+#[test]
+fn relativize_paths_only_rewrites_paths_under_base_dir() {
+    let conn = setup();
+    let project_id = create_project(&conn, "血常规");
+    let record_id = create_record(&conn, "2024-01-01");
+    let inside = add_file(&conn, &record_id, &project_id, "a.jpg");
+    let sibling = add_file(&conn, &record_id, &project_id, "b.jpg");
+    let relative = add_file(&conn, &record_id, &project_id, "c.jpg");
+
+    let set_path = |id: &str, path: &str| {
+        conn.execute("UPDATE checkup_files SET stored_path = ?1 WHERE id = ?2", [path, id])
+            .unwrap();
+    };
+    set_path(&inside, "/opt/健康管家/pictures/血常规/a.jpg");
+    set_path(&sibling, "/opt/健康管家2/pictures/b.jpg");
+
+    let repo = FileRepo::new(&conn);
+    assert_eq!(repo.relativize_paths("/opt/健康管家/").unwrap(), 1);
+    assert_eq!(repo.location(&inside).unwrap().0, "pictures/血常规/a.jpg");
+    assert_eq!(repo.location(&sibling).unwrap().0, "/opt/健康管家2/pictures/b.jpg");
+    assert_eq!(repo.location(&relative).unwrap().0, "pictures/血常规/2024-01-01/c.jpg");
+}
+
+fn audit_entries(conn: &Connection, table: &str, action: &str) -> Vec<audit::AuditEntry> {
+    AuditRepo::new(conn)
+        .list(&audit::AuditFilter {
+            table_name: Some(table.to_string()),
+            action: Some(action.to_string()),
+            ..Default::default()
+        })
+        .unwrap()
+}
+
+#[test]
+fn mutations_write_audit_log_and_update_can_be_undone() {
+    let conn = setup();
+    let project_id = create_project(&conn, "血常规");
+    let indicator_id = create_indicator(&conn, &project_id, "血糖");
+    assert_eq!(audit_entries(&conn, "indicators", "create").len(), 1);
+
+    IndicatorRepo::new(&conn)
+        .update(UpdateIndicatorInput {
+            id: indicator_id.clone(),
+            name: Some("空腹血糖".to_string()),
+            unit: None,
+            reference_range: None,
+            is_core: None,
+            sort_order: None,
+        })
+        .unwrap();
+    let updates = audit_entries(&conn, "indicators", "update");
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0].before.as_ref().unwrap()["name"], "血糖");
+    assert_eq!(updates[0].after.as_ref().unwrap()["name"], "空腹血糖");
+
+    let undo = AuditRepo::new(&conn).undo(&updates[0].id).unwrap();
+    assert_eq!(undo.undo_of.as_deref(), Some(updates[0].id.as_str()));
+    assert_eq!(IndicatorRepo::new(&conn).get(&indicator_id).unwrap().name, "血糖");
+
+    // 同一条变更不能重复撤销
+    let again = AuditRepo::new(&conn).undo(&updates[0].id).unwrap_err();
+    assert_eq!(again.code(), "conflict");
+
+    // 撤销本身也可以再撤销
+    AuditRepo::new(&conn).undo(&undo.id).unwrap();
+    assert_eq!(IndicatorRepo::new(&conn).get(&indicator_id).unwrap().name, "空腹血糖");
+}
+
+#[test]
+fn undo_refuses_when_row_changed_afterwards() {
+    let conn = setup();
+    let record_id = create_record(&conn, "2024-01-01");
+    let update = |notes: &str| {
+        RecordRepo::new(&conn)
+            .update(UpdateRecordInput {
+                id: record_id.clone(),
+                checkup_date: None,
+                notes: Some(notes.to_string()),
+                status: None,
+            })
+            .unwrap();
+    };
+    update("第一次");
+    update("第二次");
+
+    let updates = audit_entries(&conn, "checkup_records", "update");
+    assert_eq!(updates.len(), 2);
+    let first = updates.iter().find(|e| e.after.as_ref().unwrap()["notes"] == "第一次").unwrap();
+    assert_eq!(AuditRepo::new(&conn).undo(&first.id).unwrap_err().code(), "conflict");
+
+    // 撤销创建时，记录已被修改同样拒绝
+    let created = &audit_entries(&conn, "checkup_records", "create")[0];
+    assert_eq!(AuditRepo::new(&conn).undo(&created.id).unwrap_err().code(), "conflict");
+}
+
+#[test]
+fn cascaded_delete_can_be_undone_parent_first() {
+    let conn = setup();
+    let project_id = create_project(&conn, "血常规");
+    create_indicator(&conn, &project_id, "血糖");
+    let record_id = create_record(&conn, "2024-01-01");
+    let file_id = add_file(&conn, &record_id, &project_id, "a.jpg");
+    save_ocr(&conn, &record_id, &project_id, &file_id, &[item("血糖", "5.2")]);
+
+    RecordRepo::new(&conn).purge(&record_id).unwrap();
+    let repo = AuditRepo::new(&conn);
+    let deleted = |table: &str| audit_entries(&conn, table, "delete").remove(0);
+
+    // 上级数据未恢复时，子数据的撤销因外键约束被拒绝
+    let value = deleted("indicator_values");
+    assert_eq!(repo.undo(&value.id).unwrap_err().code(), "conflict");
+
+    for table in ["checkup_records", "checkup_files", "ocr_results", "indicator_values"] {
+        repo.undo(&deleted(table).id).unwrap();
+    }
+
+    assert_eq!(RecordRepo::new(&conn).get(&record_id).unwrap().file_count, Some(1));
+    let trend = TrendService::new(&conn).project_trend(&default_patient(&conn), &project_id).unwrap();
+    assert_eq!(trend.indicators[0].data_points[0].value, Some(5.2));
+}
+
+#[test]
+fn config_changes_are_audited() {
+    let conn = setup();
+    let repo = ConfigRepo::new(&conn);
+    repo.set("ai_default_model", "gpt-4o").unwrap();
+    repo.set("ai_default_model", "gpt-4o").unwrap();
+    repo.set("ai_default_model", "qwen").unwrap();
+
+    assert_eq!(audit_entries(&conn, "system_config", "create").len(), 1);
+    let updates = audit_entries(&conn, "system_config", "update");
+    assert_eq!(updates.len(), 1);
+
+    AuditRepo::new(&conn).undo(&updates[0].id).unwrap();
+    assert_eq!(repo.get("ai_default_model").unwrap().as_deref(), Some("gpt-4o"));
+}
+
+/// 写入指定表的审计日志时失败
+fn fail_audit_for(conn: &Connection, table: &str) {
+    conn.execute_batch(&format!(
+        "CREATE TEMP TRIGGER fail_audit BEFORE INSERT ON audit_log WHEN NEW.table_name = '{}'
+         BEGIN SELECT RAISE(ABORT, 'audit failed'); END;",
+        table
+    ))
+    .unwrap();
+}
+
+#[test]
+fn mutation_is_rolled_back_when_audit_fails() {
+    let conn = setup();
+    let record_id = create_record(&conn, "2024-01-01");
+    let project_id = create_project(&conn, "血常规");
+
+    fail_audit_for(&conn, "checkup_records");
+    let update = RecordRepo::new(&conn).update(UpdateRecordInput {
+        id: record_id.clone(),
+        checkup_date: Some("2024-02-02".into()),
+        notes: None,
+        status: None,
+    });
+    assert!(update.is_err());
+    assert_eq!(RecordRepo::new(&conn).checkup_date(&record_id).unwrap(), "2024-01-01");
+    conn.execute_batch("DROP TRIGGER fail_audit").unwrap();
+
+    fail_audit_for(&conn, "checkup_projects");
+    let update = ProjectRepo::new(&conn).update(UpdateProjectInput {
+        id: project_id.clone(),
+        name: Some("肝功能".into()),
+        description: None,
+        is_active: None,
+        sort_order: None,
+        ocr_engine: None,
+    });
+    assert!(update.is_err());
+    assert_eq!(ProjectRepo::new(&conn).name_of(&project_id).unwrap(), "血常规");
+    conn.execute_batch("DROP TRIGGER fail_audit").unwrap();
+
+    // 任务配置写入失败时，新建的 AI 配置一并回滚
+    fail_audit_for(&conn, "system_config");
+    assert!(ConfigRepo::new(&conn).set("ai_default_model", "qwen").is_err());
+    assert_eq!(ConfigRepo::new(&conn).get("ai_default_model").unwrap(), None);
+    let created = AiProfileRepo::new(&conn).create(CreateAiProfileInput {
+        name: "默认".into(),
+        provider: None,
+        api_url: "https://example.com/v1/chat/completions".into(),
+        api_key: "key".into(),
+        proxy_enabled: None,
+        proxy_url: None,
+        proxy_username: None,
+        proxy_password: None,
+        model: "gpt-4o".into(),
+        parameters: None,
+    });
+    assert!(created.is_err());
+    assert_eq!(count(&conn, "ai_profiles"), 0);
+    assert!(conn.is_autocommit());
+}
+
+#[test]
+fn secrets_are_redacted_in_audit_log_and_kept_on_undo() {
+    let conn = setup();
+    let first = create_ai_profile(&conn, "默认", "gpt-4o");
+    let second = create_ai_profile(&conn, "备用", "qwen");
+    let profiles = AiProfileRepo::new(&conn);
+    profiles
+        .update(super::ai_profile::UpdateAiProfileInput {
+            id: first.clone(),
+            name: Some("主配置".into()),
+            provider: None,
+            api_url: None,
+            api_key: Some("new-key".into()),
+            proxy_enabled: None,
+            proxy_url: None,
+            proxy_username: None,
+            proxy_password: Some("secret".into()),
+            model: None,
+            parameters: None,
+        })
+        .unwrap();
+    profiles.delete(&second).unwrap();
+    ConfigRepo::new(&conn).set("api_server_token", "token-1").unwrap();
+    ConfigRepo::new(&conn).set("api_server_token", "token-2").unwrap();
+
+    let update = &audit_entries(&conn, "ai_profiles", "update")[0];
+    assert_eq!(update.before.as_ref().unwrap()["api_key"], audit::REDACTED);
+    assert_eq!(update.after.as_ref().unwrap()["api_key"], audit::REDACTED);
+    assert_eq!(update.after.as_ref().unwrap()["proxy_password"], audit::REDACTED);
+    assert_eq!(update.after.as_ref().unwrap()["name"], "主配置");
+    let created = audit_entries(&conn, "system_config", "create");
+    let token = created.iter().find(|e| e.after.as_ref().unwrap()["config_key"] == "api_server_token").unwrap();
+    assert_eq!(token.after.as_ref().unwrap()["config_value"], audit::REDACTED);
+    // 仅密钥变化时快照相同，不产生记录
+    assert!(audit_entries(&conn, "system_config", "update").is_empty());
+
+    // 撤销时隐藏的密钥保持当前值，原本为空的字段照常恢复；已删除配置恢复后需重新填写密钥
+    AuditRepo::new(&conn).undo(&update.id).unwrap();
+    let restored = profiles.get(&first).unwrap();
+    assert_eq!(restored.name, "默认");
+    assert_eq!(restored.api_key, "new-key");
+    assert_eq!(restored.proxy_password, "");
+    let deleted = &audit_entries(&conn, "ai_profiles", "delete")[0];
+    AuditRepo::new(&conn).undo(&deleted.id).unwrap();
+    assert_eq!(profiles.get(&second).unwrap().api_key, "");
+}
+
+#[test]
+fn trash_move_is_rolled_back_when_audit_fails() {
+    let conn = setup();
+    let record_id = create_record(&conn, "2024-01-01");
+    RecordRepo::new(&conn).delete(&record_id).unwrap();
+
+    fail_audit_for(&conn, "checkup_records");
+    assert!(TrashRepo::new(&conn).restore(TrashKind::Record, &record_id).is_err());
+    assert_eq!(TrashRepo::new(&conn).list(&default_patient(&conn)).unwrap().len(), 1);
+    conn.execute_batch("DROP TRIGGER fail_audit").unwrap();
+
+    TrashRepo::new(&conn).restore(TrashKind::Record, &record_id).unwrap();
+    fail_audit_for(&conn, "checkup_records");
+    assert!(RecordRepo::new(&conn).delete(&record_id).is_err());
+    assert_eq!(RecordRepo::new(&conn).list(&default_patient(&conn)).unwrap().len(), 1);
+}
+
+#[test]
+fn trashed_rows_are_hidden_until_restored() {
+    let conn = setup();
+    let project_id = create_project(&conn, "血常规");
+    create_indicator(&conn, &project_id, "血糖");
+    let record_a = create_record(&conn, "2024-01-01");
+    let file_a = add_file(&conn, &record_a, &project_id, "a.jpg");
+    save_ocr(&conn, &record_a, &project_id, &file_a, &[item("血糖", "5.2")]);
+    let record_b = create_record(&conn, "2024-02-01");
+    let file_b = add_file(&conn, &record_b, &project_id, "b.jpg");
+    save_ocr(&conn, &record_b, &project_id, &file_b, &[item("血糖", "5.8")]);
+
+    let points = |conn: &Connection| {
+        TrendService::new(conn).project_trend(&default_patient(conn), &project_id).unwrap().indicators[0]
+            .data_points
+            .iter()
+            .map(|p| p.checkup_date.clone())
+            .collect::<Vec<_>>()
+    };
+
+    RecordRepo::new(&conn).delete(&record_a).unwrap();
+    FileRepo::new(&conn).delete(&file_b).unwrap();
+    assert_eq!(RecordRepo::new(&conn).list(&default_patient(&conn)).unwrap().len(), 1);
+    assert_eq!(RecordRepo::new(&conn).get(&record_b).unwrap().file_count, Some(0));
+    assert_eq!(RecordRepo::new(&conn).find_by_date(&default_patient(&conn), "2024-01-01").unwrap(), None);
+    assert!(OcrRepo::new(&conn).parsed_history("other").unwrap().is_empty());
+    assert!(points(&conn).is_empty());
+
+    let trash = TrashRepo::new(&conn);
+    let items = trash.list(&default_patient(&conn)).unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].kind, TrashKind::File);
+    assert_eq!(items[0].record_id.as_deref(), Some(record_b.as_str()));
+
+    trash.restore(TrashKind::Record, &record_a).unwrap();
+    assert_eq!(points(&conn), vec!["2024-01-01".to_string()]);
+    trash.restore(TrashKind::File, &file_b).unwrap();
+    assert_eq!(points(&conn), vec!["2024-01-01".to_string(), "2024-02-01".to_string()]);
+    assert!(trash.list(&default_patient(&conn)).unwrap().is_empty());
+    assert_eq!(trash.restore(TrashKind::File, &file_b).unwrap_err().code(), "not_found");
+
+    // 移入回收站可以通过审计日志撤销
+    RecordRepo::new(&conn).delete(&record_b).unwrap();
+    let trashed = audit_entries(&conn, "checkup_records", "update").remove(0);
+    AuditRepo::new(&conn).undo(&trashed.id).unwrap();
+    assert_eq!(RecordRepo::new(&conn).list(&default_patient(&conn)).unwrap().len(), 2);
+}
+
+#[test]
+fn restoring_file_requires_its_record_restored_first() {
+    let conn = setup();
+    let project_id = create_project(&conn, "血常规");
+    let record_id = create_record(&conn, "2024-01-01");
+    let file_id = add_file(&conn, &record_id, &project_id, "a.jpg");
+
+    FileRepo::new(&conn).delete(&file_id).unwrap();
+    RecordRepo::new(&conn).delete(&record_id).unwrap();
+
+    let trash = TrashRepo::new(&conn);
+    assert_eq!(trash.restore(TrashKind::File, &file_id).unwrap_err().code(), "conflict");
+    trash.restore(TrashKind::Record, &record_id).unwrap();
+    trash.restore(TrashKind::File, &file_id).unwrap();
+    assert_eq!(FileRepo::new(&conn).list(&record_id).unwrap().len(), 1);
+}
+
+#[test]
+fn purge_all_only_removes_items_deleted_before_cutoff() {
+    let conn = setup();
+    let project_id = create_project(&conn, "血常规");
+    let old_record = create_record(&conn, "2024-01-01");
+    add_file(&conn, &old_record, &project_id, "a.jpg");
+    let new_record = create_record(&conn, "2024-02-01");
+    let kept_project = create_project(&conn, "尿常规");
+    let live_record = create_record(&conn, "2024-03-01");
+    add_file(&conn, &live_record, &kept_project, "b.jpg");
+
+    RecordRepo::new(&conn).delete(&old_record).unwrap();
+    RecordRepo::new(&conn).delete(&new_record).unwrap();
+    // 删除时间统一保存为 UTC
+    let deleted_at: String = conn
+        .query_row("SELECT deleted_at FROM checkup_records WHERE id = ?1", [&new_record], |row| row.get(0))
+        .unwrap();
+    assert!(deleted_at.ends_with('Z'), "{}", deleted_at);
+
+    let long_ago = crate::repo::trash::deleted_at("2024-01-01T16:00:00Z".parse().unwrap());
+    conn.execute("UPDATE checkup_records SET deleted_at = ?1 WHERE id = ?2", [&long_ago, &old_record])
+        .unwrap();
+    conn.execute("UPDATE checkup_projects SET deleted_at = ?1 WHERE id = ?2", [&long_ago, &kept_project])
+        .unwrap();
+
+    let trash = TrashRepo::new(&conn);
+    let cutoff = crate::repo::trash::deleted_at("2024-06-01T00:00:00Z".parse().unwrap());
+    let purged = trash.purge_all(Some(&cutoff), None).unwrap();
+    assert_eq!(purged.count, 1);
+    assert_eq!(purged.stored_paths, vec!["pictures/血常规/2024-01-01/a.jpg".to_string()]);
+
+    // 仍有文件引用的项目保留在回收站中
+    let remaining = trash.list(&default_patient(&conn)).unwrap();
+    assert_eq!(remaining.len(), 2);
+
+    let purged = trash.purge_all(None, None).unwrap();
+    assert_eq!(purged.count, 1);
+    assert_eq!(count(&conn, "checkup_records"), 1);
+    assert_eq!(count(&conn, "checkup_projects"), 2);
+}
+
+#[test]
+fn dangling_references_are_reported_and_deleted_with_dependents() {
+    let conn = setup();
+    let project_id = create_project(&conn, "血常规");
+    let indicator_id = create_indicator(&conn, &project_id, "血糖");
+    let record_id = create_record(&conn, "2024-01-01");
+    let file_id = add_file(&conn, &record_id, &project_id, "a.jpg");
+    save_ocr(&conn, &record_id, &project_id, &file_id, &[item("血糖", "5.2")]);
+
+    let repo = integrity::IntegrityRepo::new(&conn);
+    assert!(repo.dangling_references().unwrap().is_empty());
+    assert!(repo.sqlite_check().unwrap().is_empty());
+
+    // 模拟外键未开启时留下的脏数据
+    conn.execute_batch("PRAGMA foreign_keys = OFF").unwrap();
+    conn.execute("DELETE FROM checkup_files WHERE id = ?1", [&file_id]).unwrap();
+    conn.execute("DELETE FROM indicators WHERE id = ?1", [&indicator_id]).unwrap();
+    conn.execute_batch("PRAGMA foreign_keys = ON").unwrap();
+
+    let dangling = repo.dangling_references().unwrap();
+    assert_eq!(dangling.len(), 2);
+    let ocr = dangling.iter().find(|d| d.table_name == "ocr_results").unwrap();
+    assert_eq!(ocr.column, "file_id");
+    assert_eq!(ocr.parent_id, file_id);
+    let value = dangling.iter().find(|d| d.table_name == "indicator_values").unwrap();
+    assert_eq!(value.column, "indicator_id");
+    assert_eq!(value.parent_table, "indicators");
+
+    assert_eq!(repo.delete_dangling().unwrap(), 2);
+    assert!(repo.dangling_references().unwrap().is_empty());
+    assert_eq!(count(&conn, "ocr_results"), 0);
+    assert_eq!(count(&conn, "indicator_values"), 0);
+    assert_eq!(count(&conn, "checkup_records"), 1);
+}
+
+#[test]
+fn records_trends_and_history_are_scoped_to_one_patient() {
+    let conn = setup();
+    let repo = PatientRepo::new(&conn);
+    let me = default_patient(&conn);
+    let child = repo
+        .create(CreatePatientInput {
+            name: "孩子".to_string(),
+            gender: None,
+            birth_date: Some("2015-06-01".to_string()),
+            notes: None,
+        })
+        .unwrap()
+        .id;
+
+    let project_id = create_project(&conn, "血常规");
+    create_indicator(&conn, &project_id, "血糖");
+    let mine = create_record_for(&conn, &me, "2024-01-01");
+    let my_file = add_file(&conn, &mine, &project_id, "a.jpg");
+    save_ocr(&conn, &mine, &project_id, &my_file, &[item("血糖", "5.2")]);
+    let theirs = create_record_for(&conn, &child, "2024-02-01");
+    let their_file = add_file(&conn, &theirs, &project_id, "b.jpg");
+    save_ocr(&conn, &theirs, &project_id, &their_file, &[item("血糖", "4.8")]);
+    let their_next = create_record_for(&conn, &child, "2024-03-01");
+
+    let records = RecordRepo::new(&conn);
+    assert_eq!(records.list(&me).unwrap().len(), 1);
+    assert_eq!(records.list(&child).unwrap().len(), 2);
+    assert_eq!(records.find_by_date(&child, "2024-01-01").unwrap(), None);
+
+    // 按 ID 访问其他成员的记录与文件视为不存在
+    let files = FileRepo::new(&conn);
+    records.ensure_active(&mine).unwrap();
+    files.ensure_active(&my_file).unwrap();
+    assert_eq!(records.ensure_active(&theirs).unwrap_err().code(), "not_found");
+    assert_eq!(files.ensure_active(&their_file).unwrap_err().code(), "not_found");
+    assert_eq!(records.ensure_active("missing").unwrap_err().code(), "not_found");
+
+    let trend = TrendService::new(&conn).project_trend(&child, &project_id).unwrap();
+    let values: Vec<_> = trend.indicators[0].data_points.iter().map(|p| p.value).collect();
+    assert_eq!(values, vec![Some(4.8)]);
+
+    let history = OcrRepo::new(&conn).parsed_history(&their_next).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].checkup_date, "2024-02-01");
+
+    // 切换当前成员；仍有记录的成员不能删除
+    assert_eq!(repo.active_id().unwrap(), me);
+    repo.set_active(&child).unwrap();
+    assert_eq!(repo.active_id().unwrap(), child);
+    records.ensure_active(&theirs).unwrap();
+    assert_eq!(records.ensure_active(&mine).unwrap_err().code(), "not_found");
+    assert_eq!(repo.delete(&child).unwrap_err().code(), "conflict");
+    assert_eq!(repo.set_active("missing").unwrap_err().code(), "not_found");
+}
+
+#[test]
+fn trash_and_jobs_are_scoped_to_the_active_patient() {
+    use super::job::{JobFilter, JobKind};
+
+    let conn = setup();
+    let patients = PatientRepo::new(&conn);
+    let me = default_patient(&conn);
+    let child = patients
+        .create(CreatePatientInput { name: "孩子".to_string(), gender: None, birth_date: None, notes: None })
+        .unwrap()
+        .id;
+    let project_id = create_project(&conn, "血常规");
+    let mine = create_record_for(&conn, &me, "2024-01-01");
+    let my_file = add_file(&conn, &mine, &project_id, "a.jpg");
+    let theirs = create_record_for(&conn, &child, "2024-02-01");
+    let their_file = add_file(&conn, &theirs, &project_id, "b.jpg");
+    let jobs = JobRepo::new(&conn);
+    let my_job = jobs.enqueue(JobKind::Ocr, &mine).unwrap();
+    jobs.enqueue(JobKind::Ocr, &theirs).unwrap();
+
+    FileRepo::new(&conn).delete(&my_file).unwrap();
+    RecordRepo::new(&conn).delete(&theirs).unwrap();
+    FileRepo::new(&conn).delete(&their_file).unwrap();
+
+    let trash = TrashRepo::new(&conn);
+    let listed: Vec<_> = trash.list(&me).unwrap().into_iter().map(|item| item.id).collect();
+    assert_eq!(listed, vec![my_file.clone()]);
+    trash.ensure_active(TrashKind::File, &my_file).unwrap();
+    assert_eq!(trash.ensure_active(TrashKind::Record, &theirs).unwrap_err().code(), "not_found");
+    assert_eq!(trash.ensure_active(TrashKind::File, &their_file).unwrap_err().code(), "not_found");
+    let listed: Vec<_> = jobs.list(Some(&me), &JobFilter::default()).unwrap().into_iter().map(|j| j.id).collect();
+    assert_eq!(listed, vec![my_job.id.clone()]);
+    assert_eq!(jobs.list(None, &JobFilter::default()).unwrap().len(), 2);
+
+    // 切换成员后，原成员的数据按不存在处理
+    patients.set_active(&child).unwrap();
+    assert_eq!(trash.ensure_active(TrashKind::File, &my_file).unwrap_err().code(), "not_found");
+    assert_eq!(RecordRepo::new(&conn).ensure_active(&my_job.record_id).unwrap_err().code(), "not_found");
+    trash.ensure_active(TrashKind::Record, &theirs).unwrap();
+    assert_eq!(trash.list(&child).unwrap().len(), 2);
+
+    // 清空回收站只删除当前成员的数据，项目仍被其他成员的文件引用时保留
+    ProjectRepo::new(&conn).delete(&project_id).unwrap();
+    let purged = trash.purge_all(None, Some(&child)).unwrap();
+    assert_eq!(purged.count, 2);
+    let kinds: Vec<_> = trash.list(&me).unwrap().into_iter().map(|item| item.kind).collect();
+    assert_eq!(kinds, vec![TrashKind::Project, TrashKind::File]);
+}
+
+#[test]
+fn last_patient_cannot_be_deleted_and_active_falls_back() {
+    let conn = setup();
+    let repo = PatientRepo::new(&conn);
+    let me = default_patient(&conn);
+    assert_eq!(repo.delete(&me).unwrap_err().code(), "validation");
+
+    let other = repo
+        .create(CreatePatientInput {
+            name: "父亲".to_string(),
+            gender: Some("男".to_string()),
+            birth_date: None,
+            notes: None,
+        })
+        .unwrap()
+        .id;
+    repo.set_active(&other).unwrap();
+    repo.delete(&other).unwrap();
+    assert_eq!(repo.active_id().unwrap(), me);
+}
+
+#[test]
+fn interrupted_jobs_are_requeued_until_attempts_run_out() {
+    use super::job::{JobFilter, JobKind, JobStatus, MAX_ATTEMPTS};
+
+    let conn = setup();
+    let record = create_record(&conn, "2024-01-01");
+    let repo = JobRepo::new(&conn);
+
+    let job = repo.enqueue(JobKind::Ocr, &record).unwrap();
+    assert_eq!(job.status, JobStatus::Queued);
+    assert_eq!(repo.enqueue(JobKind::Ocr, &record).unwrap_err().code(), "busy");
+    // 不同类型的任务互不影响
+    let ai_job = repo.enqueue(JobKind::AiAnalysis, &record).unwrap();
+
+    assert_eq!(repo.next_queued(JobKind::Ocr).unwrap().unwrap().id, job.id);
+    assert_eq!(repo.next_queued(JobKind::AiAnalysis).unwrap().unwrap().id, ai_job.id);
+    assert!(repo.claim(&job.id, "cli").unwrap());
+    assert!(!repo.claim(&job.id, "desktop").unwrap());
+    repo.set_progress(&job.id, 1, 3).unwrap();
+
+    // 心跳仍在更新的任务（如在命令行工具中执行）不会被重新排队
+    repo.heartbeat(&job.id).unwrap();
+    assert_eq!(repo.requeue_interrupted(60).unwrap(), (0, 0));
+    assert_eq!(repo.get(&job.id).unwrap().status, JobStatus::Running);
+
+    // 模拟程序在执行中退出，心跳不再更新
+    for attempt in 1..MAX_ATTEMPTS {
+        assert_eq!(repo.requeue_interrupted(0).unwrap(), (1, 0));
+        let job = repo.get(&job.id).unwrap();
+        assert_eq!((job.status, job.attempts), (JobStatus::Queued, attempt));
+        assert!(repo.claim(&job.id, "desktop").unwrap());
+    }
+    assert_eq!(repo.requeue_interrupted(0).unwrap(), (0, 1));
+    let job = repo.get(&job.id).unwrap();
+    assert_eq!(job.status, JobStatus::Failed);
+    assert_eq!(job.progress_done, 1);
+    assert!(job.last_error.is_some());
+
+    let failed = repo
+        .list(None, &JobFilter {
+            status: Some(JobStatus::Failed),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(failed.len(), 1);
+
+    // 没有未完成任务却停留在识别中的记录需要修正
+    RecordRepo::new(&conn).set_status(&record, "ocr_processing").unwrap();
+    assert_eq!(repo.records_without_active_job(JobKind::Ocr, "ocr_processing").unwrap(), vec![record.clone()]);
+
+    RecordRepo::new(&conn).purge(&record).unwrap();
+    assert_eq!(count(&conn, "jobs"), 0);
+}
+
+#[test]
+fn cancelled_results_keep_files_pending_and_partial_analysis() {
+    use super::job::{JobKind, JobStatus};
+
+    let conn = setup();
+    let project_id = create_project(&conn, "血常规");
+    let record_id = create_record(&conn, "2024-01-01");
+    let done = add_file(&conn, &record_id, &project_id, "a.png");
+    let cancelled = add_file(&conn, &record_id, &project_id, "b.png");
+    save_ocr(&conn, &record_id, &project_id, &done, &[item("白细胞", "5.2")]);
+
+    let ocr = OcrRepo::new(&conn);
+    ocr.save_cancelled(&OcrTarget {
+        file_id: &cancelled,
+        record_id: &record_id,
+        project_id: &project_id,
+        checkup_date: "2024-01-01",
+    })
+    .unwrap();
+    // 已取消的文件下次识别时重新处理
+    let pending = ocr.pending_files(&record_id).unwrap();
+    assert_eq!(pending.iter().map(|f| f.file_id.as_str()).collect::<Vec<_>>(), vec![cancelled.as_str()]);
+    assert_eq!(ocr.counts(&record_id).unwrap().success_ocr, 1);
+
+    let ai = AiRepo::new(&conn);
+    let analysis = ai.create(&record_id, "prompt", "model").unwrap();
+    let jobs = JobRepo::new(&conn);
+    let job = jobs.enqueue(JobKind::AiAnalysis, &record_id).unwrap();
+    jobs.set_result(&job.id, &analysis).unwrap();
+    assert_eq!(jobs.active_for_result(&analysis).unwrap().unwrap().id, job.id);
+
+    ai.cancel(&analysis, "## 部分").unwrap();
+    jobs.finish(&job.id, JobStatus::Cancelled, None).unwrap();
+    let saved = ai.list(&record_id).unwrap().remove(0);
+    assert_eq!((saved.status.as_str(), saved.response_content.as_str()), ("cancelled", "## 部分"));
+    assert!(jobs.active_for_result(&analysis).unwrap().is_none());
+    // 取消后可重新发起
+    jobs.enqueue(JobKind::AiAnalysis, &record_id).unwrap();
+}
+
+#[test]
+fn repeated_ocr_failures_keep_one_result_per_file() {
+    let conn = setup();
+    let project_id = create_project(&conn, "血常规");
+    let record_id = create_record(&conn, "2024-01-01");
+    let file_id = add_file(&conn, &record_id, &project_id, "a.png");
+    let target = OcrTarget {
+        file_id: &file_id,
+        record_id: &record_id,
+        project_id: &project_id,
+        checkup_date: "2024-01-01",
+    };
+
+    let repo = OcrRepo::new(&conn);
+    repo.save_failure(&target, "HTTP 502").unwrap();
+    repo.save_cancelled(&target).unwrap();
+    repo.save_failure(&target, "HTTP 429").unwrap();
+    let results = repo.list(&record_id).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].error_message, "HTTP 429");
+
+    save_ocr(&conn, &record_id, &project_id, &file_id, &[item("白细胞", "5.2")]);
+    let results = repo.list(&record_id).unwrap();
+    assert_eq!(results.iter().map(|r| r.status.as_str()).collect::<Vec<_>>(), vec!["success"]);
+}
+
+#[test]
+fn ocr_items_keep_their_pdf_page() {
+    let conn = setup();
+    let project_id = create_project(&conn, "生化");
+    let record_id = create_record(&conn, "2024-05-01");
+    let file_id = add_file(&conn, &record_id, &project_id, "report.pdf");
+    let paged = OcrParsedItem { page: Some(2), ..item("血糖", "5.1") };
+    save_ocr(&conn, &record_id, &project_id, &file_id, &[paged, item("尿酸", "300")]);
+
+    let saved = &OcrRepo::new(&conn).list(&record_id).unwrap()[0];
+    let parsed: Vec<OcrParsedItem> = serde_json::from_str(&saved.parsed_items).unwrap();
+    assert_eq!((parsed[0].page, parsed[1].page), (Some(2), None));
+    // 没有页码的条目不写入 page 字段
+    assert_eq!(saved.parsed_items.matches("page").count(), 1);
+}
+
+#[test]
+fn parse_failed_results_keep_raw_output() {
+    // 解析失败的结果保留原始输出，不写入指标值，再次识别时仍会处理
+    let conn = setup();
+    let project_id = create_project(&conn, "生化");
+    let record_id = create_record(&conn, "2024-05-01");
+    let file_id = add_file(&conn, &record_id, &project_id, "a.png");
+    let target = OcrTarget { file_id: &file_id, record_id: &record_id, project_id: &project_id, checkup_date: "2024-05-01" };
+    OcrRepo::new(&conn).save_parse_failed(&target, "血糖 5.1", &[], "a.png: 输出不是有效的 JSON").unwrap();
+
+    let saved = &OcrRepo::new(&conn).list(&record_id).unwrap()[0];
+    assert_eq!((saved.status.as_str(), saved.raw_json.as_str()), ("parse_failed", "血糖 5.1"));
+    assert_eq!(OcrRepo::new(&conn).counts(&record_id).unwrap().failed_ocr, 1);
+    assert_eq!(OcrRepo::new(&conn).pending_files(&record_id).unwrap().len(), 1);
+    assert_eq!(count(&conn, "indicator_values"), 0);
+}
+
+#[test]
+fn corrected_ocr_results_regenerate_values_and_survive_reruns() {
+    let conn = setup();
+    let project_id = create_project(&conn, "生化");
+    create_indicator(&conn, &project_id, "血糖");
+    create_indicator(&conn, &project_id, "总胆固醇");
+    let record_id = create_record(&conn, "2024-05-01");
+    let file_id = add_file(&conn, &record_id, &project_id, "a.png");
+    let ocr_id = save_ocr(&conn, &record_id, &project_id, &file_id, &[item("血糖", "51")]);
+
+    let indicators = IndicatorRepo::new(&conn).list_all().unwrap();
+    let repo = OcrRepo::new(&conn);
+    let corrections = [item("血糖", "5.1"), item("总胆固醇", "4.2")];
+
+    // 记录状态未能更新时，修正整体回滚
+    conn.execute_batch(
+        "CREATE TEMP TRIGGER fail_status BEFORE UPDATE ON checkup_records
+         BEGIN SELECT RAISE(ABORT, 'status failed'); END;",
+    )
+    .unwrap();
+    assert!(repo.update_items(&ocr_id, &corrections, &indicators).is_err());
+    assert!(repo.get(&ocr_id).unwrap().corrected_at.is_none());
+    assert_eq!(count(&conn, "indicator_values"), 1);
+    conn.execute_batch("DROP TRIGGER fail_status").unwrap();
+
+    let corrected = repo.update_items(&ocr_id, &corrections, &indicators).unwrap();
+    assert!(corrected.corrected_at.is_some());
+    assert_eq!(corrected.status, "success");
+    // 此前没有成功识别的记录，修正后可以进行 AI 分析
+    assert_eq!(RecordRepo::new(&conn).status(&record_id).unwrap(), "ocr_done");
+
+    let values: Vec<String> = conn
+        .prepare("SELECT value_text FROM indicator_values WHERE ocr_result_id = ?1 ORDER BY value_text")
+        .unwrap()
+        .query_map([&ocr_id], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(values, ["4.2", "5.1"]);
+
+    // 撤销修正前的删除可以找回原来的指标值
+    let deleted = AuditRepo::new(&conn)
+        .list(&audit::AuditFilter {
+            table_name: Some("indicator_values".into()),
+            action: Some(audit::ACTION_DELETE.into()),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0].before.as_ref().unwrap()["value_text"], "51");
+
+    // 之后的识别不会覆盖修正过的结果
+    let target = OcrTarget { file_id: &file_id, record_id: &record_id, project_id: &project_id, checkup_date: "2024-05-01" };
+    assert_eq!(repo.save_success(&target, "", &[item("血糖", "99")], &indicators).unwrap(), ocr_id);
+    assert_eq!(repo.save_failure(&target, "超时").unwrap(), ocr_id);
+    assert_eq!(repo.list(&record_id).unwrap().len(), 1);
+    assert_eq!(count(&conn, "indicator_values"), 2);
+    assert!(repo.pending_files(&record_id).unwrap().is_empty());
+
+    let err = repo.update_items(&ocr_id, &[item(" ", "1")], &indicators).unwrap_err();
+    assert_eq!(err.code(), "validation");
+    assert_eq!(repo.update_items("missing", &[], &indicators).unwrap_err().code(), "not_found");
+}
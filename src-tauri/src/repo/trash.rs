@@ -0,0 +1,262 @@
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use crate::error::{AppError, AppResult, DbResultExt};
+use super::{audit, FileRepo, PatientRepo, ProjectRepo, RecordRepo};
+
+/// 回收站中的数据类型
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashKind {
+    Record,
+    File,
+    Project,
+}
+
+impl TrashKind {
+    fn table(self) -> &'static str {
+        match self {
+            TrashKind::Record => "checkup_records",
+            TrashKind::File => "checkup_files",
+            TrashKind::Project => "checkup_projects",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TrashItem {
+    pub kind: TrashKind,
+    pub id: String,
+    /// 显示名称：记录为检查日期，文件为原始文件名，项目为项目名称
+    pub name: String,
+    /// 文件所属的检查记录
+    pub record_id: Option<String>,
+    pub deleted_at: String,
+}
+
+/// 彻底删除的结果，`stored_paths` 为需要删除的物理文件（相对数据目录）
+#[derive(Debug, Default)]
+pub struct Purged {
+    pub count: usize,
+    pub stored_paths: Vec<String>,
+}
+
+pub struct TrashRepo<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> TrashRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// 成员回收站中的内容（最近删除在前）；项目为全部成员共用
+    pub fn list(&self, patient_id: &str) -> AppResult<Vec<TrashItem>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT 'record', id, checkup_date, NULL, deleted_at FROM checkup_records
+                 WHERE deleted_at IS NOT NULL AND patient_id = ?1
+                 UNION ALL
+                 SELECT 'file', f.id, f.original_filename, f.record_id, f.deleted_at FROM checkup_files f
+                 JOIN checkup_records r ON f.record_id = r.id
+                 WHERE f.deleted_at IS NOT NULL AND r.patient_id = ?1
+                 UNION ALL
+                 SELECT 'project', id, name, NULL, deleted_at FROM checkup_projects WHERE deleted_at IS NOT NULL
+                 ORDER BY 5 DESC"
+            )
+            .db_context("查询回收站失败")?;
+
+        stmt.query_map([patient_id], |row| {
+            let kind = match row.get::<_, String>(0)?.as_str() {
+                "record" => TrashKind::Record,
+                "file" => TrashKind::File,
+                _ => TrashKind::Project,
+            };
+            Ok(TrashItem {
+                kind,
+                id: row.get(1)?,
+                name: row.get(2)?,
+                record_id: row.get(3)?,
+                deleted_at: row.get(4)?,
+            })
+        })
+        .db_context("查询回收站失败")?
+        .collect::<Result<Vec<_>, _>>()
+        .db_context("解析回收站数据失败")
+    }
+
+    /// 确认回收站中的记录或文件属于当前成员，其他成员的数据按不存在处理
+    pub fn ensure_active(&self, kind: TrashKind, id: &str) -> AppResult<()> {
+        let sql = match kind {
+            TrashKind::Record => "SELECT patient_id FROM checkup_records WHERE id = ?1",
+            TrashKind::File => {
+                "SELECT r.patient_id FROM checkup_files f JOIN checkup_records r ON f.record_id = r.id WHERE f.id = ?1"
+            }
+            TrashKind::Project => return Ok(()),
+        };
+        let patient_id: String = self
+            .conn
+            .query_row(sql, [id], |row| row.get(0))
+            .or_not_found("回收站中没有该数据")?;
+        if patient_id != PatientRepo::new(self.conn).active_id()? {
+            return Err(AppError::NotFound("回收站中没有该数据".into()));
+        }
+        Ok(())
+    }
+
+    /// 从回收站恢复；上级数据仍在回收站中时拒绝，避免恢复出在任何列表中都看不到的数据
+    pub fn restore(&self, kind: TrashKind, id: &str) -> AppResult<()> {
+        let tx = super::transaction(self.conn)?;
+        if let Some(message) = self.trashed_parent(kind, id)? {
+            return Err(AppError::Conflict {
+                message: message.into(),
+                details: serde_json::json!({ "kind": kind, "id": id }),
+            });
+        }
+
+        if !set_deleted(self.conn, kind.table(), id, false)? {
+            return Err(AppError::NotFound("回收站中没有该数据".into()));
+        }
+        tx.commit()
+    }
+
+    /// 仍在回收站中的上级数据对应的提示；只有文件有上级（检查记录与项目），
+    /// 检查记录所属的成员不会进入回收站
+    fn trashed_parent(&self, kind: TrashKind, id: &str) -> AppResult<Option<&'static str>> {
+        if kind != TrashKind::File {
+            return Ok(None);
+        }
+        let (record_deleted, project_deleted) = self
+            .conn
+            .query_row(
+                "SELECT r.deleted_at IS NOT NULL, p.deleted_at IS NOT NULL
+                 FROM checkup_files f
+                 JOIN checkup_records r ON f.record_id = r.id
+                 JOIN checkup_projects p ON f.project_id = p.id
+                 WHERE f.id = ?1",
+                [id],
+                |row| Ok((row.get::<_, bool>(0)?, row.get::<_, bool>(1)?)),
+            )
+            .or_not_found("回收站中没有该数据")?;
+        Ok(if record_deleted {
+            Some("所属检查记录也在回收站中，请先恢复该记录")
+        } else if project_deleted {
+            Some("所属项目也在回收站中，请先恢复该项目")
+        } else {
+            None
+        })
+    }
+
+    /// 彻底删除回收站中的单条数据
+    pub fn purge(&self, kind: TrashKind, id: &str) -> AppResult<Purged> {
+        let in_trash: bool = self
+            .conn
+            .query_row(
+                &format!("SELECT deleted_at IS NOT NULL FROM {} WHERE id = ?1", kind.table()),
+                [id],
+                |row| row.get(0),
+            )
+            .or_not_found("回收站中没有该数据")?;
+        if !in_trash {
+            return Err(AppError::NotFound("回收站中没有该数据".into()));
+        }
+
+        let tx = self.conn.unchecked_transaction().db_context("开启事务失败")?;
+        let stored_paths = purge_item(&tx, kind, id)?;
+        tx.commit().db_context("提交删除失败")?;
+
+        Ok(Purged { count: 1, stored_paths })
+    }
+
+    /// 彻底删除 `before`（见 [`deleted_at`]）之前移入回收站的数据，`before` 为空时清空回收站
+    ///
+    /// 指定 `patient_id` 时只删除该成员的记录与文件，项目仅在不含其他成员的文件时删除；
+    /// 仍有未删除文件引用的项目会保留在回收站中。
+    pub fn purge_all(&self, before: Option<&str>, patient_id: Option<&str>) -> AppResult<Purged> {
+        let tx = self.conn.unchecked_transaction().db_context("开启事务失败")?;
+        let mut purged = Purged::default();
+
+        // 先删文件与记录，项目最后删除，避免残留引用
+        for (kind, sql) in [
+            (
+                TrashKind::File,
+                "SELECT f.id FROM checkup_files f JOIN checkup_records r ON f.record_id = r.id
+                 WHERE f.deleted_at IS NOT NULL AND (?1 IS NULL OR f.deleted_at < ?1) AND (?2 IS NULL OR r.patient_id = ?2)",
+            ),
+            (
+                TrashKind::Record,
+                "SELECT id FROM checkup_records
+                 WHERE deleted_at IS NOT NULL AND (?1 IS NULL OR deleted_at < ?1) AND (?2 IS NULL OR patient_id = ?2)",
+            ),
+            (
+                TrashKind::Project,
+                "SELECT p.id FROM checkup_projects p
+                 WHERE p.deleted_at IS NOT NULL AND (?1 IS NULL OR p.deleted_at < ?1)
+                   AND NOT EXISTS (
+                       SELECT 1 FROM checkup_files f
+                       JOIN checkup_records r ON f.record_id = r.id
+                       WHERE f.project_id = p.id AND f.deleted_at IS NULL AND r.deleted_at IS NULL
+                   )
+                   AND (?2 IS NULL OR NOT EXISTS (
+                       SELECT 1 FROM checkup_files f
+                       JOIN checkup_records r ON f.record_id = r.id
+                       WHERE f.project_id = p.id AND r.patient_id != ?2
+                   ))",
+            ),
+        ] {
+            let ids = {
+                let mut stmt = tx.prepare(sql).db_context("查询回收站失败")?;
+                stmt.query_map(rusqlite::params![before, patient_id], |row| row.get::<_, String>(0))
+                    .db_context("查询回收站失败")?
+                    .collect::<Result<Vec<_>, _>>()
+                    .db_context("解析回收站数据失败")?
+            };
+            for id in ids {
+                purged.stored_paths.extend(purge_item(&tx, kind, &id)?);
+                purged.count += 1;
+            }
+        }
+
+        tx.commit().db_context("提交删除失败")?;
+        Ok(purged)
+    }
+}
+
+fn purge_item(conn: &Connection, kind: TrashKind, id: &str) -> AppResult<Vec<String>> {
+    match kind {
+        TrashKind::Record => RecordRepo::new(conn).purge(id),
+        TrashKind::File => Ok(vec![FileRepo::new(conn).purge(id)?]),
+        TrashKind::Project => ProjectRepo::new(conn).purge(id),
+    }
+}
+
+/// 移入回收站的时间：UTC、固定到微秒的 RFC 3339，按字符串比较即为时间先后，不受时区与夏令时影响
+pub fn deleted_at(time: chrono::DateTime<chrono::Utc>) -> String {
+    time.to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+}
+
+/// 设置或清除 deleted_at 并写入审计日志，返回是否有数据变化
+pub(crate) fn set_deleted(conn: &Connection, table: &str, id: &str, deleted: bool) -> AppResult<bool> {
+    let tx = super::transaction(conn)?;
+    let before = audit::snapshot(conn, table, id)?;
+    let (sql, deleted_at) = if deleted {
+        (
+            format!("UPDATE {} SET deleted_at = ?1 WHERE id = ?2 AND deleted_at IS NULL", table),
+            Some(deleted_at(chrono::Utc::now())),
+        )
+    } else {
+        (
+            format!("UPDATE {} SET deleted_at = ?1 WHERE id = ?2 AND deleted_at IS NOT NULL", table),
+            None,
+        )
+    };
+
+    let changed = conn
+        .execute(&sql, rusqlite::params![deleted_at, id])
+        .db_context("更新回收站状态失败")?;
+    if changed > 0 {
+        audit::record_update(conn, table, id, before)?;
+    }
+    tx.commit()?;
+    Ok(changed > 0)
+}
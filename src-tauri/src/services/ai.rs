@@ -0,0 +1,182 @@
+use crate::db::Database;
+use crate::error::{AppError, AppResult};
+use crate::repo::ai_profile::AiTask;
+use crate::repo::{AiRepo, ConfigRepo, OcrRepo, RecordRepo};
+use crate::services::events::EventSink;
+use crate::services::http_client::{self, AiClientConfig, RetryAttempt, Timeouts};
+use crate::services::provider::{ChatClient, ChatMessage, ChatRequest};
+use rusqlite::Connection;
+use tokio_util::sync::CancellationToken;
+
+/// 默认 AI 分析 Prompt 模板
+pub const DEFAULT_AI_PROMPT: &str = "请根据以下检查数据，综合分析患者的健康状况，指出异常指标，提供治疗建议和生活方式改善方案。请以中文回复，使用Markdown格式。";
+
+/// 一次 AI 分析任务，分析记录已预先创建
+pub struct AiJob {
+    pub record_id: String,
+    pub analysis_id: String,
+    config: AiClientConfig,
+    full_prompt: String,
+}
+
+/// 检查记录是否可以开始分析，供排队前提前返回错误
+pub fn check(conn: &Connection, record_id: &str) -> AppResult<()> {
+    RecordRepo::new(conn).checkup_date(record_id)?;
+    http_client::load_ai_config(conn, AiTask::Analysis)?;
+    if OcrRepo::new(conn).parsed_for_record(record_id)?.is_empty() {
+        return Err(AppError::Validation("当前检查记录没有成功的 OCR 结果，请先进行 OCR 识别".into()));
+    }
+    Ok(())
+}
+
+/// 汇总当前记录与同一成员的历史 OCR 数据，预创建分析记录并将状态置为 ai_processing
+pub fn prepare(db: &Database, record_id: &str) -> AppResult<AiJob> {
+    let conn = db.write()?;
+
+    // 获取 AI 配置
+    let config = http_client::load_ai_config(&conn, AiTask::Analysis)?;
+
+    // 获取 AI 分析 Prompt 模板
+    let ai_prompt = ConfigRepo::new(&conn).get_or("ai_analysis_prompt_template", DEFAULT_AI_PROMPT)?;
+
+    // 获取当前检查记录的 OCR 数据
+    let checkup_date = RecordRepo::new(&conn).checkup_date(record_id)?;
+
+    let ocr_repo = OcrRepo::new(&conn);
+    let current_data = ocr_repo.parsed_for_record(record_id)?;
+    if current_data.is_empty() {
+        return Err(AppError::Validation("当前检查记录没有成功的 OCR 结果，请先进行 OCR 识别".into()));
+    }
+
+    // 收集历史检查数据
+    let history_data = ocr_repo.parsed_history(record_id).unwrap_or_default();
+
+    // 组装完整的 Prompt 数据
+    let mut prompt_parts = Vec::new();
+    prompt_parts.push(format!("## 本次检查（日期: {}）\n", checkup_date));
+
+    for data in &current_data {
+        prompt_parts.push(format!("### {}\n", data.project_name));
+        prompt_parts.push(format!("{}\n", data.parsed_items));
+    }
+
+    if !history_data.is_empty() {
+        prompt_parts.push("\n## 历史检查数据\n".to_string());
+        let mut prev_date = String::new();
+        for data in &history_data {
+            if data.checkup_date != prev_date {
+                prompt_parts.push(format!("\n### 检查日期: {}\n", data.checkup_date));
+                prev_date = data.checkup_date.clone();
+            }
+            prompt_parts.push(format!("#### {}\n", data.project_name));
+            prompt_parts.push(format!("{}\n", data.parsed_items));
+        }
+    }
+
+    // 预创建分析记录
+    let full_prompt = format!("{}\n\n{}", ai_prompt, prompt_parts.join(""));
+    let analysis_id = AiRepo::new(&conn).create(record_id, &full_prompt, &config.model)?;
+
+    // 更新检查记录状态
+    RecordRepo::new(&conn).set_status(record_id, "ai_processing")?;
+
+    Ok(AiJob {
+        record_id: record_id.to_string(),
+        analysis_id,
+        config,
+        full_prompt,
+    })
+}
+
+/// 流式请求分析结果并保存，通过 `events` 发送 ai_stream_chunk / ai_stream_retry / ai_stream_done / ai_stream_error 事件
+///
+/// `cancel` 触发后中止请求，保留已生成的内容并发送 ai_stream_cancelled 事件，此时返回 `Ok(None)`。
+pub async fn run(
+    db: &Database,
+    job: AiJob,
+    events: &dyn EventSink,
+    cancel: &CancellationToken,
+) -> AppResult<Option<String>> {
+    match stream_analysis(&job, events, cancel).await {
+        Ok(content) if cancel.is_cancelled() => {
+            let saved = db.write().and_then(|conn| {
+                AiRepo::new(&conn).cancel(&job.analysis_id, &content)?;
+                RecordRepo::new(&conn).set_status(&job.record_id, "ocr_done")
+            });
+            if let Err(e) = &saved {
+                log::error!("保存 AI 分析取消状态失败: {}", e);
+            }
+
+            events.emit("ai_stream_cancelled", serde_json::json!({
+                "record_id": job.record_id,
+                "analysis_id": job.analysis_id,
+            }));
+            saved.map(|_| None)
+        }
+        Ok(content) => {
+            // 保存完成的分析结果
+            let saved = db.write().and_then(|conn| {
+                AiRepo::new(&conn).complete(&job.analysis_id, &content)?;
+                RecordRepo::new(&conn).set_status(&job.record_id, "ai_done")
+            });
+            if let Err(e) = &saved {
+                log::error!("保存 AI 分析结果失败: {}", e);
+            }
+
+            // 发送完成事件
+            events.emit("ai_stream_done", serde_json::json!({
+                "record_id": job.record_id,
+                "analysis_id": job.analysis_id,
+            }));
+            saved.map(|_| Some(content))
+        }
+        Err(e) => {
+            let error = e.to_string();
+            let saved = db.write().and_then(|conn| {
+                AiRepo::new(&conn).fail(&job.analysis_id, &error)?;
+                RecordRepo::new(&conn).set_status(&job.record_id, "ocr_done")
+            });
+            if let Err(e) = saved {
+                log::error!("保存 AI 分析错误状态失败: {}", e);
+            }
+
+            events.emit("ai_stream_error", serde_json::json!({
+                "record_id": job.record_id,
+                "analysis_id": job.analysis_id,
+                "error": error,
+            }));
+            Err(e)
+        }
+    }
+}
+
+/// 返回已收到的全部内容；被取消时为取消前收到的部分
+async fn stream_analysis(job: &AiJob, events: &dyn EventSink, cancel: &CancellationToken) -> AppResult<String> {
+    // 流式输出没有总时长限制，只限制连接、首字节与输出间隔
+    let timeouts = Timeouts { total_secs: 0, ..job.config.timeouts.analysis };
+    let client = ChatClient::new(&job.config, timeouts)?;
+
+    let request = ChatRequest {
+        system: Some("你是一位专业的医疗健康分析助手。请根据用户提供的检查报告数据，给出全面、专业的健康分析和建议。".into()),
+        messages: vec![ChatMessage::user(job.full_prompt.clone())],
+        max_tokens: 8192,
+        schema: None,
+    };
+
+    let on_retry = |retry: &RetryAttempt| {
+        events.emit("ai_stream_retry", serde_json::json!({
+            "record_id": job.record_id,
+            "analysis_id": job.analysis_id,
+            "retry": retry,
+        }));
+    };
+    // 发送流式 chunk 事件
+    let on_delta = |content: &str| {
+        events.emit("ai_stream_chunk", serde_json::json!({
+            "record_id": job.record_id,
+            "analysis_id": job.analysis_id,
+            "content": content,
+        }));
+    };
+    client.stream(&request, on_retry, on_delta, cancel).await
+}
@@ -0,0 +1,371 @@
+use crate::db::{migrations, Database, DatabaseStatus, DB_FILE_NAME};
+use crate::error::{AppError, AppResult, DbResultExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Component, Path};
+
+/// 备份归档格式版本，格式不兼容时递增
+const BACKUP_FORMAT_VERSION: u32 = 1;
+
+/// 归档内清单文件名
+const MANIFEST_NAME: &str = "manifest.json";
+
+/// 图片目录（checkup_files.stored_path 均以此开头）
+const PICTURES_DIR: &str = "pictures";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupEntry {
+    /// 归档内路径，统一使用 `/` 分隔
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    pub app_version: String,
+    pub schema_version: u32,
+    pub encrypted: bool,
+    pub created_at: String,
+    pub entries: Vec<BackupEntry>,
+    /// 数据库中登记但未能打包的文件（磁盘缺失或不在数据目录内）
+    pub skipped_files: Vec<String>,
+}
+
+/// 将数据库快照与全部检查文件打包为单个 zip 归档
+pub fn create_backup(db: &Database, app_dir: &Path, dest: &Path) -> AppResult<BackupManifest> {
+    let backup_dir = app_dir.join("backups");
+    std::fs::create_dir_all(&backup_dir).map_err(|e| AppError::io("创建备份目录失败", e))?;
+    let snapshot_path = backup_dir.join(format!(".snapshot-{}.db", uuid::Uuid::new_v4()));
+
+    let result = write_archive(db, app_dir, &snapshot_path, dest);
+    std::fs::remove_file(&snapshot_path).ok();
+    result
+}
+
+fn write_archive(db: &Database, app_dir: &Path, snapshot_path: &Path, dest: &Path) -> AppResult<BackupManifest> {
+    // 1. 在线备份 API 生成一致性快照，文件清单与版本号从快照中读取，保证与打包的数据库一致
+    let (schema_version, stored_paths) = {
+        let snapshot = db.snapshot(snapshot_path)?;
+        let version = migrations::current_version(&snapshot)
+            .db_context("读取数据库版本失败")?;
+        let mut stmt = snapshot
+            .prepare("SELECT DISTINCT stored_path FROM checkup_files ORDER BY stored_path ASC")
+            .db_context("查询文件失败")?;
+        let paths = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .db_context("查询文件失败")?
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("解析文件数据失败")?;
+        (version, paths)
+    };
+
+    // 2. 写入临时归档，完成后再改名，避免留下半成品
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| AppError::io("创建备份目录失败", e))?;
+    }
+    let part_path = dest.with_extension("zip.part");
+    let file = File::create(&part_path).map_err(|e| AppError::io("创建备份文件失败", e))?;
+    let mut zip = zip::ZipWriter::new(file);
+
+    let mut entries = vec![add_entry(&mut zip, DB_FILE_NAME, snapshot_path, zip::CompressionMethod::Deflated)?];
+    let mut skipped_files = Vec::new();
+
+    for stored_path in stored_paths {
+        let entry_name = stored_path.replace('\\', "/");
+        let full_path = app_dir.join(&stored_path);
+        if !is_safe_entry(&entry_name) || !entry_name.starts_with(PICTURES_DIR) || !full_path.is_file() {
+            log::warn!("备份时跳过文件: {}", stored_path);
+            skipped_files.push(stored_path);
+            continue;
+        }
+        // 图片本身已压缩，直接存储
+        entries.push(add_entry(&mut zip, &entry_name, &full_path, zip::CompressionMethod::Stored)?);
+    }
+
+    let manifest = BackupManifest {
+        format_version: BACKUP_FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        schema_version,
+        encrypted: db.status()?.encrypted,
+        created_at: chrono::Local::now().to_rfc3339(),
+        entries,
+        skipped_files,
+    };
+
+    let manifest_json = serde_json::to_vec_pretty(&manifest).map_err(|e| AppError::io("生成备份清单失败", e))?;
+    zip.start_file(MANIFEST_NAME, zip_options(zip::CompressionMethod::Deflated))
+        .map_err(|e| AppError::io("写入备份清单失败", e))?;
+    zip.write_all(&manifest_json).map_err(|e| AppError::io("写入备份清单失败", e))?;
+    zip.finish().map_err(|e| AppError::io("写入备份文件失败", e))?;
+
+    std::fs::rename(&part_path, dest).map_err(|e| AppError::io("保存备份文件失败", e))?;
+    Ok(manifest)
+}
+
+/// 校验归档清单后，用备份内容替换当前数据库与 pictures 目录
+///
+/// 替换前当前数据库与图片目录会移入 backups/ 下，便于手动回退。
+pub fn restore_backup(db: &Database, app_dir: &Path, archive_path: &Path) -> AppResult<(BackupManifest, DatabaseStatus)> {
+    let file = File::open(archive_path).map_err(|e| AppError::io("打开备份文件失败", e))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| AppError::Validation(format!("备份文件格式无效: {}", e)))?;
+
+    let manifest = read_manifest(&mut archive)?;
+    if manifest.format_version != BACKUP_FORMAT_VERSION {
+        return Err(AppError::Validation(format!("不支持的备份格式版本: {}", manifest.format_version)));
+    }
+    if manifest.schema_version > migrations::latest_version() {
+        return Err(AppError::Conflict {
+            message: format!(
+                "备份的数据库版本 (v{}) 高于当前程序支持的版本 (v{})，请升级程序后再恢复",
+                manifest.schema_version,
+                migrations::latest_version()
+            ),
+            details: serde_json::json!({
+                "backup_version": manifest.schema_version,
+                "supported_version": migrations::latest_version(),
+            }),
+        });
+    }
+    if !manifest.entries.iter().any(|e| e.path == DB_FILE_NAME) {
+        return Err(AppError::Validation("备份中缺少数据库文件".into()));
+    }
+
+    let timestamp = chrono::Local::now().format("%Y%m%d%H%M%S").to_string();
+    let staging_dir = app_dir.join(format!(".restore-{}", timestamp));
+    let result = extract_verified(&mut archive, &manifest, &staging_dir)
+        .and_then(|_| swap_in(db, app_dir, &staging_dir, &timestamp));
+    std::fs::remove_dir_all(&staging_dir).ok();
+
+    result.map(|status| (manifest, status))
+}
+
+fn read_manifest(archive: &mut zip::ZipArchive<File>) -> AppResult<BackupManifest> {
+    let mut entry = archive
+        .by_name(MANIFEST_NAME)
+        .map_err(|_| AppError::Validation("备份中缺少清单文件 manifest.json".into()))?;
+    let mut content = String::new();
+    entry.read_to_string(&mut content).map_err(|e| AppError::io("读取备份清单失败", e))?;
+    serde_json::from_str(&content).map_err(|e| AppError::Validation(format!("备份清单格式无效: {}", e)))
+}
+
+/// 解压清单中的全部文件到临时目录，逐个校验大小与 SHA-256
+fn extract_verified(archive: &mut zip::ZipArchive<File>, manifest: &BackupManifest, staging_dir: &Path) -> AppResult<()> {
+    for entry in &manifest.entries {
+        if !is_safe_entry(&entry.path) {
+            return Err(AppError::Validation(format!("备份中包含非法路径: {}", entry.path)));
+        }
+
+        let mut source = archive
+            .by_name(&entry.path)
+            .map_err(|_| AppError::Validation(format!("备份中缺少文件: {}", entry.path)))?;
+        let out_path = staging_dir.join(&entry.path);
+        if let Some(parent) = out_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| AppError::io("创建目录失败", e))?;
+        }
+        let mut out = File::create(&out_path).map_err(|e| AppError::io("解压文件失败", e))?;
+
+        let (size, sha256) = copy_hashed(&mut source, &mut out)
+            .map_err(|e| AppError::io(&format!("解压文件失败 {}", entry.path), e))?;
+        if size != entry.size || sha256 != entry.sha256 {
+            return Err(AppError::Validation(format!("备份文件校验失败: {}", entry.path)));
+        }
+    }
+    Ok(())
+}
+
+/// 将临时目录中的数据库与图片替换到数据目录
+fn swap_in(db: &Database, app_dir: &Path, staging_dir: &Path, timestamp: &str) -> AppResult<DatabaseStatus> {
+    let backup_dir = app_dir.join("backups");
+    std::fs::create_dir_all(&backup_dir).map_err(|e| AppError::io("创建备份目录失败", e))?;
+
+    // 保留当前数据库
+    let previous_db = backup_dir.join(format!("health_guard.before_restore.{}.db", timestamp));
+    if db.status()?.locked {
+        std::fs::copy(app_dir.join(DB_FILE_NAME), &previous_db)
+            .map_err(|e| AppError::io("备份当前数据库失败", e))?;
+    } else {
+        db.snapshot(&previous_db)?;
+    }
+
+    // 先替换图片目录，数据库替换失败时还原
+    let live_pictures = app_dir.join(PICTURES_DIR);
+    let previous_pictures = backup_dir.join(format!("pictures.before_restore.{}", timestamp));
+    let staged_pictures = staging_dir.join(PICTURES_DIR);
+    std::fs::create_dir_all(&staged_pictures).map_err(|e| AppError::io("创建目录失败", e))?;
+
+    if live_pictures.exists() {
+        std::fs::rename(&live_pictures, &previous_pictures)
+            .map_err(|e| AppError::io("移动当前图片目录失败", e))?;
+    }
+    if let Err(e) = std::fs::rename(&staged_pictures, &live_pictures) {
+        restore_dir(&previous_pictures, &live_pictures);
+        return Err(AppError::io("恢复图片目录失败", e));
+    }
+
+    match db.replace_with(&staging_dir.join(DB_FILE_NAME)) {
+        Ok(status) => {
+            log::info!("已从备份恢复，原数据保存在 {}", backup_dir.display());
+            Ok(status)
+        }
+        Err(e) => {
+            restore_dir(&previous_pictures, &live_pictures);
+            Err(e)
+        }
+    }
+}
+
+/// 回滚目录替换
+fn restore_dir(previous: &Path, live: &Path) {
+    std::fs::remove_dir_all(live).ok();
+    if previous.exists()
+        && let Err(e) = std::fs::rename(previous, live)
+    {
+        log::error!("还原图片目录失败: {}", e);
+    }
+}
+
+fn add_entry(
+    zip: &mut zip::ZipWriter<File>,
+    name: &str,
+    source: &Path,
+    method: zip::CompressionMethod,
+) -> AppResult<BackupEntry> {
+    let mut file = File::open(source).map_err(|e| AppError::io(&format!("读取文件失败 {}", name), e))?;
+    zip.start_file(name, zip_options(method))
+        .map_err(|e| AppError::io("写入备份文件失败", e))?;
+    let (size, sha256) = copy_hashed(&mut file, zip).map_err(|e| AppError::io(&format!("写入备份文件失败 {}", name), e))?;
+    Ok(BackupEntry {
+        path: name.to_string(),
+        size,
+        sha256,
+    })
+}
+
+fn zip_options(method: zip::CompressionMethod) -> zip::write::SimpleFileOptions {
+    zip::write::SimpleFileOptions::default()
+        .compression_method(method)
+        .large_file(true)
+}
+
+/// 边复制边计算 SHA-256，返回（字节数, 十六进制摘要）
+fn copy_hashed(reader: &mut impl Read, writer: &mut impl Write) -> std::io::Result<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        writer.write_all(&buf[..n])?;
+        size += n as u64;
+    }
+    let sha256 = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+    Ok((size, sha256))
+}
+
+/// 仅允许普通的相对路径，防止解压到数据目录之外
+fn is_safe_entry(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::file::NewCheckupFile;
+    use crate::repo::project::CreateProjectInput;
+    use crate::repo::record::CreateRecordInput;
+    use crate::repo::{FileRepo, PatientRepo, ProjectRepo, RecordRepo};
+
+    const PICTURE: &str = "pictures/血常规/2024-01-01/a.jpg";
+
+    /// 登记一张图片并写入磁盘
+    fn add_picture(db: &Database, app_dir: &Path) {
+        let conn = db.write().unwrap();
+        let project = ProjectRepo::new(&conn)
+            .create(CreateProjectInput { name: "血常规".into(), description: None, ocr_engine: None })
+            .unwrap();
+        let patient_id = PatientRepo::new(&conn).active_id().unwrap();
+        let record = RecordRepo::new(&conn)
+            .create(&patient_id, CreateRecordInput { checkup_date: "2024-01-01".into(), notes: None })
+            .unwrap();
+        FileRepo::new(&conn)
+            .insert(NewCheckupFile {
+                id: uuid::Uuid::new_v4().to_string(),
+                record_id: record.id,
+                project_id: project.id,
+                project_name: project.name,
+                original_filename: "a.jpg".into(),
+                stored_path: PICTURE.into(),
+                file_size: 5,
+                mime_type: "image/jpeg".into(),
+            })
+            .unwrap();
+
+        let path = app_dir.join(PICTURE);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, b"image").unwrap();
+    }
+
+    /// 复制归档，将 `name` 的内容换成 `data`，清单保持不变
+    fn tamper(source: &Path, dest: &Path, name: &str, data: &[u8]) {
+        let mut archive = zip::ZipArchive::new(File::open(source).unwrap()).unwrap();
+        let mut zip = zip::ZipWriter::new(File::create(dest).unwrap());
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).unwrap();
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content).unwrap();
+            zip.start_file(entry.name(), zip_options(zip::CompressionMethod::Stored)).unwrap();
+            zip.write_all(if entry.name() == name { data } else { &content }).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn backup_round_trips_and_rejects_modified_files() {
+        let root = std::env::temp_dir().join(format!("health-backup-{}", uuid::Uuid::new_v4()));
+        let app_dir = root.join("data");
+        let db = Database::new(app_dir.clone()).unwrap();
+        add_picture(&db, &app_dir);
+
+        let archive = root.join("backup.zip");
+        let manifest = create_backup(&db, &app_dir, &archive).unwrap();
+        assert_eq!(manifest.schema_version, migrations::latest_version());
+        assert!(!manifest.encrypted);
+        assert!(manifest.skipped_files.is_empty());
+        let paths = manifest.entries.iter().map(|e| e.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, [DB_FILE_NAME, PICTURE]);
+        assert!(!archive.with_extension("zip.part").exists());
+
+        // 修改的图片无法通过校验，当前数据保持不变
+        let tampered = root.join("tampered.zip");
+        tamper(&archive, &tampered, PICTURE, b"other");
+        let error = restore_backup(&db, &app_dir, &tampered).unwrap_err();
+        assert_eq!(error.code(), "validation");
+        assert_eq!(std::fs::read(app_dir.join(PICTURE)).unwrap(), b"image");
+
+        // 恢复后图片与数据库中的登记一致
+        std::fs::remove_dir_all(app_dir.join(PICTURES_DIR)).unwrap();
+        let (restored, status) = restore_backup(&db, &app_dir, &archive).unwrap();
+        assert_eq!(restored.entries.len(), 2);
+        assert!(!status.locked);
+        assert_eq!(std::fs::read(app_dir.join(PICTURE)).unwrap(), b"image");
+        let stored: String = db
+            .read()
+            .unwrap()
+            .query_row("SELECT stored_path FROM checkup_files", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, PICTURE);
+
+        drop(db);
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
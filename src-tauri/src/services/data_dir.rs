@@ -0,0 +1,393 @@
+use crate::db::{sidecar_path, Database, DB_FILE_NAME};
+use crate::error::{AppError, AppResult};
+use crate::repo::FileRepo;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// 指定数据目录的环境变量
+pub const DATA_DIR_ENV: &str = "HEALTH_GUARD_DATA_DIR";
+
+/// 指定数据目录的命令行参数（`--data-dir <路径>` 或 `--data-dir=<路径>`）
+pub const DATA_DIR_ARG: &str = "--data-dir";
+
+/// 便携模式标记文件，放在程序所在目录时数据保存在程序目录
+pub const PORTABLE_MARKER: &str = "portable";
+
+/// 平台数据/配置目录下的应用子目录（与 tauri.conf.json 的 identifier 一致）
+const APP_DIR_NAME: &str = "com.apks.site";
+
+/// 记录迁移后数据目录的文件，保存在平台配置目录
+const LOCATION_FILE: &str = "data_dir.json";
+
+/// 随数据目录一起迁移的图片目录与备份目录
+const DATA_DIRS: [&str; 2] = ["pictures", "backups"];
+
+/// 数据目录的来源，按优先级从高到低排列
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DataDirSource {
+    CommandLine,
+    Environment,
+    Portable,
+    /// 通过 relocate_data_dir 迁移后的目录
+    Relocated,
+    /// 旧版本在程序目录下创建的数据
+    Legacy,
+    Default,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DataDirInfo {
+    pub path: PathBuf,
+    pub source: DataDirSource,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LocationFile {
+    data_dir: PathBuf,
+    /// 迁移前的目录，新目录首次成功打开后清理
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    previous_dir: Option<PathBuf>,
+}
+
+/// 按 命令行 > 环境变量 > 便携模式 > 迁移记录 > 旧版程序目录 > 平台数据目录 的顺序确定数据目录
+pub fn resolve() -> DataDirInfo {
+    let exe_dir = exe_dir();
+    resolve_from(Candidates {
+        arg: arg_override(std::env::args().skip(1)),
+        env: std::env::var_os(DATA_DIR_ENV).filter(|v| !v.is_empty()).map(PathBuf::from),
+        portable: exe_dir.join(PORTABLE_MARKER).exists(),
+        relocated: location_path().and_then(|path| read_location(&path)).map(|l| l.data_dir),
+        legacy: exe_dir.join(DB_FILE_NAME).exists(),
+        platform: dirs::data_dir().map(|d| d.join(APP_DIR_NAME)),
+        exe_dir,
+    })
+}
+
+/// 确定数据目录所需的各项来源
+#[derive(Clone)]
+struct Candidates {
+    arg: Option<PathBuf>,
+    env: Option<PathBuf>,
+    exe_dir: PathBuf,
+    /// 程序目录下有便携模式标记
+    portable: bool,
+    relocated: Option<PathBuf>,
+    /// 程序目录下有旧版数据库
+    legacy: bool,
+    platform: Option<PathBuf>,
+}
+
+fn resolve_from(candidates: Candidates) -> DataDirInfo {
+    let Candidates { arg, env, exe_dir, portable, relocated, legacy, platform } = candidates;
+    let (path, source) = if let Some(path) = arg {
+        (path, DataDirSource::CommandLine)
+    } else if let Some(path) = env {
+        (path, DataDirSource::Environment)
+    } else if portable {
+        (exe_dir, DataDirSource::Portable)
+    } else if let Some(path) = relocated {
+        (path, DataDirSource::Relocated)
+    } else if legacy {
+        (exe_dir, DataDirSource::Legacy)
+    } else {
+        (platform.unwrap_or(exe_dir), DataDirSource::Default)
+    };
+    DataDirInfo { path, source }
+}
+
+/// 新目录成功打开后，删除迁移前目录中已迁走的数据
+pub fn finish_relocation(info: &DataDirInfo) {
+    if info.source != DataDirSource::Relocated {
+        return;
+    }
+    if let Some(location) = location_path() {
+        remove_previous(&location);
+    }
+}
+
+fn remove_previous(location: &Path) {
+    let Some(LocationFile { data_dir, previous_dir: Some(previous) }) = read_location(location) else {
+        return;
+    };
+
+    for suffix in ["", "-wal", "-shm"] {
+        let path = previous.join(format!("{}{}", DB_FILE_NAME, suffix));
+        if path.exists()
+            && let Err(e) = std::fs::remove_file(&path)
+        {
+            log::warn!("清理旧数据库失败 {}: {}", path.display(), e);
+        }
+    }
+    for dir in DATA_DIRS {
+        let path = previous.join(dir);
+        if path.exists()
+            && let Err(e) = std::fs::remove_dir_all(&path)
+        {
+            log::warn!("清理旧目录失败 {}: {}", path.display(), e);
+        }
+    }
+
+    if let Err(e) = write_location(location, &LocationFile { data_dir, previous_dir: None }) {
+        log::warn!("更新数据目录记录失败: {}", e);
+    }
+    log::info!("已清理迁移前的数据目录: {}", previous.display());
+}
+
+/// 将数据库、pictures 与 backups 目录复制到新目录并记录新位置，重启后生效
+///
+/// 复制前关闭数据库，复制完成后保持关闭直到重启，期间的写入会被拒绝而不是留在旧目录中丢失。
+/// 旧目录中的数据在新目录首次成功打开后才删除，迁移中途失败不影响当前数据。
+pub fn relocate(db: &Database, current: &DataDirInfo, target: &Path) -> AppResult<PathBuf> {
+    let location = location_path().ok_or_else(|| AppError::Internal("无法确定系统配置目录".into()))?;
+    relocate_with(db, current, target, &location)
+}
+
+fn relocate_with(db: &Database, current: &DataDirInfo, target: &Path, location: &Path) -> AppResult<PathBuf> {
+    match current.source {
+        DataDirSource::CommandLine | DataDirSource::Environment | DataDirSource::Portable => {
+            return Err(AppError::Validation(
+                "当前数据目录由命令行参数、环境变量或便携模式指定，请修改对应设置后重启".into(),
+            ));
+        }
+        DataDirSource::Relocated | DataDirSource::Legacy | DataDirSource::Default => {}
+    }
+    if !target.is_absolute() {
+        return Err(AppError::Validation("请选择绝对路径作为新的数据目录".into()));
+    }
+    if target.starts_with(&current.path) || current.path.starts_with(target) {
+        return Err(AppError::Validation("新数据目录不能与当前数据目录相互包含".into()));
+    }
+    if target.join(DB_FILE_NAME).exists() || DATA_DIRS.iter().any(|dir| target.join(dir).exists()) {
+        return Err(AppError::Conflict {
+            message: "目标目录中已有健康管家数据，请选择空目录".into(),
+            details: serde_json::json!({ "path": target.to_string_lossy() }),
+        });
+    }
+    std::fs::create_dir_all(target).map_err(|e| AppError::io("创建数据目录失败", e))?;
+
+    let result = copy_data(db, &current.path, target, || {
+        write_location(
+            location,
+            &LocationFile { data_dir: target.to_path_buf(), previous_dir: Some(current.path.clone()) },
+        )
+    });
+    if let Err(e) = result {
+        for suffix in ["", "-wal"] {
+            std::fs::remove_file(target.join(format!("{}{}", DB_FILE_NAME, suffix))).ok();
+        }
+        for dir in DATA_DIRS {
+            std::fs::remove_dir_all(target.join(dir)).ok();
+        }
+        return Err(e);
+    }
+    log::info!("数据目录已迁移到 {}，重启后生效", target.display());
+    Ok(target.to_path_buf())
+}
+
+/// 复制数据并执行 `commit`（记录新位置）；两者都成功后数据库保持关闭直到重启
+fn copy_data(db: &Database, current: &Path, target: &Path, commit: impl FnOnce() -> AppResult<()>) -> AppResult<()> {
+    // 图片路径统一保存为相对数据目录的路径，迁移后无需按目录改写；锁定状态下此前已改写过
+    if !db.status()?.locked {
+        let conn = db.write()?;
+        let rewritten = FileRepo::new(&conn).relativize_paths(&current.to_string_lossy())?;
+        if rewritten > 0 {
+            log::info!("已将 {} 条文件路径改写为相对路径", rewritten);
+        }
+    }
+
+    // 连接全部关闭后不会再有写入，直接复制数据库文件（连同尚未合并的 WAL）与各目录
+    db.retire_with(|db_path| {
+        std::fs::copy(db_path, target.join(DB_FILE_NAME)).map_err(|e| AppError::io("复制数据库失败", e))?;
+        let wal = sidecar_path(db_path, "-wal");
+        if wal.exists() {
+            std::fs::copy(&wal, sidecar_path(&target.join(DB_FILE_NAME), "-wal"))
+                .map_err(|e| AppError::io("复制数据库失败", e))?;
+        }
+
+        for dir in DATA_DIRS {
+            let source = current.join(dir);
+            if !source.exists() {
+                continue;
+            }
+            let copied = copy_dir(&source, &target.join(dir))?;
+            let expected = dir_size(&source)?;
+            if copied != expected {
+                return Err(AppError::Io(format!("{} 目录复制不完整（{}/{} 字节）", dir, copied, expected)));
+            }
+        }
+        commit()
+    })
+}
+
+/// 递归复制目录，返回复制的总字节数
+fn copy_dir(source: &Path, dest: &Path) -> AppResult<u64> {
+    std::fs::create_dir_all(dest).map_err(|e| AppError::io("创建目录失败", e))?;
+    let mut total = 0;
+    for entry in std::fs::read_dir(source).map_err(|e| AppError::io("读取目录失败", e))? {
+        let entry = entry.map_err(|e| AppError::io("读取目录失败", e))?;
+        let path = entry.path();
+        let dest_path = dest.join(entry.file_name());
+        if path.is_dir() {
+            total += copy_dir(&path, &dest_path)?;
+        } else {
+            total += std::fs::copy(&path, &dest_path)
+                .map_err(|e| AppError::io(&format!("复制文件失败 {}", path.display()), e))?;
+        }
+    }
+    Ok(total)
+}
+
+fn dir_size(dir: &Path) -> AppResult<u64> {
+    let mut total = 0;
+    for entry in std::fs::read_dir(dir).map_err(|e| AppError::io("读取目录失败", e))? {
+        let path = entry.map_err(|e| AppError::io("读取目录失败", e))?.path();
+        total += if path.is_dir() {
+            dir_size(&path)?
+        } else {
+            path.metadata().map_err(|e| AppError::io("读取文件信息失败", e))?.len()
+        };
+    }
+    Ok(total)
+}
+
+fn arg_override(mut args: impl Iterator<Item = String>) -> Option<PathBuf> {
+    while let Some(arg) = args.next() {
+        if arg == DATA_DIR_ARG {
+            return args.next().filter(|v| !v.is_empty()).map(PathBuf::from);
+        }
+        if let Some(value) = arg.strip_prefix(DATA_DIR_ARG).and_then(|v| v.strip_prefix('=')) {
+            return Some(PathBuf::from(value)).filter(|p| !p.as_os_str().is_empty());
+        }
+    }
+    None
+}
+
+fn exe_dir() -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(Path::to_path_buf))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+fn location_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join(APP_DIR_NAME).join(LOCATION_FILE))
+}
+
+fn read_location(path: &Path) -> Option<LocationFile> {
+    let content = std::fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&content) {
+        Ok(location) => Some(location),
+        Err(e) => {
+            log::warn!("数据目录记录格式无效，已忽略: {}", e);
+            None
+        }
+    }
+}
+
+fn write_location(path: &Path, location: &LocationFile) -> AppResult<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| AppError::io("创建配置目录失败", e))?;
+    }
+    let content = serde_json::to_string_pretty(location).map_err(|e| AppError::Internal(e.to_string()))?;
+    std::fs::write(path, content).map_err(|e| AppError::io("保存数据目录记录失败", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates() -> Candidates {
+        Candidates {
+            arg: Some(PathBuf::from("/arg")),
+            env: Some(PathBuf::from("/env")),
+            exe_dir: PathBuf::from("/exe"),
+            portable: true,
+            relocated: Some(PathBuf::from("/relocated")),
+            legacy: true,
+            platform: Some(PathBuf::from("/platform")),
+        }
+    }
+
+    #[test]
+    fn resolve_follows_source_precedence() {
+        let mut c = candidates();
+        let resolved = |c: &Candidates| {
+            let info = resolve_from(c.clone());
+            (info.path.to_string_lossy().into_owned(), info.source)
+        };
+
+        // 依次去掉优先级最高的来源
+        assert_eq!(resolved(&c), ("/arg".into(), DataDirSource::CommandLine));
+        c.arg = None;
+        assert_eq!(resolved(&c), ("/env".into(), DataDirSource::Environment));
+        c.env = None;
+        assert_eq!(resolved(&c), ("/exe".into(), DataDirSource::Portable));
+        c.portable = false;
+        assert_eq!(resolved(&c), ("/relocated".into(), DataDirSource::Relocated));
+        c.relocated = None;
+        assert_eq!(resolved(&c), ("/exe".into(), DataDirSource::Legacy));
+        c.legacy = false;
+        assert_eq!(resolved(&c), ("/platform".into(), DataDirSource::Default));
+        c.platform = None;
+        assert_eq!(resolved(&c), ("/exe".into(), DataDirSource::Default));
+    }
+
+    #[test]
+    fn arg_override_accepts_both_forms() {
+        let args = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>().into_iter();
+        assert_eq!(arg_override(args(&["--data-dir", "/a"])), Some(PathBuf::from("/a")));
+        assert_eq!(arg_override(args(&["--data-dir=/b"])), Some(PathBuf::from("/b")));
+        assert_eq!(arg_override(args(&["--data-dir="])), None);
+        assert_eq!(arg_override(args(&["--data-dir"])), None);
+    }
+
+    #[test]
+    fn relocate_moves_data_and_blocks_later_writes() {
+        let root = std::env::temp_dir().join(format!("health-relocate-{}", uuid::Uuid::new_v4()));
+        let current = root.join("current");
+        let target = root.join("target");
+        let location = root.join("config").join(LOCATION_FILE);
+
+        let db = Database::new(current.clone()).unwrap();
+        db.write()
+            .unwrap()
+            .execute("INSERT INTO system_config (id, config_key, config_value, updated_at) VALUES ('c', 'k', 'v', 't')", [])
+            .unwrap();
+        for (dir, file) in [("pictures/p", "a.jpg"), ("backups", "b.zip")] {
+            std::fs::create_dir_all(current.join(dir)).unwrap();
+            std::fs::write(current.join(dir).join(file), b"data").unwrap();
+        }
+        let info = DataDirInfo { path: current.clone(), source: DataDirSource::Default };
+
+        let portable = DataDirInfo { source: DataDirSource::Portable, ..info.clone() };
+        assert_eq!(relocate_with(&db, &portable, &target, &location).unwrap_err().code(), "validation");
+        let nested = current.join("sub");
+        assert_eq!(relocate_with(&db, &info, &nested, &location).unwrap_err().code(), "validation");
+
+        relocate_with(&db, &info, &target, &location).unwrap();
+        assert_eq!(db.write().err().map(|e| e.code()), Some("busy"));
+        assert_eq!(db.read().err().map(|e| e.code()), Some("busy"));
+        assert_eq!(std::fs::read(target.join("pictures/p/a.jpg")).unwrap(), b"data");
+        assert_eq!(std::fs::read(target.join("backups/b.zip")).unwrap(), b"data");
+        let recorded = read_location(&location).unwrap();
+        assert_eq!(recorded.data_dir, target);
+        assert_eq!(recorded.previous_dir.as_deref(), Some(current.as_path()));
+
+        let moved = Database::new(target.clone()).unwrap();
+        let value: String = moved
+            .read()
+            .unwrap()
+            .query_row("SELECT config_value FROM system_config WHERE config_key = 'k'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(value, "v");
+
+        remove_previous(&location);
+        assert!(!current.join(DB_FILE_NAME).exists());
+        assert!(!current.join("pictures").exists() && !current.join("backups").exists());
+        assert_eq!(read_location(&location).unwrap().previous_dir, None);
+
+        drop(moved);
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
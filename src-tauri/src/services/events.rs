@@ -0,0 +1,51 @@
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// 后台任务的事件出口：桌面端转发给前端，命令行输出到终端
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: &str, payload: serde_json::Value);
+}
+
+/// 广播的事件，供本地 API 的 SSE 订阅
+#[derive(Debug, Serialize, Clone)]
+pub struct BroadcastEvent {
+    pub event: String,
+    pub payload: serde_json::Value,
+}
+
+/// 订阅者处理过慢时最多缓存的事件数，超出后丢弃最旧的事件
+const HUB_CAPACITY: usize = 256;
+
+/// 进程内的事件广播，没有订阅者时事件直接丢弃
+#[derive(Clone)]
+pub struct EventHub {
+    sender: broadcast::Sender<BroadcastEvent>,
+}
+
+impl EventHub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(HUB_CAPACITY);
+        Self { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<BroadcastEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventSink for EventHub {
+    fn emit(&self, event: &str, payload: serde_json::Value) {
+        self.sender
+            .send(BroadcastEvent {
+                event: event.to_string(),
+                payload,
+            })
+            .ok();
+    }
+}
@@ -1,20 +1,321 @@
-use reqwest::Client;
+use crate::error::{AppError, AppResult};
+use crate::repo::ai_profile::{AiProfile, AiTask, ProviderKind};
+use crate::repo::{AiProfileRepo, ConfigRepo};
+use rand::Rng;
+use rusqlite::Connection;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
-/// AI API 配置结构
+/// 未配置 `http_max_attempts` 时每个请求最多发送的次数（含首次）
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const MAX_ATTEMPTS_LIMIT: u32 = 10;
+/// 首次重试前的等待时间，之后每次翻倍
+const BASE_DELAY: Duration = Duration::from_millis(500);
+/// 退避等待的上限
+const MAX_DELAY: Duration = Duration::from_secs(30);
+/// Retry-After 等待的上限，服务端要求更久时放弃重试
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// 一次 AI 请求所需的配置
 pub struct AiClientConfig {
+    pub provider: ProviderKind,
     pub api_url: String,
     pub api_key: String,
     pub proxy_enabled: bool,
     pub proxy_url: String,
     pub proxy_username: String,
     pub proxy_password: String,
+    pub model: String,
+    /// 配置中的额外请求参数，由各接口合并到请求体中对应的位置
+    pub parameters: serde_json::Map<String, serde_json::Value>,
+    pub retry: RetryPolicy,
+    pub timeouts: TimeoutSettings,
+}
+
+/// 单类请求的超时设置（秒），0 表示不限制
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// 建立连接（含代理握手）
+    pub connect_secs: u64,
+    /// 发出请求到收到响应头
+    pub first_byte_secs: u64,
+    /// 读取响应体时两次收到数据的最长间隔
+    pub idle_secs: u64,
+    /// 整个请求（含读取响应体）；流式分析不使用该项
+    pub total_secs: u64,
 }
 
-/// 根据配置构建 HTTP 客户端（支持 SOCKS5 代理）
-pub fn build_client(config: &AiClientConfig) -> Result<Client, String> {
-    let mut builder = Client::builder()
-        .timeout(Duration::from_secs(30));
+impl Timeouts {
+    pub const CONNECTION_TEST: Timeouts = Timeouts { connect_secs: 10, first_byte_secs: 20, idle_secs: 10, total_secs: 30 };
+    /// 视觉模型识别大图较慢，总时长放宽到 5 分钟
+    pub const OCR: Timeouts = Timeouts { connect_secs: 15, first_byte_secs: 180, idle_secs: 60, total_secs: 300 };
+    /// 流式分析输出可能持续数分钟，只限制首字节与输出间隔
+    pub const ANALYSIS: Timeouts = Timeouts { connect_secs: 15, first_byte_secs: 180, idle_secs: 120, total_secs: 0 };
+
+    pub fn connect(&self) -> Option<Duration> {
+        secs(self.connect_secs)
+    }
+
+    pub fn first_byte(&self) -> Option<Duration> {
+        secs(self.first_byte_secs)
+    }
+
+    pub fn idle(&self) -> Option<Duration> {
+        secs(self.idle_secs)
+    }
+
+    pub fn total(&self) -> Option<Duration> {
+        secs(self.total_secs)
+    }
+
+    /// 读取 JSON 配置，未设置的项使用 `default`
+    fn parse(value: &str, default: Timeouts) -> Timeouts {
+        let Ok(serde_json::Value::Object(map)) = serde_json::from_str::<serde_json::Value>(value) else {
+            return default;
+        };
+        let field = |key: &str, fallback: u64| map.get(key).and_then(|v| v.as_u64()).unwrap_or(fallback);
+        Timeouts {
+            connect_secs: field("connect_secs", default.connect_secs),
+            first_byte_secs: field("first_byte_secs", default.first_byte_secs),
+            idle_secs: field("idle_secs", default.idle_secs),
+            total_secs: field("total_secs", default.total_secs),
+        }
+    }
+}
+
+fn secs(value: u64) -> Option<Duration> {
+    (value > 0).then(|| Duration::from_secs(value))
+}
+
+/// 各类请求的超时设置，分别保存在 `http_timeouts_connection_test` / `http_timeouts_ocr` / `http_timeouts_analysis`
+#[derive(Debug, Clone, Copy)]
+pub struct TimeoutSettings {
+    pub connection_test: Timeouts,
+    pub ocr: Timeouts,
+    pub analysis: Timeouts,
+}
+
+/// 请求失败的原因
+#[derive(Debug)]
+pub enum RequestError {
+    Http(reqwest::Error),
+    /// 在限定时间内没有收到响应头或新的响应数据
+    Timeout { stage: &'static str, after: Duration },
+}
+
+impl std::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestError::Http(e) if e.is_timeout() => write!(f, "请求超时: {}", e),
+            RequestError::Http(e) => write!(f, "{}", e),
+            RequestError::Timeout { stage, after } => write!(f, "{}超时（{} 秒）", stage, after.as_secs()),
+        }
+    }
+}
+
+impl From<reqwest::Error> for RequestError {
+    fn from(e: reqwest::Error) -> Self {
+        RequestError::Http(e)
+    }
+}
+
+/// 等待响应体的下一块数据，超过 `idle` 未收到时返回超时错误
+pub async fn next_chunk<S, B>(stream: &mut S, idle: Option<Duration>) -> Option<Result<B, RequestError>>
+where
+    S: futures_util::Stream<Item = reqwest::Result<B>> + Unpin,
+{
+    let next = match idle {
+        Some(idle) => match tokio::time::timeout(idle, stream.next()).await {
+            Ok(next) => next,
+            Err(_) => return Some(Err(RequestError::Timeout { stage: "等待响应数据", after: idle })),
+        },
+        None => stream.next().await,
+    };
+    next.map(|chunk| chunk.map_err(RequestError::Http))
+}
+
+/// 读取完整响应体，每块数据之间受 `idle` 限制
+async fn read_body(response: Response, idle: Option<Duration>) -> Result<Vec<u8>, RequestError> {
+    let mut stream = response.bytes_stream();
+    let mut body = Vec::new();
+    while let Some(chunk) = next_chunk(&mut stream, idle).await {
+        body.extend_from_slice(&chunk?);
+    }
+    Ok(body)
+}
+
+/// 请求失败后的重试策略：指数退避加随机抖动，429 / 503 等响应优先遵循 Retry-After
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_attempts: DEFAULT_MAX_ATTEMPTS }
+    }
+}
+
+/// 一次重试的信息，随进度事件发送给前端
+#[derive(Debug, Serialize, Clone)]
+pub struct RetryAttempt {
+    /// 即将进行的是第几次请求
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub delay_ms: u64,
+    pub reason: String,
+}
+
+impl RetryPolicy {
+    /// 第 `attempt` 次请求失败后的等待时间：BASE_DELAY * 2^(attempt-1)，取其一半到全部之间的随机值
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.backoff_with(attempt, &mut rand::rng())
+    }
+
+    /// 使用指定随机数生成器计算等待时间
+    pub fn backoff_with(&self, attempt: u32, rng: &mut impl Rng) -> Duration {
+        let exp = BASE_DELAY.saturating_mul(1 << attempt.saturating_sub(1).min(16)).min(MAX_DELAY);
+        let half = exp / 2;
+        half + half.mul_f64(rng.random::<f64>())
+    }
+
+    /// 发送请求，连接失败、超时以及 429 / 5xx 网关类响应会按策略重试
+    ///
+    /// `request` 每次重试时重新构建请求；`timeouts` 的首字节与总时长限制作用于每次请求，
+    /// 连接超时在 [`build_client`] 中设置。返回最后一次的响应，状态码由调用方处理。
+    /// `on_retry` 在每次等待前调用，用于发送重试进度。
+    pub async fn send(
+        &self,
+        timeouts: &Timeouts,
+        request: impl Fn() -> RequestBuilder,
+        on_retry: impl Fn(&RetryAttempt),
+    ) -> Result<Response, RequestError> {
+        let mut attempt = 1;
+        loop {
+            let result = send_once(timeouts, request()).await;
+            let Some((delay, reason)) = self.retry_delay(attempt, &result) else {
+                return result;
+            };
+            attempt = self.wait(attempt, delay, reason, &on_retry).await;
+        }
+    }
+
+    /// 与 [`RetryPolicy::send`] 相同，并读取完整响应体，返回状态码与响应体
+    ///
+    /// 读取响应体时连接中断或超过 `idle` 未收到数据，同样按策略重新发送整个请求。
+    pub async fn fetch(
+        &self,
+        timeouts: &Timeouts,
+        request: impl Fn() -> RequestBuilder,
+        on_retry: impl Fn(&RetryAttempt),
+    ) -> Result<(StatusCode, Vec<u8>), RequestError> {
+        let mut attempt = 1;
+        loop {
+            let result = send_once(timeouts, request()).await;
+            let (delay, reason) = match self.retry_delay(attempt, &result) {
+                Some(retry) => retry,
+                None => {
+                    let response = result?;
+                    let status = response.status();
+                    match read_body(response, timeouts.idle()).await {
+                        Ok(body) => return Ok((status, body)),
+                        Err(e) if attempt < self.max_attempts && is_transient(&e) => {
+                            (self.backoff(attempt), format!("读取响应失败: {}", e))
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+            };
+            attempt = self.wait(attempt, delay, reason, &on_retry).await;
+        }
+    }
+
+    /// 第 `attempt` 次请求的结果需要重试时，返回等待时间与原因
+    fn retry_delay(&self, attempt: u32, result: &Result<Response, RequestError>) -> Option<(Duration, String)> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        match result {
+            Ok(response) if is_retryable_status(response.status()) => {
+                let delay = match retry_after(response.headers()) {
+                    Some(delay) if delay > MAX_RETRY_AFTER => return None,
+                    Some(delay) => delay,
+                    None => self.backoff(attempt),
+                };
+                Some((delay, format!("HTTP {}", response.status())))
+            }
+            Err(e) if is_transient(e) => Some((self.backoff(attempt), e.to_string())),
+            _ => None,
+        }
+    }
+
+    /// 通知重试并等待，返回下一次请求的序号
+    async fn wait(&self, attempt: u32, delay: Duration, reason: String, on_retry: &impl Fn(&RetryAttempt)) -> u32 {
+        let attempt = attempt + 1;
+        log::warn!("请求失败（{}），{} 毫秒后进行第 {} 次尝试", reason, delay.as_millis(), attempt);
+        on_retry(&RetryAttempt {
+            attempt,
+            max_attempts: self.max_attempts,
+            delay_ms: delay.as_millis() as u64,
+            reason,
+        });
+        tokio::time::sleep(delay).await;
+        attempt
+    }
+}
+
+/// 连接失败、超时或传输中断，重新请求可能成功
+fn is_transient(error: &RequestError) -> bool {
+    match error {
+        // 响应体未读完连接即断开时为 body / decode 错误
+        RequestError::Http(e) => e.is_connect() || e.is_timeout() || e.is_request() || e.is_body() || e.is_decode(),
+        RequestError::Timeout { .. } => true,
+    }
+}
+
+async fn send_once(timeouts: &Timeouts, mut request: RequestBuilder) -> Result<Response, RequestError> {
+    if let Some(total) = timeouts.total() {
+        request = request.timeout(total);
+    }
+    match timeouts.first_byte() {
+        Some(first_byte) => tokio::time::timeout(first_byte, request.send())
+            .await
+            .map_err(|_| RequestError::Timeout { stage: "等待响应", after: first_byte })?
+            .map_err(RequestError::Http),
+        None => request.send().await.map_err(RequestError::Http),
+    }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// 解析 Retry-After，支持秒数与 HTTP 日期两种格式
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().unwrap_or_default())
+}
+
+/// 根据配置构建 HTTP 客户端（支持 SOCKS5 代理），不设整体超时，由各请求按 `timeouts` 限制
+pub fn build_client(config: &AiClientConfig, timeouts: &Timeouts) -> AppResult<Client> {
+    let mut builder = Client::builder();
+    if let Some(connect) = timeouts.connect() {
+        builder = builder.connect_timeout(connect);
+    }
 
     if config.proxy_enabled && !config.proxy_url.is_empty() {
         let proxy_addr = if config.proxy_url.starts_with("socks5://") || config.proxy_url.starts_with("http") {
@@ -24,7 +325,7 @@ pub fn build_client(config: &AiClientConfig) -> Result<Client, String> {
         };
 
         let mut proxy = reqwest::Proxy::all(&proxy_addr)
-            .map_err(|e| format!("代理地址无效: {}", e))?;
+            .map_err(|e| AppError::Validation(format!("代理地址无效: {}", e)))?;
 
         if !config.proxy_username.is_empty() {
             proxy = proxy.basic_auth(&config.proxy_username, &config.proxy_password);
@@ -33,66 +334,243 @@ pub fn build_client(config: &AiClientConfig) -> Result<Client, String> {
         builder = builder.proxy(proxy);
     }
 
-    builder.build().map_err(|e| format!("创建HTTP客户端失败: {}", e))
+    builder.build().map_err(|e| AppError::Internal(format!("创建HTTP客户端失败: {}", e)))
 }
 
-/// 从数据库读取 AI 配置
-pub fn load_ai_config(conn: &rusqlite::Connection) -> Result<AiClientConfig, String> {
-    let get_config = |key: &str| -> String {
-        conn.query_row(
-            "SELECT config_value FROM system_config WHERE config_key = ?1",
-            [key],
-            |row| row.get::<_, String>(0),
-        )
-        .unwrap_or_default()
-    };
-
-    let api_url = get_config("ai_api_url");
-    let api_key = get_config("ai_api_key");
-    let proxy_enabled = get_config("proxy_enabled") == "true";
-    let proxy_url = get_config("proxy_url");
-    let proxy_username = get_config("proxy_username");
-    let proxy_password = get_config("proxy_password");
+/// 读取任务所用的 AI 配置
+pub fn load_ai_config(conn: &Connection, task: AiTask) -> AppResult<AiClientConfig> {
+    let profile = AiProfileRepo::new(conn).for_task(task)?.ok_or_else(|| AppError::ConfigMissing {
+        message: format!("请先为{}选择 AI 配置", task.label()),
+        key: task.config_key().into(),
+    })?;
+    client_config(conn, profile)
+}
 
-    if api_url.is_empty() {
-        return Err("请先配置 AI API 地址".into());
+/// 由 AI 配置与全局的重试、超时设置组成请求配置
+pub fn client_config(conn: &Connection, profile: AiProfile) -> AppResult<AiClientConfig> {
+    if profile.api_url.is_empty() {
+        return Err(AppError::ConfigMissing {
+            message: format!("请先填写「{}」的 API 地址", profile.name),
+            key: "api_url".into(),
+        });
     }
-    if api_key.is_empty() {
-        return Err("请先配置 API Key".into());
+    // Ollama 本地服务不需要密钥
+    if profile.api_key.is_empty() && profile.provider != ProviderKind::Ollama {
+        return Err(AppError::ConfigMissing {
+            message: format!("请先填写「{}」的 API Key", profile.name),
+            key: "api_key".into(),
+        });
     }
 
+    let config = ConfigRepo::new(conn);
+    let max_attempts = config
+        .get_or("http_max_attempts", "")?
+        .trim()
+        .parse::<u32>()
+        .map(|n| n.clamp(1, MAX_ATTEMPTS_LIMIT))
+        .unwrap_or(DEFAULT_MAX_ATTEMPTS);
+    let timeouts = TimeoutSettings {
+        connection_test: Timeouts::parse(&config.get_or("http_timeouts_connection_test", "")?, Timeouts::CONNECTION_TEST),
+        ocr: Timeouts::parse(&config.get_or("http_timeouts_ocr", "")?, Timeouts::OCR),
+        analysis: Timeouts::parse(&config.get_or("http_timeouts_analysis", "")?, Timeouts::ANALYSIS),
+    };
+
     Ok(AiClientConfig {
-        api_url,
-        api_key,
-        proxy_enabled,
-        proxy_url,
-        proxy_username,
-        proxy_password,
+        provider: profile.provider,
+        api_url: profile.api_url,
+        api_key: profile.api_key,
+        proxy_enabled: profile.proxy_enabled,
+        proxy_url: profile.proxy_url,
+        proxy_username: profile.proxy_username,
+        proxy_password: profile.proxy_password,
+        model: profile.model,
+        parameters: profile.parameters,
+        retry: RetryPolicy { max_attempts },
+        timeouts,
     })
 }
 
-/// 获取默认模型名称
-pub fn get_default_model(conn: &rusqlite::Connection) -> String {
-    let default = conn.query_row(
-        "SELECT config_value FROM system_config WHERE config_key = 'ai_default_model'",
-        [],
-        |row| row.get::<_, String>(0),
-    ).unwrap_or_default();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::ai_profile::CreateAiProfileInput;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// 已执行全部迁移的内存数据库
+    fn setup() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run(&mut conn, std::path::Path::new("unused")).unwrap();
+        conn
+    }
+
+    fn create_profile(conn: &Connection, name: &str, model: &str) -> String {
+        AiProfileRepo::new(conn)
+            .create(CreateAiProfileInput {
+                name: name.into(),
+                provider: None,
+                api_url: "https://example.com/v1/chat/completions".into(),
+                api_key: "key".into(),
+                proxy_enabled: None,
+                proxy_url: None,
+                proxy_username: None,
+                proxy_password: None,
+                model: model.into(),
+                parameters: None,
+            })
+            .unwrap()
+            .id
+    }
+
+    /// 总是返回同一个值的随机数生成器
+    struct FixedRng(u64);
+
+    impl rand::RngCore for FixedRng {
+        fn next_u32(&mut self) -> u32 {
+            (self.0 >> 32) as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            dest.fill(0);
+        }
+    }
+
+    #[test]
+    fn retry_after_and_backoff() {
+        use reqwest::header::HeaderValue;
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"));
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
 
-    if !default.is_empty() {
-        return default;
+        let policy = RetryPolicy::default();
+        for attempt in 1..=4 {
+            let full = Duration::from_millis(500 * (1 << (attempt - 1)));
+            let delay = policy.backoff(attempt);
+            assert!(delay >= full / 2 && delay <= full, "{:?}", delay);
+        }
+        assert!(policy.backoff(30) <= Duration::from_secs(30));
+
+        // 随机数固定为 0 与 0.5 时分别等待一半与四分之三
+        let mut zero = FixedRng(0);
+        let mut half = FixedRng(1 << 63);
+        let delays: Vec<_> = (1..=4).map(|attempt| policy.backoff_with(attempt, &mut zero).as_millis()).collect();
+        assert_eq!(delays, [250, 500, 1000, 2000]);
+        let delays: Vec<_> = (1..=4).map(|attempt| policy.backoff_with(attempt, &mut half).as_millis()).collect();
+        assert_eq!(delays, [375, 750, 1500, 3000]);
+        assert_eq!(policy.backoff_with(30, &mut half), MAX_DELAY / 2 + MAX_DELAY / 4);
     }
 
-    // 回退到模型列表的第一个
-    let models_json = conn.query_row(
-        "SELECT config_value FROM system_config WHERE config_key = 'ai_models'",
-        [],
-        |row| row.get::<_, String>(0),
-    ).unwrap_or_default();
+    #[tokio::test]
+    async fn interrupted_body_is_retried() {
+        let listener = tokio::net::TcpListener::bind((std::net::Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        // 第一次响应体只发送一半就断开连接
+        tokio::spawn(async move {
+            for body in [r#"{"ok""#, r#"{"ok":true}"#] {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0u8; 1024];
+                assert!(socket.read(&mut request).await.unwrap() > 0);
+                let response = format!("HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\n{}", body);
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let client = Client::new();
+        let timeouts = Timeouts { connect_secs: 5, first_byte_secs: 5, idle_secs: 5, total_secs: 0 };
+        let retries = AtomicU32::new(0);
+        let (status, body) = RetryPolicy { max_attempts: 2 }
+            .fetch(&timeouts, || client.get(&url), |_| {
+                retries.fetch_add(1, Ordering::SeqCst);
+            })
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, br#"{"ok":true}"#);
+        assert_eq!(retries.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn timeouts_override_defaults_per_operation() {
+        let conn = setup();
+        create_profile(&conn, "默认", "gpt-4o");
+        let repo = ConfigRepo::new(&conn);
+        repo.set("http_timeouts_ocr", r#"{"total_secs": 600, "idle_secs": 0}"#).unwrap();
+        repo.set("http_timeouts_analysis", "不是 JSON").unwrap();
+
+        let timeouts = load_ai_config(&conn, AiTask::Ocr).unwrap().timeouts;
+        assert_eq!(timeouts.connection_test, Timeouts::CONNECTION_TEST);
+        assert_eq!(timeouts.analysis, Timeouts::ANALYSIS);
+        assert_eq!(timeouts.ocr.total_secs, 600);
+        assert_eq!(timeouts.ocr.idle(), None);
+        assert_eq!(timeouts.ocr.connect_secs, Timeouts::OCR.connect_secs);
+        assert_eq!(timeouts.analysis.total(), None);
+    }
+
+    #[test]
+    fn tasks_use_their_own_ai_profile() {
+        use crate::repo::ai_profile::UpdateAiProfileInput;
+        use crate::services::provider::{ChatClient, ChatRequest};
+
+        let conn = setup();
+        assert_eq!(load_ai_config(&conn, AiTask::Ocr).map(|_| ()).unwrap_err().code(), "config_missing");
+
+        // 第一个配置同时用于全部任务
+        let vision = create_profile(&conn, "视觉", "qwen-vl");
+        let reasoning = create_profile(&conn, "推理", "deepseek-r1");
+        let repo = AiProfileRepo::new(&conn);
+        assert_eq!(repo.task_profiles().unwrap().analysis.as_deref(), Some(vision.as_str()));
+
+        repo.set_task_profile(AiTask::Analysis, &reasoning).unwrap();
+        repo.update(UpdateAiProfileInput {
+            id: reasoning.clone(),
+            name: None,
+            provider: None,
+            api_url: None,
+            api_key: None,
+            proxy_enabled: None,
+            proxy_url: None,
+            proxy_username: None,
+            proxy_password: None,
+            model: None,
+            parameters: Some(serde_json::from_str(r#"{"temperature": 0.2, "max_tokens": 16000}"#).unwrap()),
+        })
+        .unwrap();
+
+        assert_eq!(load_ai_config(&conn, AiTask::Ocr).unwrap().model, "qwen-vl");
+        let analysis = load_ai_config(&conn, AiTask::Analysis).unwrap();
+        let client = ChatClient::new(&analysis, analysis.timeouts.analysis).unwrap();
+        let body = client.body(&ChatRequest { system: None, messages: Vec::new(), max_tokens: 8192, schema: None }, true);
+        assert_eq!(body["model"], "deepseek-r1");
+        assert_eq!(body["max_tokens"], 16000);
+        assert_eq!(body["temperature"], 0.2);
+
+        // 使用中的配置不能删除，保留字段不能作为参数
+        assert_eq!(repo.delete(&vision).unwrap_err().code(), "validation");
+        let reserved = repo.update(UpdateAiProfileInput {
+            id: vision.clone(),
+            name: None,
+            provider: None,
+            api_url: None,
+            api_key: None,
+            proxy_enabled: None,
+            proxy_url: None,
+            proxy_username: None,
+            proxy_password: None,
+            model: None,
+            parameters: Some(serde_json::from_str(r#"{"stream": false}"#).unwrap()),
+        });
+        assert_eq!(reserved.unwrap_err().code(), "validation");
 
-    if let Ok(models) = serde_json::from_str::<Vec<String>>(&models_json) {
-        models.into_iter().next().unwrap_or_else(|| "gpt-4o-mini".to_string())
-    } else {
-        "gpt-4o-mini".to_string()
+        repo.set_task_profile(AiTask::Ocr, &reasoning).unwrap();
+        repo.delete(&vision).unwrap();
+        assert_eq!(repo.list().unwrap().len(), 1);
     }
 }
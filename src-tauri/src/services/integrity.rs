@@ -0,0 +1,218 @@
+use crate::db::Database;
+use crate::error::{AppError, AppResult};
+use crate::repo::integrity::{DanglingReference, IntegrityRepo, StoredFile};
+use crate::repo::FileRepo;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// 图片目录，数据库中登记的文件都保存在此目录下
+const PICTURES_DIR: &str = "pictures";
+
+/// 修复时隔离孤立文件的目录
+const QUARANTINE_DIR: &str = "orphaned";
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct IntegrityReport {
+    /// 数据库中登记但磁盘上不存在的文件
+    pub missing_files: Vec<StoredFile>,
+    /// pictures/ 下未被任何文件记录引用的文件（相对数据目录）
+    pub orphaned_files: Vec<String>,
+    /// pictures/ 下不含任何文件且不属于现有项目的目录（相对数据目录）
+    pub empty_dirs: Vec<String>,
+    pub dangling_references: Vec<DanglingReference>,
+    /// `PRAGMA integrity_check` 的输出，正常时为空
+    pub sqlite_errors: Vec<String>,
+}
+
+/// 缺失文件的处理方式
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingFileAction {
+    /// 移入回收站（已在回收站中的保持不变）
+    Trash,
+    /// 彻底删除文件记录及其识别结果
+    Purge,
+}
+
+/// 孤立文件的处理方式
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanedFileAction {
+    Delete,
+    /// 移动到 orphaned/<时间戳>/ 下，保留原有的相对路径
+    Quarantine,
+}
+
+/// 每类问题的修复方式，未指定的类别不做处理
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RepairOptions {
+    pub missing_files: Option<MissingFileAction>,
+    pub orphaned_files: Option<OrphanedFileAction>,
+    /// 删除空目录
+    #[serde(default)]
+    pub empty_dirs: bool,
+    /// 删除引用缺失数据的行
+    #[serde(default)]
+    pub dangling_references: bool,
+    /// 重建索引（integrity_check 报告索引问题时可修复）
+    #[serde(default)]
+    pub reindex: bool,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct RepairResult {
+    pub missing_files: usize,
+    pub orphaned_files: usize,
+    pub empty_dirs: usize,
+    pub dangling_references: usize,
+    pub reindexed: bool,
+    /// 修复后重新检查的结果
+    pub remaining: IntegrityReport,
+}
+
+/// 检查数据库与 pictures/ 目录的一致性
+///
+/// 上传时先写入物理文件、再在写连接上登记，因此检查期间持有写连接，
+/// 避免把正在上传、尚未登记的文件误判为孤立文件。
+pub fn check(db: &Database, app_dir: &Path) -> AppResult<IntegrityReport> {
+    let conn = db.write()?;
+    inspect(&conn, app_dir)
+}
+
+fn inspect(conn: &Connection, app_dir: &Path) -> AppResult<IntegrityReport> {
+    let repo = IntegrityRepo::new(conn);
+
+    let stored_files = repo.stored_files()?;
+    let referenced: HashSet<PathBuf> = stored_files.iter().map(|f| app_dir.join(&f.stored_path)).collect();
+    let missing_files = stored_files
+        .into_iter()
+        .filter(|f| !app_dir.join(&f.stored_path).is_file())
+        .collect();
+
+    let pictures = app_dir.join(PICTURES_DIR);
+    let mut orphaned_files = Vec::new();
+    let mut empty_dirs = Vec::new();
+    if pictures.is_dir() {
+        let projects: HashSet<PathBuf> = repo.project_names()?.iter().map(|name| pictures.join(name)).collect();
+        scan_dir(&pictures, &referenced, &projects, &mut orphaned_files, &mut empty_dirs)?;
+    }
+
+    Ok(IntegrityReport {
+        missing_files,
+        orphaned_files: orphaned_files.iter().map(|p| relative(app_dir, p)).collect(),
+        empty_dirs: empty_dirs.iter().map(|p| relative(app_dir, p)).collect(),
+        dangling_references: repo.dangling_references()?,
+        sqlite_errors: repo.sqlite_check()?,
+    })
+}
+
+/// 按选项修复各类问题，完成后重新检查；检查与修复全程持有写连接
+pub fn repair(db: &Database, app_dir: &Path, options: &RepairOptions) -> AppResult<RepairResult> {
+    let conn = db.write()?;
+    let report = inspect(&conn, app_dir)?;
+    let mut result = RepairResult::default();
+
+    if options.reindex && !report.sqlite_errors.is_empty() {
+        IntegrityRepo::new(&conn).reindex()?;
+        result.reindexed = true;
+    }
+
+    if let Some(action) = options.missing_files {
+        let files = FileRepo::new(&conn);
+        for file in &report.missing_files {
+            match action {
+                MissingFileAction::Trash if file.trashed => continue,
+                MissingFileAction::Trash => files.delete(&file.id)?,
+                MissingFileAction::Purge => {
+                    files.purge(&file.id)?;
+                }
+            }
+            result.missing_files += 1;
+        }
+    }
+
+    // 在处理缺失文件之后执行，被删除的文件记录不会再被当作悬空引用
+    if options.dangling_references && !report.dangling_references.is_empty() {
+        result.dangling_references = IntegrityRepo::new(&conn).delete_dangling()?;
+    }
+
+    if let Some(action) = options.orphaned_files {
+        let quarantine = app_dir
+            .join(QUARANTINE_DIR)
+            .join(chrono::Local::now().format("%Y%m%d%H%M%S").to_string());
+        for orphan in &report.orphaned_files {
+            let path = app_dir.join(orphan);
+            match action {
+                OrphanedFileAction::Delete => {
+                    std::fs::remove_file(&path)
+                        .map_err(|e| AppError::io(&format!("删除文件失败 {}", path.display()), e))?;
+                }
+                OrphanedFileAction::Quarantine => {
+                    let dest = quarantine.join(orphan);
+                    if let Some(parent) = dest.parent() {
+                        std::fs::create_dir_all(parent).map_err(|e| AppError::io("创建隔离目录失败", e))?;
+                    }
+                    move_file(&path, &dest)?;
+                }
+            }
+            result.orphaned_files += 1;
+        }
+    }
+
+    if options.empty_dirs {
+        // 由深到浅删除，父目录在子目录删除后才可能为空
+        let mut dirs = report.empty_dirs.clone();
+        dirs.sort_by_key(|d| std::cmp::Reverse(d.len()));
+        for dir in dirs {
+            let path = app_dir.join(&dir);
+            if std::fs::remove_dir(&path).is_ok() {
+                result.empty_dirs += 1;
+            }
+        }
+    }
+
+    result.remaining = inspect(&conn, app_dir)?;
+    Ok(result)
+}
+
+/// 递归扫描目录，返回目录下是否有文件
+fn scan_dir(
+    dir: &Path,
+    referenced: &HashSet<PathBuf>,
+    projects: &HashSet<PathBuf>,
+    orphaned: &mut Vec<PathBuf>,
+    empty_dirs: &mut Vec<PathBuf>,
+) -> AppResult<bool> {
+    let mut has_files = false;
+    for entry in std::fs::read_dir(dir).map_err(|e| AppError::io("读取目录失败", e))? {
+        let path = entry.map_err(|e| AppError::io("读取目录失败", e))?.path();
+        if path.is_dir() {
+            if scan_dir(&path, referenced, projects, orphaned, empty_dirs)? {
+                has_files = true;
+            } else if !projects.contains(&path) {
+                empty_dirs.push(path);
+            }
+        } else {
+            has_files = true;
+            if !referenced.contains(&path) {
+                orphaned.push(path);
+            }
+        }
+    }
+    Ok(has_files)
+}
+
+/// 优先重命名，跨磁盘时退化为复制后删除
+fn move_file(source: &Path, dest: &Path) -> AppResult<()> {
+    if std::fs::rename(source, dest).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(source, dest).map_err(|e| AppError::io(&format!("移动文件失败 {}", source.display()), e))?;
+    std::fs::remove_file(source).map_err(|e| AppError::io(&format!("移动文件失败 {}", source.display()), e))
+}
+
+fn relative(base: &Path, path: &Path) -> String {
+    path.strip_prefix(base).unwrap_or(path).to_string_lossy().to_string()
+}
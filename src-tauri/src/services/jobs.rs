@@ -0,0 +1,500 @@
+use crate::db::Database;
+use crate::error::{AppError, AppResult};
+use crate::repo::job::{Job, JobKind, JobStatus};
+use crate::repo::{AiRepo, JobRepo, OcrRepo, RecordRepo};
+use crate::services::events::EventSink;
+use crate::services::{ai, ocr};
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+/// 执行中任务刷新心跳的间隔
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+/// 心跳超过该时长未更新的执行中任务视为已中断
+const STALE_AFTER: Duration = Duration::from_secs(60);
+
+/// 本进程的执行者标识（进程号与随机后缀），领取任务时写入 `jobs.owner`
+static INSTANCE_ID: LazyLock<String> =
+    LazyLock::new(|| format!("{}-{}", std::process::id(), &uuid::Uuid::new_v4().to_string()[..8]));
+
+/// 唤醒任务执行器，新任务入队或数据库解锁后调用 `wake`；同时保存本进程中执行中任务的取消令牌
+#[derive(Default)]
+pub struct JobQueue {
+    ocr: Notify,
+    ai: Notify,
+    running: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl JobQueue {
+    pub fn wake(&self) {
+        self.ocr.notify_one();
+        self.ai.notify_one();
+    }
+
+    fn lane(&self, kind: JobKind) -> &Notify {
+        match kind {
+            JobKind::Ocr => &self.ocr,
+            JobKind::AiAnalysis => &self.ai,
+        }
+    }
+
+    /// 通知执行中的任务中止，任务不在本进程中执行时返回 false
+    pub fn cancel_running(&self, job_id: &str) -> bool {
+        match self.lock().get(job_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn register(&self, job_id: &str) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.lock().insert(job_id.to_string(), token.clone());
+        token
+    }
+
+    fn unregister(&self, job_id: &str) {
+        self.lock().remove(job_id);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, CancellationToken>> {
+        self.running.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// 检查能否执行后将任务入队；配置缺失等问题在入队前直接返回
+pub fn enqueue(db: &Database, kind: JobKind, record_id: &str) -> AppResult<Job> {
+    let conn = db.write()?;
+    match kind {
+        JobKind::Ocr => ocr::check(&conn, record_id)?,
+        JobKind::AiAnalysis => ai::check(&conn, record_id)?,
+    }
+    JobRepo::new(&conn).enqueue(kind, record_id)
+}
+
+/// 取消任务：排队中的直接标记为已取消，执行中的通知执行器中止并在结束后更新状态
+pub fn cancel(db: &Database, queue: &JobQueue, job: &Job) -> AppResult<Job> {
+    let mut job = job.clone();
+    if job.status == JobStatus::Queued {
+        let conn = db.write()?;
+        let repo = JobRepo::new(&conn);
+        if repo.cancel_queued(&job.id)? {
+            return repo.get(&job.id);
+        }
+        // 读取状态后已被执行器领取，按执行中的任务取消
+        job = repo.get(&job.id)?;
+    }
+    match job.status {
+        JobStatus::Running if queue.cancel_running(&job.id) => Ok(job),
+        JobStatus::Running => Err(AppError::Busy("任务正在其他进程（如命令行工具）中执行，无法在此取消".into())),
+        _ => Err(AppError::Validation("任务已结束".into())),
+    }
+}
+
+/// 常驻的任务执行器：首次可访问数据库时恢复中断的任务，之后 OCR 与 AI 分析任务分别在各自的队列中依次执行
+///
+/// 两类任务互不等待，长时间的识别不会阻塞分析，反之亦然。
+/// 启动时心跳尚未超时的任务（可能仍在其他进程中执行）之后定期再检查。
+pub async fn run_worker(db: &Database, app_dir: &Path, events: &dyn EventSink, queue: &JobQueue) {
+    // 数据库锁定时失败，解锁后被唤醒再试
+    while let Err(e) = recover(db) {
+        log::warn!("恢复后台任务失败: {}", e);
+        queue.lane(JobKind::Ocr).notified().await;
+    }
+    tokio::join!(
+        run_lane(db, app_dir, events, queue, JobKind::Ocr),
+        run_lane(db, app_dir, events, queue, JobKind::AiAnalysis),
+        async {
+            loop {
+                tokio::time::sleep(STALE_AFTER).await;
+                match recover(db) {
+                    Ok(0) => {}
+                    Ok(_) => queue.wake(),
+                    Err(e) => log::warn!("恢复后台任务失败: {}", e),
+                }
+            }
+        },
+    );
+}
+
+async fn run_lane(db: &Database, app_dir: &Path, events: &dyn EventSink, queue: &JobQueue, kind: JobKind) {
+    loop {
+        if let Err(e) = run_pending(db, app_dir, events, queue, kind).await {
+            log::error!("执行后台任务失败: {}", e);
+        }
+        queue.lane(kind).notified().await;
+    }
+}
+
+/// 将中断的任务重新排队，并修正没有任务却停留在处理中的检查记录；返回重新排队的任务数
+pub fn recover(db: &Database) -> AppResult<usize> {
+    let conn = db.write()?;
+    let repo = JobRepo::new(&conn);
+
+    let (requeued, abandoned) = repo.requeue_interrupted(STALE_AFTER.as_secs() as i64)?;
+    if requeued + abandoned > 0 {
+        log::info!("恢复中断的后台任务: 重新排队 {} 个，放弃 {} 个", requeued, abandoned);
+    }
+
+    for (kind, status) in [(JobKind::Ocr, "ocr_processing"), (JobKind::AiAnalysis, "ai_processing")] {
+        for record_id in repo.records_without_active_job(kind, status)? {
+            restore_record_status(&conn, kind, &record_id)?;
+        }
+    }
+    Ok(requeued)
+}
+
+/// 依次执行该类型全部排队中的任务，返回执行的任务数
+pub async fn run_pending(
+    db: &Database,
+    app_dir: &Path,
+    events: &dyn EventSink,
+    queue: &JobQueue,
+    kind: JobKind,
+) -> AppResult<usize> {
+    let mut count = 0;
+    loop {
+        let next = {
+            let conn = db.read()?;
+            JobRepo::new(&conn).next_queued(kind)?
+        };
+        let Some(job) = next else {
+            return Ok(count);
+        };
+        match run_job(db, app_dir, events, queue, &job.id).await {
+            Ok(_) => count += 1,
+            // 已被其他进程（如命令行）领取
+            Err(AppError::Busy(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// 领取并执行一个排队中的任务，返回结束后的任务
+pub async fn run_job(
+    db: &Database,
+    app_dir: &Path,
+    events: &dyn EventSink,
+    queue: &JobQueue,
+    job_id: &str,
+) -> AppResult<Job> {
+    // 领取前登记取消令牌，领取后立即到来的取消请求也能通知到执行器
+    let cancel = queue.register(job_id);
+    let claimed = db.write().and_then(|conn| {
+        let repo = JobRepo::new(&conn);
+        if !repo.claim(job_id, &INSTANCE_ID)? {
+            return Err(AppError::Busy("任务已在执行或已结束".into()));
+        }
+        repo.get(job_id)
+    });
+    let job = match claimed {
+        Ok(job) => job,
+        Err(e) => {
+            queue.unregister(job_id);
+            return Err(e);
+        }
+    };
+    emit_job(events, &job, "job_started");
+
+    let sink = JobEvents { db, job_id, inner: events };
+    let (status, error) = with_heartbeat(db, job_id, async {
+        match job.kind {
+            JobKind::Ocr => run_ocr(db, app_dir, &job, &sink, &cancel).await,
+            JobKind::AiAnalysis => run_ai(db, &job, &sink, &cancel).await,
+        }
+    })
+    .await;
+    queue.unregister(job_id);
+
+    let finished = db.write().and_then(|conn| {
+        let repo = JobRepo::new(&conn);
+        repo.finish(job_id, status, error.as_deref())?;
+        repo.get(job_id)
+    });
+    match finished {
+        Ok(job) => {
+            emit_job(events, &job, "job_finished");
+            Ok(job)
+        }
+        Err(e) => {
+            // 状态未能保存时仍通知结束，下次启动时任务会按中断处理
+            emit_job(events, &Job { status, last_error: error, ..job }, "job_finished");
+            Err(e)
+        }
+    }
+}
+
+/// 执行期间定期刷新任务心跳，其他进程据此判断任务仍在执行
+async fn with_heartbeat<F: std::future::Future>(db: &Database, job_id: &str, work: F) -> F::Output {
+    tokio::pin!(work);
+    let mut ticker = tokio::time::interval(HEARTBEAT_INTERVAL);
+    // 第一次立即触发，领取时已写入心跳
+    ticker.tick().await;
+    loop {
+        tokio::select! {
+            output = &mut work => return output,
+            _ = ticker.tick() => {
+                if let Err(e) = db.write().and_then(|conn| JobRepo::new(&conn).heartbeat(job_id)) {
+                    log::warn!("更新任务心跳失败: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// 执行结果与错误信息；成功时的错误信息为部分文件失败的原因
+type Outcome = (JobStatus, Option<String>);
+
+async fn run_ocr(
+    db: &Database,
+    app_dir: &Path,
+    job: &Job,
+    events: &dyn EventSink,
+    cancel: &CancellationToken,
+) -> Outcome {
+    let prepared = match ocr::prepare(db, &job.record_id) {
+        Ok(prepared) => prepared,
+        Err(e) => return fail_before_start(db, job, events, "ocr_error", e),
+    };
+
+    let summary = ocr::run(db, app_dir, prepared, events, cancel).await;
+    let errors = (!summary.errors.is_empty()).then(|| summary.errors.join("\n"));
+    if summary.cancelled {
+        (JobStatus::Cancelled, errors)
+    } else if summary.success == 0 && errors.is_some() {
+        (JobStatus::Failed, errors)
+    } else {
+        (JobStatus::Succeeded, errors)
+    }
+}
+
+async fn run_ai(db: &Database, job: &Job, events: &dyn EventSink, cancel: &CancellationToken) -> Outcome {
+    let prepared = db
+        .write()
+        .and_then(|conn| {
+            // 上次中断时遗留的分析不会再完成
+            AiRepo::new(&conn).fail_processing(&job.record_id, "任务中断，已重新分析")?;
+            Ok(())
+        })
+        .and_then(|_| ai::prepare(db, &job.record_id));
+    let prepared = match prepared {
+        Ok(prepared) => prepared,
+        Err(e) => return fail_before_start(db, job, events, "ai_stream_error", e),
+    };
+
+    if let Err(e) = db
+        .write()
+        .and_then(|conn| JobRepo::new(&conn).set_result(&job.id, &prepared.analysis_id))
+    {
+        log::error!("保存任务结果失败: {}", e);
+    }
+    match ai::run(db, prepared, events, cancel).await {
+        Ok(Some(_)) => (JobStatus::Succeeded, None),
+        Ok(None) => (JobStatus::Cancelled, None),
+        Err(e) => (JobStatus::Failed, Some(e.to_string())),
+    }
+}
+
+/// 开始执行前失败：恢复检查记录状态，并发送与执行中失败相同的错误事件，前端据此结束等待
+fn fail_before_start(db: &Database, job: &Job, events: &dyn EventSink, event: &str, error: AppError) -> Outcome {
+    if let Err(e) = db
+        .write()
+        .and_then(|conn| restore_record_status(&conn, job.kind, &job.record_id))
+    {
+        log::error!("恢复检查记录状态失败: {}", e);
+    }
+    let error = error.to_string();
+    events.emit(event, serde_json::json!({
+        "record_id": job.record_id,
+        "error": error,
+    }));
+    (JobStatus::Failed, Some(error))
+}
+
+/// 任务未能执行完时，按已有结果把停留在处理中的检查记录改回可再次发起的状态
+fn restore_record_status(conn: &Connection, kind: JobKind, record_id: &str) -> AppResult<()> {
+    let records = RecordRepo::new(conn);
+    let Ok(status) = records.status(record_id) else {
+        // 记录已删除
+        return Ok(());
+    };
+
+    match kind {
+        JobKind::Ocr if status == "ocr_processing" => {
+            let counts = OcrRepo::new(conn).counts(record_id)?;
+            records.set_status(record_id, if counts.success_ocr > 0 { "ocr_done" } else { "pending_ocr" })
+        }
+        JobKind::AiAnalysis if status == "ai_processing" => {
+            AiRepo::new(conn).fail_processing(record_id, "任务中断")?;
+            records.set_status(record_id, "ocr_done")
+        }
+        _ => Ok(()),
+    }
+}
+
+fn emit_job(events: &dyn EventSink, job: &Job, event: &str) {
+    events.emit(event, serde_json::to_value(job).unwrap_or_default());
+}
+
+/// 转发任务事件时附加 job_id，并把识别进度写入任务
+struct JobEvents<'a> {
+    db: &'a Database,
+    job_id: &'a str,
+    inner: &'a dyn EventSink,
+}
+
+impl EventSink for JobEvents<'_> {
+    fn emit(&self, event: &str, mut payload: serde_json::Value) {
+        let progress = match event {
+            "ocr_progress" => Some((payload["completed"].as_i64(), payload["total"].as_i64())),
+            "ocr_complete" => Some((payload["total"].as_i64(), payload["total"].as_i64())),
+            _ => None,
+        };
+        if let Some((Some(done), Some(total))) = progress
+            && let Err(e) = self
+                .db
+                .write()
+                .and_then(|conn| JobRepo::new(&conn).set_progress(self.job_id, done, total))
+        {
+            log::error!("更新任务进度失败: {}", e);
+        }
+
+        if let Some(object) = payload.as_object_mut() {
+            object.insert("job_id".into(), serde_json::Value::String(self.job_id.to_string()));
+        }
+        self.inner.emit(event, payload);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::file::NewCheckupFile;
+    use crate::repo::project::{CreateProjectInput, OcrEngineKind};
+    use crate::repo::record::CreateRecordInput;
+    use crate::repo::{FileRepo, PatientRepo, ProjectRepo};
+
+    /// 记录收到的事件
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(String, serde_json::Value)>>);
+
+    impl EventSink for Recorder {
+        fn emit(&self, event: &str, payload: serde_json::Value) {
+            self.0.lock().unwrap().push((event.to_string(), payload));
+        }
+    }
+
+    /// 临时目录中的数据库，以及一条带有一个 Tesseract 识别文件的检查记录
+    fn setup() -> (std::path::PathBuf, Database, String, String) {
+        let dir = std::env::temp_dir().join(format!("health-jobs-{}", uuid::Uuid::new_v4()));
+        let db = Database::new(dir.clone()).unwrap();
+        let (record_id, file_id) = {
+            let conn = db.write().unwrap();
+            let project = ProjectRepo::new(&conn)
+                .create(CreateProjectInput {
+                    name: "血常规".into(),
+                    description: None,
+                    ocr_engine: Some(OcrEngineKind::Tesseract),
+                })
+                .unwrap();
+            let patient = PatientRepo::new(&conn).active_id().unwrap();
+            let record = RecordRepo::new(&conn)
+                .create(&patient, CreateRecordInput { checkup_date: "2024-01-01".into(), notes: None })
+                .unwrap();
+            let file = FileRepo::new(&conn)
+                .insert(NewCheckupFile {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    record_id: record.id.clone(),
+                    project_id: project.id,
+                    project_name: project.name,
+                    original_filename: "a.jpg".into(),
+                    stored_path: "pictures/a.jpg".into(),
+                    file_size: 3,
+                    mime_type: "image/jpeg".into(),
+                })
+                .unwrap();
+            (record.id, file.id)
+        };
+        (dir, db, record_id, file_id)
+    }
+
+    #[tokio::test]
+    async fn failure_before_start_emits_error_event() {
+        let (dir, db, record_id, file_id) = setup();
+
+        // 入队后文件被删除，执行时准备阶段失败
+        let job = enqueue(&db, JobKind::Ocr, &record_id).unwrap();
+        FileRepo::new(&db.write().unwrap()).delete(&file_id).unwrap();
+
+        let events = Recorder::default();
+        let job = run_job(&db, &dir, &events, &JobQueue::default(), &job.id).await.unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+
+        let events = events.0.into_inner().unwrap();
+        let names: Vec<&str> = events.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["job_started", "ocr_error", "job_finished"]);
+        assert_eq!(events[1].1["record_id"], record_id.as_str());
+        assert_eq!(events[1].1["job_id"], job.id.as_str());
+        assert_eq!(RecordRepo::new(&db.read().unwrap()).status(&record_id).unwrap(), "pending_ocr");
+
+        drop(db);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn queued_and_running_jobs_can_be_cancelled_by_id() {
+        let (dir, db, record_id, _) = setup();
+        let queue = JobQueue::default();
+
+        let job = enqueue(&db, JobKind::Ocr, &record_id).unwrap();
+        assert_eq!(cancel(&db, &queue, &job).unwrap().status, JobStatus::Cancelled);
+
+        let job = enqueue(&db, JobKind::Ocr, &record_id).unwrap();
+        JobRepo::new(&db.write().unwrap()).claim(&job.id, "cli").unwrap();
+        let running = JobRepo::new(&db.read().unwrap()).get(&job.id).unwrap();
+        // 在其他进程中执行的任务无法取消
+        assert_eq!(cancel(&db, &queue, &running).unwrap_err().code(), "busy");
+        let token = queue.register(&job.id);
+        cancel(&db, &queue, &running).unwrap();
+        assert!(token.is_cancelled());
+
+        drop(db);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn cancel_after_claim_stops_the_running_job() {
+        let (dir, db, record_id, _) = setup();
+        let queue = JobQueue::default();
+
+        // 读取到排队中的状态后，执行器先领取了任务
+        let queued = enqueue(&db, JobKind::Ocr, &record_id).unwrap();
+        let token = queue.register(&queued.id);
+        assert!(JobRepo::new(&db.write().unwrap()).claim(&queued.id, &INSTANCE_ID).unwrap());
+
+        let job = cancel(&db, &queue, &queued).unwrap();
+        assert_eq!(job.status, JobStatus::Running);
+        assert!(token.is_cancelled());
+
+        JobRepo::new(&db.write().unwrap()).finish(&job.id, JobStatus::Cancelled, None).unwrap();
+
+        // 已取消的任务不会被之后的结束状态覆盖
+        let job = enqueue(&db, JobKind::Ocr, &record_id).unwrap();
+        cancel(&db, &queue, &job).unwrap();
+        let conn = db.write().unwrap();
+        let repo = JobRepo::new(&conn);
+        assert!(!repo.claim(&job.id, &INSTANCE_ID).unwrap());
+        repo.finish(&job.id, JobStatus::Succeeded, None).unwrap();
+        assert_eq!(repo.get(&job.id).unwrap().status, JobStatus::Cancelled);
+        drop(conn);
+
+        drop(db);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
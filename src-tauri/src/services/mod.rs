@@ -1 +1,17 @@
 pub mod http_client;
+pub mod provider;
+pub mod backup;
+pub mod trend;
+pub mod data_dir;
+pub mod trash;
+pub mod integrity;
+pub mod events;
+pub mod storage;
+pub mod ocr;
+pub mod ocr_engine;
+pub mod pdf;
+pub mod preprocess;
+pub mod ai;
+pub mod jobs;
+
+pub use trend::TrendService;
@@ -0,0 +1,736 @@
+use crate::db::Database;
+use crate::error::{AppError, AppResult};
+use crate::repo::ai_profile::AiTask;
+use crate::repo::indicator::Indicator;
+use crate::repo::ocr::{OcrParsedItem, OcrSourceFile, OcrTarget};
+use crate::repo::project::OcrEngineKind;
+use crate::repo::{ConfigRepo, IndicatorRepo, OcrRepo, RecordRepo};
+use crate::services::events::EventSink;
+use crate::services::http_client::{self, AiClientConfig, RetryAttempt};
+use crate::services::ocr_engine::{OcrEngine, Recognition, TesseractEngine, VisionEngine};
+use crate::services::pdf::PdfRenderer;
+use crate::services::preprocess::{PreprocessSettings, Preprocessor};
+use crate::services::provider::{ChatClient, OutputSchema};
+use rusqlite::Connection;
+use serde::Serialize;
+use futures_util::StreamExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio_util::sync::CancellationToken;
+
+/// 未配置 `ocr_concurrency` 时同时识别的文件数
+pub const DEFAULT_OCR_CONCURRENCY: usize = 3;
+/// 并发数上限，避免触发接口限流
+pub const MAX_OCR_CONCURRENCY: usize = 8;
+
+/// 默认 OCR Prompt 模板
+pub const DEFAULT_OCR_PROMPT: &str = "请识别图片中的医疗检查报告，提取所有检查指标的名称、数值、单位和参考范围。请以严格的JSON格式返回一个对象，其中 items 为数组，每个元素包含: name(指标名称)、value(数值,字符串)、unit(单位)、reference_range(参考范围)、is_abnormal(是否异常,布尔值)，没有的字段填空字符串。只返回JSON，不要返回其他内容。";
+
+#[derive(Debug, Serialize, Clone)]
+pub struct OcrProgress {
+    pub record_id: String,
+    pub total: usize,
+    pub completed: usize,
+    pub current_file: String,
+    /// processing / retrying / success / parse_failed / failed
+    pub status: String,
+    /// status 为 retrying 时的重试信息
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryAttempt>,
+    /// 正在识别 PDF 的第几页（从 1 开始），图片文件为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<usize>,
+    /// PDF 的总页数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page_count: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct OcrSummary {
+    pub record_id: String,
+    pub total: usize,
+    pub success: usize,
+    pub errors: Vec<String>,
+    /// 被取消时未识别的文件已保存为 cancelled 结果
+    pub cancelled: bool,
+}
+
+/// 一次 OCR 任务所需的全部数据，准备完成后不再需要读取配置
+pub struct OcrJob {
+    pub record_id: String,
+    checkup_date: String,
+    files: Vec<OcrSourceFile>,
+    /// 只有使用视觉模型的项目才需要 AI 配置
+    config: Option<AiClientConfig>,
+    prompt: String,
+    tesseract: TesseractEngine,
+    pdf: PdfRenderer,
+    preprocess: PreprocessSettings,
+    indicators: Vec<Indicator>,
+    concurrency: usize,
+}
+
+/// 检查记录是否可以开始识别，供排队前提前返回错误
+pub fn check(conn: &Connection, record_id: &str) -> AppResult<()> {
+    RecordRepo::new(conn).checkup_date(record_id)?;
+    let files = OcrRepo::new(conn).pending_files(record_id)?;
+    if files.is_empty() {
+        return Err(AppError::Validation("该检查记录下没有待识别的文件，请先上传检查报告图片".into()));
+    }
+    if uses_vision(&files) {
+        http_client::load_ai_config(conn, AiTask::Ocr)?;
+    }
+    Ok(())
+}
+
+fn uses_vision(files: &[OcrSourceFile]) -> bool {
+    files.iter().any(|file| file.ocr_engine == OcrEngineKind::Vision)
+}
+
+/// 读取待识别文件与 AI 配置，并将记录状态置为 ocr_processing
+pub fn prepare(db: &Database, record_id: &str) -> AppResult<OcrJob> {
+    let job = {
+        let conn = db.read()?;
+
+        let checkup_date = RecordRepo::new(&conn).checkup_date(record_id)?;
+        let files = OcrRepo::new(&conn).pending_files(record_id)?;
+        if files.is_empty() {
+            return Err(AppError::Validation("该检查记录下没有文件，请先上传检查报告图片".into()));
+        }
+
+        let config = if uses_vision(&files) {
+            Some(http_client::load_ai_config(&conn, AiTask::Ocr)?)
+        } else {
+            None
+        };
+
+        OcrJob {
+            record_id: record_id.to_string(),
+            checkup_date,
+            files,
+            config,
+            prompt: ConfigRepo::new(&conn).get_or("ocr_prompt_template", DEFAULT_OCR_PROMPT)?,
+            tesseract: TesseractEngine::load(&conn)?,
+            pdf: PdfRenderer::load(&conn)?,
+            preprocess: PreprocessSettings::load(&conn)?,
+            // 加载所有项目的指标（用于匹配 indicator_values）
+            indicators: IndicatorRepo::new(&conn).list_all()?,
+            concurrency: concurrency(&conn)?,
+        }
+    };
+
+    let conn = db.write()?;
+    RecordRepo::new(&conn).set_status(record_id, "ocr_processing")?;
+    Ok(job)
+}
+
+/// 并发识别文件并逐个保存结果，通过 `events` 发送 ocr_progress / ocr_error / ocr_complete 事件
+///
+/// 同时进行的请求数不超过 `ocr_concurrency`；每个文件开始时发送 processing 进度，结束时发送
+/// success / parse_failed / failed 进度，`completed` 为已结束的文件数。
+/// `cancel` 触发后立即中止进行中的请求，未完成的文件记为已取消并发送 ocr_cancelled 事件。
+pub async fn run(
+    db: &Database,
+    app_dir: &Path,
+    job: OcrJob,
+    events: &dyn EventSink,
+    cancel: &CancellationToken,
+) -> OcrSummary {
+    // 只有使用视觉模型的项目才需要 AI 客户端
+    let client = job.config.as_ref().map(|config| ChatClient::new(config, config.timeouts.ocr));
+    let client = match client.transpose() {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("OCR 创建客户端失败: {}", e);
+            events.emit("ocr_error", serde_json::json!({
+                "record_id": job.record_id,
+                "error": e.to_string(),
+            }));
+            set_status(db, &job.record_id, "pending_ocr");
+            let mut summary = OcrSummary::new(&job);
+            summary.errors.push(e.to_string());
+            return summary;
+        }
+    };
+
+    let vision = client.map(|client| VisionEngine::new(client, &job.prompt));
+    let vision = vision.as_ref().map(|engine| engine as &dyn OcrEngine);
+    recognize_all(db, app_dir, &job, vision, &job.tesseract, events, cancel).await
+}
+
+impl OcrSummary {
+    fn new(job: &OcrJob) -> Self {
+        Self {
+            record_id: job.record_id.clone(),
+            total: job.files.len(),
+            success: 0,
+            errors: Vec::new(),
+            cancelled: false,
+        }
+    }
+}
+
+/// 按项目选择视觉模型引擎 `vision` 或本地引擎 `local` 识别全部文件，保存结果并发送事件
+async fn recognize_all(
+    db: &Database,
+    app_dir: &Path,
+    job: &OcrJob,
+    vision: Option<&dyn OcrEngine>,
+    local: &dyn OcrEngine,
+    events: &dyn EventSink,
+    cancel: &CancellationToken,
+) -> OcrSummary {
+    let total = job.files.len();
+    let mut summary = OcrSummary::new(job);
+    let preprocessor = Preprocessor::new(job.preprocess, app_dir);
+
+    let completed = AtomicUsize::new(0);
+    let (job_ref, completed_ref, preprocessor) = (job, &completed, &preprocessor);
+    let recognitions: Vec<_> = job
+        .files
+        .iter()
+        .enumerate()
+        .map(|(i, file)| async move {
+            emit_progress(events, job_ref, completed_ref.load(Ordering::SeqCst), file, "processing", None, None);
+            // PDF 当前识别的页码与总页数，图片文件始终为 0
+            let (page, page_count) = (AtomicUsize::new(0), AtomicUsize::new(0));
+            let current_page = || match page_count.load(Ordering::SeqCst) {
+                0 => None,
+                count => Some((page.load(Ordering::SeqCst), count)),
+            };
+            let on_page = |number: usize, count: usize| {
+                page.store(number, Ordering::SeqCst);
+                page_count.store(count, Ordering::SeqCst);
+                let completed = completed_ref.load(Ordering::SeqCst);
+                emit_progress(events, job_ref, completed, file, "processing", None, current_page());
+            };
+            let on_retry = |retry: &RetryAttempt| {
+                let completed = completed_ref.load(Ordering::SeqCst);
+                emit_progress(events, job_ref, completed, file, "retrying", Some(retry.clone()), current_page());
+            };
+            let engine = match file.ocr_engine {
+                OcrEngineKind::Vision => vision,
+                OcrEngineKind::Tesseract => Some(local),
+            };
+            (i, recognize(engine, &job_ref.pdf, preprocessor, file, app_dir, &on_page, &on_retry).await)
+        })
+        .collect();
+    let mut recognitions = futures_util::stream::iter(recognitions).buffer_unordered(job.concurrency);
+
+    let mut finished = vec![false; total];
+    loop {
+        // 取消时丢弃进行中的请求
+        let next = tokio::select! {
+            next = recognitions.next() => next,
+            _ = cancel.cancelled() => {
+                summary.cancelled = true;
+                break;
+            }
+        };
+        let Some((i, recognized)) = next else {
+            break;
+        };
+
+        let file = &job.files[i];
+        let target = OcrTarget {
+            file_id: &file.file_id,
+            record_id: &job.record_id,
+            project_id: &file.project_id,
+            checkup_date: &job.checkup_date,
+        };
+        let status = match recognized {
+            Ok(Recognition { raw, items, parse_error: Some(error) }) => {
+                // 保留原始输出，不写入指标值，修正后再保存
+                let err_msg = format!("{}: {}", file.original_filename, error);
+                summary.errors.push(err_msg.clone());
+                let saved = db
+                    .write()
+                    .and_then(|conn| OcrRepo::new(&conn).save_parse_failed(&target, &raw, &items, &err_msg));
+                match saved {
+                    Ok(_) => "parse_failed",
+                    Err(e) => save_error(db, &target, file, e, &mut summary),
+                }
+            }
+            Ok(Recognition { raw, items, parse_error: None }) => {
+                // 保存 OCR 结果并写入匹配到的指标值
+                let saved = db
+                    .write()
+                    .and_then(|conn| OcrRepo::new(&conn).save_success(&target, &raw, &items, &job.indicators));
+                match saved {
+                    Ok(_) => {
+                        summary.success += 1;
+                        "success"
+                    }
+                    Err(e) => save_error(db, &target, file, e, &mut summary),
+                }
+            }
+            Err(detail) => {
+                let err_msg = format!("{}: {}", file.original_filename, detail);
+                summary.errors.push(err_msg.clone());
+                save_failure(db, &target, &err_msg);
+                "failed"
+            }
+        };
+        finished[i] = true;
+        let done = completed.fetch_add(1, Ordering::SeqCst) + 1;
+        emit_progress(events, job, done, file, status, None, None);
+    }
+    drop(recognitions);
+
+    if summary.cancelled {
+        let unfinished = job.files.iter().zip(&finished).filter(|(_, done)| !**done).map(|(file, _)| file);
+        save_cancelled(db, job, unfinished);
+    }
+
+    // 更新检查记录状态
+    let new_status = if summary.success > 0 { "ocr_done" } else { "pending_ocr" };
+    set_status(db, &job.record_id, new_status);
+
+    // 发送完成事件
+    events.emit(if summary.cancelled { "ocr_cancelled" } else { "ocr_complete" }, serde_json::json!({
+        "record_id": summary.record_id,
+        "total": summary.total,
+        "success": summary.success,
+        "errors": summary.errors,
+    }));
+    summary
+}
+
+fn emit_progress(
+    events: &dyn EventSink,
+    job: &OcrJob,
+    completed: usize,
+    file: &OcrSourceFile,
+    status: &str,
+    retry: Option<RetryAttempt>,
+    page: Option<(usize, usize)>,
+) {
+    let progress = OcrProgress {
+        record_id: job.record_id.clone(),
+        total: job.files.len(),
+        completed,
+        current_file: file.original_filename.clone(),
+        status: status.to_string(),
+        retry,
+        page: page.map(|(number, _)| number),
+        page_count: page.map(|(_, count)| count),
+    };
+    events.emit("ocr_progress", serde_json::to_value(progress).unwrap_or_default());
+}
+
+/// 为未完成的文件保存已取消的结果
+fn save_cancelled<'a>(db: &Database, job: &OcrJob, files: impl Iterator<Item = &'a OcrSourceFile>) {
+    let saved = db.write().and_then(|conn| {
+        let repo = OcrRepo::new(&conn);
+        for file in files {
+            repo.save_cancelled(&OcrTarget {
+                file_id: &file.file_id,
+                record_id: &job.record_id,
+                project_id: &file.project_id,
+                checkup_date: &job.checkup_date,
+            })?;
+        }
+        Ok(())
+    });
+    if let Err(e) = saved {
+        log::error!("保存 OCR 取消结果失败: {}", e);
+    }
+}
+
+/// 读取 `ocr_concurrency` 配置，无效时使用默认值，超出范围时取边界值
+pub fn concurrency(conn: &Connection) -> AppResult<usize> {
+    let value = ConfigRepo::new(conn).get_or("ocr_concurrency", "")?;
+    Ok(value
+        .trim()
+        .parse::<usize>()
+        .map(|n| n.clamp(1, MAX_OCR_CONCURRENCY))
+        .unwrap_or(DEFAULT_OCR_CONCURRENCY))
+}
+
+/// 使用项目所选的引擎识别单个文件；失败时返回错误描述
+///
+/// 识别前按设置预处理图片；PDF 先逐页渲染为图片再逐页识别，结果合并为一条，每个条目记录所在页码；任一页失败时整个文件记为失败，
+/// 任一页输出未通过校验时整个文件记为 parse_failed，其余页的条目保留。
+/// `on_page` 在开始识别每一页时调用，参数为页码与总页数。
+async fn recognize(
+    engine: Option<&dyn OcrEngine>,
+    pdf: &PdfRenderer,
+    preprocessor: &Preprocessor,
+    file: &OcrSourceFile,
+    app_dir: &Path,
+    on_page: &(dyn Fn(usize, usize) + Sync),
+    on_retry: &(dyn Fn(&RetryAttempt) + Sync),
+) -> Result<Recognition, String> {
+    let engine = engine.ok_or_else(|| "未配置 OCR 识别所用的 AI 服务".to_string())?;
+    let path = app_dir.join(&file.stored_path);
+
+    // 失败时按策略重试，结果只在最终成功或失败后保存一次
+    if file.mime_type != "application/pdf" {
+        let image = preprocessor.image(&path, &file.stored_path, &file.mime_type).await?;
+        return engine.recognize(&image.data, &image.mime_type, on_retry).await;
+    }
+
+    let pages = preprocessor.pdf_pages(pdf, &path, &file.stored_path).await?;
+    let mut raw = Vec::new();
+    let mut items = Vec::new();
+    let mut parse_errors = Vec::new();
+    for (i, image) in pages.iter().enumerate() {
+        let number = i + 1;
+        on_page(number, pages.len());
+        let page = engine
+            .recognize(&image.data, &image.mime_type, on_retry)
+            .await
+            .map_err(|e| format!("第 {} 页: {}", number, e))?;
+        raw.push(format!("--- 第 {} 页 ---\n{}", number, page.raw));
+        if let Some(error) = page.parse_error {
+            parse_errors.push(format!("第 {} 页: {}", number, error));
+        }
+        items.extend(page.items.into_iter().map(|item| OcrParsedItem { page: Some(number as u32), ..item }));
+    }
+    let parse_error = (!parse_errors.is_empty()).then(|| parse_errors.join("；"));
+    Ok(Recognition { raw: raw.join("\n\n"), items, parse_error })
+}
+
+/// 保存 OCR 错误结果
+/// 识别结果未能保存时按识别失败处理，返回进度事件的状态
+fn save_error(db: &Database, target: &OcrTarget, file: &OcrSourceFile, error: AppError, summary: &mut OcrSummary) -> &'static str {
+    log::error!("保存 OCR 结果失败: {}", error);
+    let err_msg = format!("{}: 保存识别结果失败: {}", file.original_filename, error);
+    summary.errors.push(err_msg.clone());
+    save_failure(db, target, &err_msg);
+    "failed"
+}
+
+fn save_failure(db: &Database, target: &OcrTarget, error_msg: &str) {
+    let saved = db
+        .write()
+        .and_then(|conn| OcrRepo::new(&conn).save_failure(target, error_msg));
+    if let Err(e) = saved {
+        log::error!("保存 OCR 错误结果失败: {}", e);
+    }
+}
+
+fn set_status(db: &Database, record_id: &str, status: &str) {
+    if let Err(e) = db
+        .write()
+        .and_then(|conn| RecordRepo::new(&conn).set_status(record_id, status))
+    {
+        log::error!("更新检查记录状态失败: {}", e);
+    }
+}
+
+/// 视觉模型结构化输出的 JSON Schema；OpenAI 严格模式要求顶层为对象，指标列表放在 items 中
+pub fn items_schema() -> OutputSchema {
+    let text = serde_json::json!({ "type": "string" });
+    OutputSchema {
+        name: "checkup_items".into(),
+        schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "items": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "name": text,
+                            "value": text,
+                            "unit": text,
+                            "reference_range": text,
+                            "is_abnormal": { "type": "boolean" },
+                        },
+                        "required": ["name", "value", "unit", "reference_range", "is_abnormal"],
+                        "additionalProperties": false,
+                    },
+                },
+            },
+            "required": ["items"],
+            "additionalProperties": false,
+        }),
+    }
+}
+
+/// 按 [`items_schema`] 校验模型输出并返回指标列表，不符合时返回第一处错误
+///
+/// 也接受顶层直接为数组的输出（自定义 Prompt 或不支持结构化输出的接口），以及包裹整个输出的
+/// markdown 代码块；数值允许为 JSON 数字。
+pub fn parse_items(content: &str) -> Result<Vec<OcrParsedItem>, String> {
+    let trimmed = content.trim();
+    let json = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|s| s.strip_suffix("```"))
+        .unwrap_or(trimmed);
+
+    let value: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("输出不是有效的 JSON - {}", e))?;
+    let list = match &value {
+        serde_json::Value::Array(list) => list,
+        serde_json::Value::Object(object) => object
+            .get("items")
+            .and_then(|items| items.as_array())
+            .ok_or("输出缺少 items 数组")?,
+        _ => return Err("输出应为包含 items 数组的对象".into()),
+    };
+
+    list.iter()
+        .enumerate()
+        .map(|(i, item)| parse_item(item).map_err(|e| format!("第 {} 项{}", i + 1, e)))
+        .collect()
+}
+
+fn parse_item(item: &serde_json::Value) -> Result<OcrParsedItem, String> {
+    let object = item.as_object().ok_or("不是对象")?;
+    let text = |key: &str| -> Result<String, String> {
+        match object.get(key) {
+            Some(serde_json::Value::String(s)) => Ok(s.trim().to_string()),
+            Some(_) => Err(format!("的 {} 应为字符串", key)),
+            None => Err(format!("缺少 {}", key)),
+        }
+    };
+
+    let name = text("name")?;
+    if name.is_empty() {
+        return Err("的 name 为空".into());
+    }
+    let value = match object.get("value") {
+        Some(serde_json::Value::Number(n)) => n.to_string(),
+        _ => text("value")?,
+    };
+    let is_abnormal = match object.get("is_abnormal") {
+        Some(serde_json::Value::Bool(b)) => *b,
+        Some(_) => return Err("的 is_abnormal 应为布尔值".into()),
+        None => return Err("缺少 is_abnormal".into()),
+    };
+
+    Ok(OcrParsedItem {
+        name,
+        value,
+        unit: text("unit")?,
+        reference_range: text("reference_range")?,
+        is_abnormal,
+        page: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::file::NewCheckupFile;
+    use crate::repo::project::{CreateProjectInput, Project, UpdateProjectInput};
+    use crate::repo::record::CreateRecordInput;
+    use crate::repo::{FileRepo, PatientRepo, ProjectRepo};
+    use futures_util::FutureExt;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// 记录收到的事件
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(String, serde_json::Value)>>);
+
+    impl EventSink for Recorder {
+        fn emit(&self, event: &str, payload: serde_json::Value) {
+            self.0.lock().unwrap().push((event.to_string(), payload));
+        }
+    }
+
+    /// 每次识别耗时一段时间，并记录同时进行的最大识别数
+    #[derive(Default)]
+    struct SlowEngine {
+        running: AtomicUsize,
+        max_running: AtomicUsize,
+    }
+
+    impl OcrEngine for SlowEngine {
+        fn recognize<'a>(
+            &'a self,
+            _image: &'a [u8],
+            _mime_type: &'a str,
+            _on_retry: &'a (dyn Fn(&RetryAttempt) + Sync),
+        ) -> futures_util::future::BoxFuture<'a, Result<Recognition, String>> {
+            async move {
+                let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+                self.max_running.fetch_max(running, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(30)).await;
+                self.running.fetch_sub(1, Ordering::SeqCst);
+                Ok(Recognition { raw: "[]".into(), items: Vec::new(), parse_error: None })
+            }
+            .boxed()
+        }
+    }
+
+    /// 已执行全部迁移的内存数据库
+    fn setup() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run(&mut conn, Path::new("unused")).unwrap();
+        conn
+    }
+
+    /// 新建检查记录并登记 `count` 个图片文件（pictures/<序号>.jpg），返回记录 id
+    fn create_record_with_files(conn: &Connection, project: &Project, count: usize) -> String {
+        let patient = PatientRepo::new(conn).active_id().unwrap();
+        let record = RecordRepo::new(conn)
+            .create(&patient, CreateRecordInput { checkup_date: "2024-01-01".into(), notes: None })
+            .unwrap();
+        for i in 0..count {
+            FileRepo::new(conn)
+                .insert(NewCheckupFile {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    record_id: record.id.clone(),
+                    project_id: project.id.clone(),
+                    project_name: project.name.clone(),
+                    original_filename: format!("{}.jpg", i),
+                    stored_path: format!("pictures/{}.jpg", i),
+                    file_size: 3,
+                    mime_type: "image/jpeg".into(),
+                })
+                .unwrap();
+        }
+        record.id
+    }
+
+    #[test]
+    fn ocr_concurrency_falls_back_and_is_clamped() {
+        let conn = setup();
+        let repo = ConfigRepo::new(&conn);
+        assert_eq!(concurrency(&conn).unwrap(), DEFAULT_OCR_CONCURRENCY);
+
+        for (value, expected) in [("5", 5), ("0", 1), ("100", MAX_OCR_CONCURRENCY), ("abc", DEFAULT_OCR_CONCURRENCY)] {
+            repo.set("ocr_concurrency", value).unwrap();
+            assert_eq!(concurrency(&conn).unwrap(), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn local_ocr_projects_do_not_need_ai_config() {
+        let conn = setup();
+        let project = ProjectRepo::new(&conn)
+            .create(CreateProjectInput { name: "血常规".into(), description: None, ocr_engine: None })
+            .unwrap();
+        let record_id = create_record_with_files(&conn, &project, 1);
+        assert_eq!(check(&conn, &record_id).unwrap_err().code(), "config_missing");
+
+        ProjectRepo::new(&conn)
+            .update(UpdateProjectInput {
+                id: project.id,
+                name: None,
+                description: None,
+                is_active: None,
+                sort_order: None,
+                ocr_engine: Some(OcrEngineKind::Tesseract),
+            })
+            .unwrap();
+        let pending = OcrRepo::new(&conn).pending_files(&record_id).unwrap();
+        assert_eq!(pending[0].ocr_engine, OcrEngineKind::Tesseract);
+        check(&conn, &record_id).unwrap();
+    }
+
+    #[test]
+    fn ocr_output_is_validated_against_schema() {
+        let item = r#"{"name": "血糖", "value": 5.1, "unit": "mmol/L", "reference_range": "3.9-6.1", "is_abnormal": false}"#;
+        let items = parse_items(&format!(r#"{{"items": [{}]}}"#, item)).unwrap();
+        assert_eq!(items[0].value, "5.1");
+        // 模型输出中没有页码，识别 PDF 时再补上
+        assert_eq!(items[0].page, None);
+        assert_eq!(parse_items(&format!("```json\n[{}]\n```", item)).unwrap().len(), 1);
+        assert!(parse_items(r#"{"items": []}"#).unwrap().is_empty());
+
+        // 不再从说明文字中猜测 JSON，也不接受中文键名或缺少的字段
+        assert!(parse_items(&format!("识别结果如下：[{}]", item)).unwrap_err().contains("不是有效的 JSON"));
+        assert_eq!(parse_items(r#"[{"指标名称": "血糖"}]"#).unwrap_err(), "第 1 项缺少 name");
+        let wrong_flag = r#"[{"name": "血糖", "value": "5.1", "unit": "", "reference_range": "", "is_abnormal": "否"}]"#;
+        assert_eq!(parse_items(wrong_flag).unwrap_err(), "第 1 项的 is_abnormal 应为布尔值");
+        assert_eq!(parse_items(r#"{"data": []}"#).unwrap_err(), "输出缺少 items 数组");
+    }
+
+    /// 临时数据目录中的数据库，检查记录下有 `count` 个使用本地引擎识别的图片文件，识别并发数为 2
+    fn database_with_files(count: usize) -> (PathBuf, Database, String) {
+        let dir = std::env::temp_dir().join(format!("health-ocr-{}", uuid::Uuid::new_v4()));
+        let db = Database::new(dir.clone()).unwrap();
+        let record_id = {
+            let conn = db.write().unwrap();
+            ConfigRepo::new(&conn).set("ocr_concurrency", "2").unwrap();
+            let project = ProjectRepo::new(&conn)
+                .create(CreateProjectInput {
+                    name: "血常规".into(),
+                    description: None,
+                    ocr_engine: Some(OcrEngineKind::Tesseract),
+                })
+                .unwrap();
+            std::fs::create_dir_all(dir.join("pictures")).unwrap();
+            for i in 0..count {
+                std::fs::write(dir.join(format!("pictures/{}.jpg", i)), b"jpg").unwrap();
+            }
+            create_record_with_files(&conn, &project, count)
+        };
+        (dir, db, record_id)
+    }
+
+    #[tokio::test]
+    async fn files_are_recognized_concurrently_with_ordered_progress() {
+        const FILES: usize = 5;
+        let (dir, db, record_id) = database_with_files(FILES);
+
+        let job = prepare(&db, &record_id).unwrap();
+        let engine = SlowEngine::default();
+        let events = Recorder::default();
+        let summary = recognize_all(&db, &dir, &job, None, &engine, &events, &CancellationToken::new()).await;
+        assert_eq!((summary.total, summary.success, summary.cancelled), (FILES, FILES, false));
+        assert_eq!(engine.max_running.load(Ordering::SeqCst), 2);
+
+        // 每个文件结束时 completed 加一，进行中的进度不超过已结束的文件数
+        let events = events.0.into_inner().unwrap();
+        let progress: Vec<_> = events.iter().filter(|(name, _)| name == "ocr_progress").map(|(_, p)| p).collect();
+        assert!(progress.iter().all(|p| p["total"] == FILES));
+        let finished: Vec<_> = progress
+            .iter()
+            .filter(|p| p["status"] == "success")
+            .map(|p| p["completed"].as_u64().unwrap())
+            .collect();
+        assert_eq!(finished, (1..=FILES as u64).collect::<Vec<_>>());
+        assert_eq!(progress.iter().filter(|p| p["status"] == "processing").count(), FILES);
+        assert_eq!(events.last().unwrap().0, "ocr_complete");
+        assert_eq!(RecordRepo::new(&db.read().unwrap()).status(&record_id).unwrap(), "ocr_done");
+
+        drop(db);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn unsaved_results_count_as_failed() {
+        let (dir, db, record_id) = database_with_files(2);
+        // 成功结果无法写入（如磁盘已满），失败结果仍可保存
+        db.write()
+            .unwrap()
+            .execute_batch(
+                "CREATE TEMP TRIGGER fail_success BEFORE INSERT ON ocr_results WHEN NEW.status = 'success'
+                 BEGIN SELECT RAISE(ABORT, 'disk full'); END;",
+            )
+            .unwrap();
+
+        let job = prepare(&db, &record_id).unwrap();
+        let events = Recorder::default();
+        let summary =
+            recognize_all(&db, &dir, &job, None, &SlowEngine::default(), &events, &CancellationToken::new()).await;
+        assert_eq!((summary.success, summary.errors.len()), (0, 2));
+        assert!(summary.errors.iter().all(|e| e.contains("保存识别结果失败")));
+
+        let events = events.0.into_inner().unwrap();
+        let finished: Vec<_> = events
+            .iter()
+            .filter(|(name, p)| name == "ocr_progress" && p["status"] != "processing")
+            .map(|(_, p)| p["status"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(finished, ["failed", "failed"]);
+
+        {
+            let conn = db.read().unwrap();
+            assert_eq!(RecordRepo::new(&conn).status(&record_id).unwrap(), "pending_ocr");
+            let results = OcrRepo::new(&conn).list(&record_id).unwrap();
+            assert!(results.len() == 2 && results.iter().all(|r| r.status == "failed"));
+        }
+
+        drop(db);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
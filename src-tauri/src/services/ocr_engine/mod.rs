@@ -0,0 +1,33 @@
+//! OCR 引擎：将一张报告图片识别为检查指标
+//!
+//! 视觉模型引擎把图片发送给 AI 服务；Tesseract 引擎在本机识别文字与表格，图片不离开本机。
+//! 每个检查项目在设置中选择使用哪种引擎，见 [`OcrEngineKind`](crate::repo::project::OcrEngineKind)。
+
+pub mod tesseract;
+pub mod vision;
+
+use crate::repo::ocr::OcrParsedItem;
+use crate::services::http_client::RetryAttempt;
+use futures_util::future::BoxFuture;
+
+pub use tesseract::TesseractEngine;
+pub use vision::VisionEngine;
+
+/// 一个文件的识别结果
+pub struct Recognition {
+    /// 引擎的原始输出，保存在 OCR 结果中供核对
+    pub raw: String,
+    pub items: Vec<OcrParsedItem>,
+    /// 输出未通过校验时的错误，此时结果保存为 parse_failed，`raw` 保留原始输出
+    pub parse_error: Option<String>,
+}
+
+pub trait OcrEngine: Send + Sync {
+    /// 识别一张报告图片，失败时返回错误描述；`on_retry` 在请求重试前调用
+    fn recognize<'a>(
+        &'a self,
+        image: &'a [u8],
+        mime_type: &'a str,
+        on_retry: &'a (dyn Fn(&RetryAttempt) + Sync),
+    ) -> BoxFuture<'a, Result<Recognition, String>>;
+}
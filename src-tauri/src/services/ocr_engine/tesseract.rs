@@ -0,0 +1,355 @@
+use super::{OcrEngine, Recognition};
+use crate::error::AppResult;
+use crate::repo::ocr::OcrParsedItem;
+use crate::repo::ConfigRepo;
+use crate::services::http_client::RetryAttempt;
+use futures_util::future::BoxFuture;
+use rusqlite::Connection;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+/// 未配置 `tesseract_path` 时使用 PATH 中的 tesseract
+pub const DEFAULT_PROGRAM: &str = "tesseract";
+/// 未配置 `tesseract_languages` 时的识别语言
+pub const DEFAULT_LANGUAGES: &str = "chi_sim+eng";
+/// 单张图片的识别时限
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+/// 调用本机 Tesseract 识别，按文字位置还原表格行，再从每行中提取指标
+pub struct TesseractEngine {
+    program: String,
+    languages: String,
+}
+
+impl TesseractEngine {
+    /// 读取 `tesseract_path` 与 `tesseract_languages` 配置
+    pub fn load(conn: &Connection) -> AppResult<Self> {
+        let config = ConfigRepo::new(conn);
+        let setting = |key: &str, default: &str| -> AppResult<String> {
+            let value = config.get_or(key, "")?;
+            let value = value.trim();
+            Ok(if value.is_empty() { default } else { value }.to_string())
+        };
+        Ok(Self {
+            program: setting("tesseract_path", DEFAULT_PROGRAM)?,
+            languages: setting("tesseract_languages", DEFAULT_LANGUAGES)?,
+        })
+    }
+
+    /// 输出 TSV 格式的识别结果；psm 6 将整张图视为一个文本块，同一表格行的文字保持在同一行
+    async fn run(&self, image: &[u8]) -> Result<String, String> {
+        let mut child = tokio::process::Command::new(&self.program)
+            .args(["stdin", "stdout", "-l", &self.languages, "--psm", "6", "tsv"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // 取消识别时结束进程
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("无法启动 Tesseract（{}），请确认已安装并在设置中填写程序路径 - {}", self.program, e))?;
+
+        let mut stdin = child.stdin.take();
+        let write = async move {
+            if let Some(stdin) = stdin.as_mut() {
+                stdin.write_all(image).await?;
+            }
+            drop(stdin);
+            std::io::Result::Ok(())
+        };
+        let (written, output) = tokio::time::timeout(TIMEOUT, async { tokio::join!(write, child.wait_with_output()) })
+            .await
+            .map_err(|_| format!("本地识别超时（{} 秒）", TIMEOUT.as_secs()))?;
+        let output = output.map_err(|e| format!("本地识别失败 - {}", e))?;
+
+        if !output.status.success() {
+            return Err(format!("本地识别失败 - {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        written.map_err(|e| format!("本地识别失败 - {}", e))?;
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl OcrEngine for TesseractEngine {
+    fn recognize<'a>(
+        &'a self,
+        image: &'a [u8],
+        mime_type: &'a str,
+        _on_retry: &'a (dyn Fn(&RetryAttempt) + Sync),
+    ) -> BoxFuture<'a, Result<Recognition, String>> {
+        Box::pin(async move {
+            if !mime_type.starts_with("image/") {
+                return Err(format!("本地识别仅支持图片文件（{}）", mime_type));
+            }
+            let rows = parse_tsv(&self.run(image).await?);
+            let raw = rows.iter().map(|cells| cells.join("\t")).collect::<Vec<_>>().join("\n");
+            let items = table_items(&rows);
+            Ok(Recognition { raw, items, parse_error: None })
+        })
+    }
+}
+
+/// TSV 中的一个词
+struct Word {
+    left: i64,
+    right: i64,
+    height: i64,
+    text: String,
+}
+
+/// 将 Tesseract 的 TSV 输出还原为表格：每行文字按词间距拆分为单元格
+///
+/// 间距超过行高的相邻词视为不同列；整行只有一个单元格时按词拆分。
+pub fn parse_tsv(tsv: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut line_key = None;
+    let mut words: Vec<Word> = Vec::new();
+
+    // 列：level page block par line word left top width height conf text
+    for line in tsv.lines().skip(1) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 12 || fields[0] != "5" || fields[11].trim().is_empty() {
+            continue;
+        }
+        let number = |i: usize| fields[i].trim().parse::<i64>().unwrap_or(0);
+        let key = (number(1), number(2), number(3), number(4));
+        if line_key != Some(key) {
+            rows.extend(cells_of(&mut words));
+            line_key = Some(key);
+        }
+        words.push(Word {
+            left: number(6),
+            right: number(6) + number(8),
+            height: number(9),
+            text: fields[11].trim().to_string(),
+        });
+    }
+    rows.extend(cells_of(&mut words));
+    rows
+}
+
+fn cells_of(words: &mut Vec<Word>) -> Option<Vec<String>> {
+    if words.is_empty() {
+        return None;
+    }
+    words.sort_by_key(|word| word.left);
+    let gap = words.iter().map(|word| word.height).max().unwrap_or(0);
+
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut prev_right = None;
+    for word in words.iter() {
+        if prev_right.is_some_and(|right| word.left - right > gap) {
+            cells.push(std::mem::take(&mut cell));
+        }
+        // 中文字符之间不加空格
+        if let (Some(a), Some(b)) = (cell.chars().last(), word.text.chars().next())
+            && (a.is_ascii() || b.is_ascii())
+        {
+            cell.push(' ');
+        }
+        cell.push_str(&word.text);
+        prev_right = Some(word.right);
+    }
+    cells.push(cell);
+
+    if cells.len() == 1 {
+        cells = words.iter().map(|word| word.text.clone()).collect();
+    }
+    words.clear();
+    Some(cells)
+}
+
+/// 从表格行中提取指标：名称在前，随后依次为结果、异常标记、单位与参考范围（顺序不限）
+///
+/// 没有结果的行（表头、标题、备注等）被忽略。
+pub fn table_items(rows: &[Vec<String>]) -> Vec<OcrParsedItem> {
+    rows.iter().filter_map(|row| row_item(&split_cells(row))).collect()
+}
+
+fn row_item(cells: &[String]) -> Option<OcrParsedItem> {
+    // 名称之前的纯数字单元格为序号
+    let mut name_parts = Vec::new();
+    let mut value_index = None;
+    for (i, cell) in cells.iter().enumerate() {
+        if !name_parts.is_empty() && is_value(cell) {
+            value_index = Some(i);
+            break;
+        }
+        if cell.parse::<f64>().is_err() {
+            name_parts.push(cell.as_str());
+        }
+    }
+    let value_index = value_index?;
+
+    let mut value = cells[value_index].clone();
+    let mut flagged = false;
+    if let Some(stripped) = value.strip_suffix(['↑', '↓', '*']) {
+        value = stripped.trim().to_string();
+        flagged = true;
+    }
+
+    let mut unit = String::new();
+    let mut reference_range = String::new();
+    for cell in &cells[value_index + 1..] {
+        if is_flag(cell) {
+            flagged = true;
+        } else if reference_range.is_empty() && is_range(cell) {
+            reference_range = cell.clone();
+        } else if unit.is_empty() && is_unit(cell) {
+            unit = cell.clone();
+        }
+    }
+
+    let is_abnormal = flagged || out_of_range(&value, &reference_range);
+    Some(OcrParsedItem {
+        name: name_parts.join(" "),
+        value,
+        unit,
+        reference_range,
+        is_abnormal,
+        page: None,
+    })
+}
+
+/// 数值与名称或单位间距较小时会被识别为同一单元格，按空格拆开
+fn split_cells(cells: &[String]) -> Vec<String> {
+    let mut result = Vec::new();
+    for cell in cells {
+        if is_range(cell) {
+            result.push(cell.clone());
+            continue;
+        }
+        let mut pending = String::new();
+        for token in cell.split_whitespace() {
+            if is_value(token) || is_flag(token) {
+                if !pending.is_empty() {
+                    result.push(std::mem::take(&mut pending));
+                }
+                result.push(token.to_string());
+            } else {
+                if !pending.is_empty() {
+                    pending.push(' ');
+                }
+                pending.push_str(token);
+            }
+        }
+        if !pending.is_empty() {
+            result.push(pending);
+        }
+    }
+    result
+}
+
+const QUALITATIVE: &[&str] = &["阴性", "阳性", "弱阳性", "negative", "positive"];
+
+fn is_qualitative(cell: &str) -> bool {
+    let lower = cell.to_lowercase();
+    QUALITATIVE.iter().any(|word| lower.starts_with(word))
+}
+
+fn is_positive(cell: &str) -> bool {
+    let lower = cell.to_lowercase();
+    ["阳性", "弱阳性", "positive"].iter().any(|word| lower.starts_with(word))
+}
+
+/// 去掉比较符号后的数值
+fn numeric(cell: &str) -> Option<f64> {
+    cell.trim_start_matches(['<', '>', '≤', '≥']).trim().parse().ok()
+}
+
+/// 数值（可带比较符号或异常箭头）或定性结果
+fn is_value(cell: &str) -> bool {
+    numeric(cell.trim_end_matches(['↑', '↓', '*'])).is_some() || is_qualitative(cell)
+}
+
+fn is_flag(cell: &str) -> bool {
+    matches!(cell, "↑" | "↓" | "H" | "L" | "*" | "高" | "低" | "偏高" | "偏低" | "异常")
+}
+
+/// 参考范围的上下限，只有一侧时另一侧为 None
+fn range_bounds(cell: &str) -> Option<(Option<f64>, Option<f64>)> {
+    let normalized = cell.replace(['～', '~', '—', '－', '–'], "-");
+    let normalized = normalized.trim();
+    if let Some(max) = normalized.strip_prefix(['<', '≤']) {
+        return Some((None, Some(max.trim().parse().ok()?)));
+    }
+    if let Some(min) = normalized.strip_prefix(['>', '≥']) {
+        return Some((Some(min.trim().parse().ok()?), None));
+    }
+    // 跳过首字符，允许下限为负数
+    let (split, _) = normalized.char_indices().skip(1).find(|(_, c)| *c == '-')?;
+    let min = normalized[..split].trim().parse().ok()?;
+    let max = normalized[split + 1..].trim().parse().ok()?;
+    Some((Some(min), Some(max)))
+}
+
+fn is_range(cell: &str) -> bool {
+    range_bounds(cell).is_some() || is_qualitative(cell)
+}
+
+/// 单位：不含中文，含字母或百分号，如 g/L、10^9/L、%
+fn is_unit(cell: &str) -> bool {
+    cell.chars().count() <= 16
+        && cell.chars().all(|c| (c as u32) < 0x2E80)
+        && cell.chars().any(|c| c.is_ascii_alphabetic() || c == '%' || c == '‰')
+}
+
+/// 没有异常标记时按参考范围判断
+fn out_of_range(value: &str, reference_range: &str) -> bool {
+    if is_qualitative(value) {
+        return is_qualitative(reference_range) && is_positive(value) != is_positive(reference_range);
+    }
+    let (Some(value), Some((min, max))) = (numeric(value), range_bounds(reference_range)) else {
+        return false;
+    };
+    min.is_some_and(|min| value < min) || max.is_some_and(|max| value > max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tesseract_table_rows_become_items() {
+        let header = "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext";
+        let word = |line: u32, left: u32, width: u32, text: &str| {
+            format!("5\t1\t1\t1\t{}\t1\t{}\t{}\t{}\t20\t90\t{}", line, left, line * 30, width, text)
+        };
+        let tsv = [
+            header.to_string(),
+            word(1, 10, 60, "项目"),
+            word(1, 200, 40, "结果"),
+            word(1, 300, 40, "单位"),
+            word(2, 10, 10, "1"),
+            word(2, 40, 20, "血"),
+            word(2, 62, 20, "红"),
+            word(2, 84, 40, "蛋白"),
+            word(2, 200, 30, "112"),
+            word(2, 232, 10, "↓"),
+            word(2, 300, 30, "g/L"),
+            word(2, 400, 60, "130-175"),
+            word(3, 10, 30, "ALT"),
+            word(3, 200, 20, "35"),
+            word(3, 300, 30, "U/L"),
+            word(3, 400, 40, "<40"),
+            word(4, 10, 60, "尿蛋白"),
+            word(4, 200, 40, "阳性"),
+            word(4, 400, 40, "阴性"),
+        ]
+        .join("\n");
+
+        let rows = parse_tsv(&tsv);
+        assert_eq!(rows[1], ["1 血红蛋白", "112 ↓", "g/L", "130-175"]);
+
+        let items = table_items(&rows);
+        assert_eq!(items.len(), 3);
+        let hb = &items[0];
+        assert_eq!((hb.name.as_str(), hb.value.as_str(), hb.unit.as_str()), ("血红蛋白", "112", "g/L"));
+        assert_eq!(hb.reference_range, "130-175");
+        assert!(hb.is_abnormal);
+        assert_eq!((items[1].name.as_str(), items[1].is_abnormal), ("ALT", false));
+        assert_eq!(items[1].reference_range, "<40");
+        assert!(items[2].is_abnormal);
+    }
+}
@@ -0,0 +1,113 @@
+use super::{OcrEngine, Recognition};
+use crate::error::{AppError, AppResult};
+use crate::services::http_client::RetryAttempt;
+use crate::services::ocr::{items_schema, parse_items};
+use crate::services::provider::{ChatClient, ChatMessage, ChatRequest};
+use futures_util::future::BoxFuture;
+
+const MAX_TOKENS: u32 = 4096;
+
+/// 将图片与 Prompt 发送给视觉模型，要求按 Schema 输出指标列表并严格校验
+///
+/// 输出未通过校验时，把输出与错误发回模型请求修正一次；仍不通过时返回带 `parse_error` 的结果。
+pub struct VisionEngine<'a> {
+    client: ChatClient<'a>,
+    prompt: &'a str,
+}
+
+impl<'a> VisionEngine<'a> {
+    pub fn new(client: ChatClient<'a>, prompt: &'a str) -> Self {
+        Self { client, prompt }
+    }
+
+    /// 部分 OpenAI 兼容接口不支持 response_format 并返回 400，此时去掉 Schema 重发一次；
+    /// 其他原因的 400（如图片过大、模型不存在）直接返回
+    async fn complete(&self, request: ChatRequest, on_retry: &(dyn Fn(&RetryAttempt) + Sync)) -> AppResult<String> {
+        match self.client.complete(&request, on_retry).await {
+            Err(AppError::AiProvider { status: Some(400), message })
+                if request.schema.is_some() && rejects_schema(&message) =>
+            {
+                log::warn!("结构化输出请求被拒绝，改为仅按 Prompt 输出: {}", message);
+                self.client.complete(&ChatRequest { schema: None, ..request }, on_retry).await
+            }
+            result => result,
+        }
+    }
+}
+
+/// 错误信息是否指向结构化输出参数
+fn rejects_schema(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("response_format") || message.contains("json_schema")
+}
+
+/// 请模型修正未通过校验的输出
+fn repair_prompt(raw: &str, error: &str) -> String {
+    format!(
+        "以下是从检查报告中识别出的指标，但输出未通过格式校验：{}。\n\
+         请修正格式后重新输出，不要增删或改动指标内容。只返回 JSON 对象，其中 items 为数组，每个元素包含 \
+         name、value、unit、reference_range（均为字符串，没有时填空字符串）与 is_abnormal（布尔值）。\n\n{}",
+        error, raw
+    )
+}
+
+impl OcrEngine for VisionEngine<'_> {
+    fn recognize<'a>(
+        &'a self,
+        image: &'a [u8],
+        mime_type: &'a str,
+        on_retry: &'a (dyn Fn(&RetryAttempt) + Sync),
+    ) -> BoxFuture<'a, Result<Recognition, String>> {
+        Box::pin(async move {
+            let request = ChatRequest {
+                system: None,
+                messages: vec![ChatMessage::user(self.prompt).with_image(mime_type, image)],
+                max_tokens: MAX_TOKENS,
+                schema: Some(items_schema()),
+            };
+            let raw = self.complete(request, on_retry).await.map_err(|e| e.to_string())?;
+            let error = match parse_items(&raw) {
+                Ok(items) => return Ok(Recognition { raw, items, parse_error: None }),
+                Err(error) => error,
+            };
+
+            log::warn!("OCR 输出未通过校验，请求模型修正: {}", error);
+            let repair = ChatRequest {
+                system: None,
+                messages: vec![ChatMessage::user(repair_prompt(&raw, &error))],
+                max_tokens: MAX_TOKENS,
+                schema: Some(items_schema()),
+            };
+            let repaired = match self.complete(repair, on_retry).await {
+                Ok(repaired) => repaired,
+                Err(e) => {
+                    let parse_error = format!("{}（自动修正失败 - {}）", error, e);
+                    return Ok(Recognition { raw, items: Vec::new(), parse_error: Some(parse_error) });
+                }
+            };
+
+            // 保留两次输出，便于核对修正前后的差异
+            let combined = format!("{}\n\n--- 自动修正 ---\n{}", raw, repaired);
+            Ok(match parse_items(&repaired) {
+                Ok(items) => Recognition { raw: combined, items, parse_error: None },
+                Err(e) => Recognition {
+                    raw: combined,
+                    items: Vec::new(),
+                    parse_error: Some(format!("{}（自动修正后仍无效 - {}）", error, e)),
+                },
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_schema_errors_fall_back() {
+        assert!(rejects_schema(r#"AI API 错误 (400 Bad Request): {"error":{"message":"Unknown parameter: 'response_format'"}}"#));
+        assert!(rejects_schema("AI API 错误 (400 Bad Request): JSON_SCHEMA is not supported"));
+        assert!(!rejects_schema(r#"AI API 错误 (400 Bad Request): {"error":{"message":"image too large"}}"#));
+    }
+}
@@ -0,0 +1,151 @@
+use crate::error::AppResult;
+use crate::repo::ConfigRepo;
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 未配置 `pdftoppm_path` 时优先使用与主程序同目录的 pdftoppm，
+/// 没有时使用 PATH 中的 pdftoppm（poppler-utils）
+pub const DEFAULT_PROGRAM: &str = "pdftoppm";
+/// 渲染分辨率，兼顾识别效果与上传的图片大小
+const DPI: u32 = 150;
+/// 最多识别的页数，超过时整个文件识别失败，避免误传的大文件产生大量请求或只识别一部分
+pub const MAX_PAGES: usize = 30;
+/// 渲染整个文件的时限
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+/// 将 PDF 逐页渲染为 PNG 图片，供视觉模型或本地引擎按页识别
+pub struct PdfRenderer {
+    program: String,
+}
+
+/// 渲染用的临时目录，离开作用域（包括识别被取消）时删除
+struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+impl PdfRenderer {
+    /// 读取 `pdftoppm_path` 配置
+    pub fn load(conn: &Connection) -> AppResult<Self> {
+        let program = ConfigRepo::new(conn).get_or("pdftoppm_path", "")?;
+        let program = program.trim();
+        Ok(Self {
+            program: if program.is_empty() { bundled_program() } else { program.to_string() },
+        })
+    }
+
+    /// 按页码顺序返回每页的 PNG 数据；超过 [`MAX_PAGES`] 页时返回错误
+    pub async fn render(&self, pdf: &Path) -> Result<Vec<Vec<u8>>, String> {
+        let dir = TempDir(std::env::temp_dir().join(format!("health-pdf-{}", uuid::Uuid::new_v4())));
+        std::fs::create_dir_all(&dir.0).map_err(|e| format!("创建临时目录失败 - {}", e))?;
+
+        // 多渲染一页用于判断是否超出页数限制
+        let output = tokio::process::Command::new(&self.program)
+            .args(["-r", &DPI.to_string(), "-l", &(MAX_PAGES + 1).to_string(), "-png"])
+            .arg(pdf)
+            .arg(dir.0.join("page"))
+            .kill_on_drop(true)
+            .output();
+        let output = tokio::time::timeout(TIMEOUT, output)
+            .await
+            .map_err(|_| format!("PDF 渲染超时（{} 秒）", TIMEOUT.as_secs()))?
+            .map_err(|e| format!("无法启动 pdftoppm（{}），请确认已安装 poppler 并在设置中填写程序路径 - {}", self.program, e))?;
+        if !output.status.success() {
+            return Err(format!("PDF 渲染失败 - {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+
+        let mut pages = std::fs::read_dir(&dir.0)
+            .map_err(|e| format!("读取渲染结果失败 - {}", e))?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                Some((page_number(path.file_name()?.to_str()?)?, path))
+            })
+            .collect::<Vec<_>>();
+        if pages.is_empty() {
+            return Err("PDF 中没有可识别的页面".into());
+        }
+        if pages.len() > MAX_PAGES {
+            return Err(format!("PDF 超过 {} 页，请拆分后分别上传", MAX_PAGES));
+        }
+        pages.sort_by_key(|(number, _)| *number);
+
+        pages
+            .into_iter()
+            .map(|(_, path)| std::fs::read(path).map_err(|e| format!("读取渲染结果失败 - {}", e)))
+            .collect()
+    }
+}
+
+/// 与主程序同目录的 pdftoppm 路径，不存在时返回 [`DEFAULT_PROGRAM`]
+fn bundled_program() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(sidecar_path(exe.parent()?)))
+        .filter(|path| path.is_file())
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|| DEFAULT_PROGRAM.to_string())
+}
+
+/// 主程序所在目录中的 pdftoppm（Windows 下带 .exe 后缀）
+fn sidecar_path(exe_dir: &Path) -> PathBuf {
+    exe_dir.join(format!("{}{}", DEFAULT_PROGRAM, std::env::consts::EXE_SUFFIX))
+}
+
+/// pdftoppm 输出文件名中的页码，如 page-1.png、page-07.png
+pub fn page_number(filename: &str) -> Option<u32> {
+    filename.strip_suffix(".png")?.rsplit('-').next()?.parse().ok()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// 用脚本模拟 pdftoppm，按 `-l` 参数输出指定页数的空白文件（PDF 共 `total` 页）
+    #[cfg(unix)]
+    pub(crate) fn fake_renderer(dir: &Path, total: usize) -> PdfRenderer {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script = dir.join("pdftoppm");
+        std::fs::write(&script, format!(
+            "#!/bin/sh\nlast=$4\n[ $last -gt {total} ] && last={total}\nfor i in $(seq 1 $last); do touch \"$7-$i.png\"; done\n"
+        )).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        PdfRenderer { program: script.to_string_lossy().into_owned() }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn pdf_over_page_limit_is_rejected() {
+        let dir = std::env::temp_dir().join(format!("health-pdf-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pdf = dir.join("report.pdf");
+
+        let pages = fake_renderer(&dir, MAX_PAGES).render(&pdf).await.unwrap();
+        assert_eq!(pages.len(), MAX_PAGES);
+
+        let error = fake_renderer(&dir, MAX_PAGES + 5).render(&pdf).await.unwrap_err();
+        assert!(error.contains(&MAX_PAGES.to_string()), "{}", error);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn pages_are_ordered_by_number() {
+        let mut names = ["page-10.png", "page-02.png", "page-1.png", "other.txt"];
+        names.sort_by_key(|name| page_number(name));
+        assert_eq!(names, ["other.txt", "page-1.png", "page-02.png", "page-10.png"]);
+        assert_eq!(page_number("other.txt"), None);
+    }
+
+    #[test]
+    fn sidecar_is_next_to_the_executable() {
+        let dir = Path::new("/opt/app");
+        let name = sidecar_path(dir).file_name().unwrap().to_string_lossy().into_owned();
+        assert!(name.starts_with(DEFAULT_PROGRAM));
+        assert_eq!(sidecar_path(dir).parent(), Some(dir));
+    }
+}
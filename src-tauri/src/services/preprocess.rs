@@ -0,0 +1,463 @@
+use crate::error::AppResult;
+use crate::repo::ConfigRepo;
+use crate::services::pdf::PdfRenderer;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, ImageBuffer, ImageDecoder, ImageReader, Pixel};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+/// 预处理结果的缓存目录（相对数据目录）
+pub const CACHE_DIR: &str = "cache/ocr";
+/// 倾斜校正搜索的最大角度
+const MAX_SKEW_DEGREES: f32 = 5.0;
+/// 小于该角度时不旋转，避免无谓的插值模糊
+const MIN_SKEW_DEGREES: f32 = 0.3;
+/// 估计倾斜角度时先缩小到该尺寸以内
+const SKEW_SAMPLE_SIZE: u32 = 800;
+
+/// 预处理后统一编码为 JPEG
+pub const OUTPUT_MIME_TYPE: &str = "image/jpeg";
+const OUTPUT_EXTENSION: &str = "jpg";
+
+/// 识别前的图片预处理设置，保存在 `ocr_preprocess`（JSON），缺少的字段使用默认值
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreprocessSettings {
+    /// 关闭时按原文件识别
+    pub enabled: bool,
+    /// 长边的最大像素数，0 表示不缩放
+    pub max_dimension: u32,
+    pub grayscale: bool,
+    /// 校正 ±5° 以内的倾斜
+    pub deskew: bool,
+    /// JPEG 质量（1-100）
+    pub quality: u8,
+}
+
+impl Default for PreprocessSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_dimension: 2048,
+            grayscale: false,
+            deskew: false,
+            quality: 85,
+        }
+    }
+}
+
+impl PreprocessSettings {
+    /// 读取 `ocr_preprocess` 配置，无效时使用默认值
+    pub fn load(conn: &Connection) -> AppResult<Self> {
+        Ok(Self::parse(&ConfigRepo::new(conn).get_or("ocr_preprocess", "")?))
+    }
+
+    fn parse(value: &str) -> Self {
+        let settings: Self = serde_json::from_str(value).unwrap_or_default();
+        Self { quality: settings.quality.clamp(1, 100), ..settings }
+    }
+
+    /// 设置的摘要，写入缓存文件名，设置变化后重新处理
+    fn fingerprint(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        hex_prefix(json.as_bytes(), 12)
+    }
+}
+
+/// 按 EXIF 方向旋转、缩放、转灰度、校正倾斜并重新编码为 JPEG
+pub fn process(bytes: &[u8], settings: &PreprocessSettings) -> Result<Vec<u8>, String> {
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| format!("读取图片失败 - {}", e))?
+        .into_decoder()
+        .map_err(|e| format!("无法解码图片 - {}", e))?;
+    let orientation = decoder.orientation().ok();
+    let mut img = DynamicImage::from_decoder(decoder).map_err(|e| format!("无法解码图片 - {}", e))?;
+    if let Some(orientation) = orientation {
+        img.apply_orientation(orientation);
+    }
+
+    let max = settings.max_dimension;
+    if max > 0 && (img.width() > max || img.height() > max) {
+        img = img.resize(max, max, FilterType::Lanczos3);
+    }
+
+    // JPEG 不支持透明通道，统一转为 RGB 或灰度
+    img = if settings.grayscale {
+        DynamicImage::ImageLuma8(img.to_luma8())
+    } else {
+        DynamicImage::ImageRgb8(img.to_rgb8())
+    };
+
+    if settings.deskew {
+        let angle = skew_angle(&img.to_luma8());
+        if angle.abs() >= MIN_SKEW_DEGREES.to_radians() {
+            img = match img {
+                DynamicImage::ImageLuma8(buffer) => DynamicImage::ImageLuma8(rotate(&buffer, angle)),
+                other => DynamicImage::ImageRgb8(rotate(&other.to_rgb8(), angle)),
+            };
+        }
+    }
+
+    let mut output = Vec::new();
+    img.write_with_encoder(JpegEncoder::new_with_quality(&mut output, settings.quality))
+        .map_err(|e| format!("图片编码失败 - {}", e))?;
+    Ok(output)
+}
+
+/// 估计文字行的倾斜角度（弧度，顺时针为正）
+///
+/// 在缩小后的图片上取深色像素，逐个角度计算旋转后的水平投影，文字行对齐时投影的起伏最大。
+pub fn skew_angle(gray: &GrayImage) -> f32 {
+    let sample = if gray.width() > SKEW_SAMPLE_SIZE || gray.height() > SKEW_SAMPLE_SIZE {
+        image::imageops::resize(
+            gray,
+            SKEW_SAMPLE_SIZE.min(gray.width()),
+            SKEW_SAMPLE_SIZE.min(gray.height()),
+            FilterType::Triangle,
+        )
+    } else {
+        gray.clone()
+    };
+    let (width, height) = sample.dimensions();
+    // 不同方向缩小后比例会变化，投影时按原始比例还原坐标
+    let scale_x = gray.width() as f32 / width as f32;
+    let scale_y = gray.height() as f32 / height as f32;
+
+    let mean = sample.pixels().map(|p| p[0] as f32).sum::<f32>() / (width * height).max(1) as f32;
+    let dark: Vec<(f32, f32)> = sample
+        .enumerate_pixels()
+        .filter(|(_, _, p)| (p[0] as f32) < mean * 0.75)
+        .map(|(x, y, _)| (x as f32 * scale_x, y as f32 * scale_y))
+        .collect();
+    if dark.is_empty() {
+        return 0.0;
+    }
+
+    let bins = gray.height() as usize + gray.width() as usize;
+    let mut best = (0.0, f64::MIN);
+    let steps = (MAX_SKEW_DEGREES * 10.0) as i32;
+    for step in -steps..=steps {
+        let angle = (step as f32 / 10.0).to_radians();
+        let (sin, cos) = angle.sin_cos();
+        let mut profile = vec![0u32; bins];
+        for &(x, y) in &dark {
+            let row = y * cos - x * sin + gray.width() as f32;
+            if let Some(count) = profile.get_mut(row.max(0.0) as usize) {
+                *count += 1;
+            }
+        }
+        let score = profile.windows(2).map(|w| (w[1] as f64 - w[0] as f64).powi(2)).sum::<f64>();
+        if score > best.1 {
+            best = (angle, score);
+        }
+    }
+    best.0
+}
+
+/// 绕中心旋转 `-angle`（双线性插值），超出原图的部分填充白色
+fn rotate<P: Pixel<Subpixel = u8>>(img: &ImageBuffer<P, Vec<u8>>, angle: f32) -> ImageBuffer<P, Vec<u8>> {
+    let (width, height) = img.dimensions();
+    let (sin, cos) = angle.sin_cos();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let channels = P::CHANNEL_COUNT as usize;
+    let white = *P::from_slice(&[255u8; 4][..channels]);
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+        let sx = cx + dx * cos - dy * sin;
+        let sy = cy + dx * sin + dy * cos;
+        let (x0, y0) = (sx.floor(), sy.floor());
+        if x0 < 0.0 || y0 < 0.0 || x0 + 1.0 >= width as f32 || y0 + 1.0 >= height as f32 {
+            return white;
+        }
+        let (fx, fy) = (sx - x0, sy - y0);
+        let (x0, y0) = (x0 as u32, y0 as u32);
+        let corners = [
+            (img.get_pixel(x0, y0), (1.0 - fx) * (1.0 - fy)),
+            (img.get_pixel(x0 + 1, y0), fx * (1.0 - fy)),
+            (img.get_pixel(x0, y0 + 1), (1.0 - fx) * fy),
+            (img.get_pixel(x0 + 1, y0 + 1), fx * fy),
+        ];
+        let mut pixel = white;
+        for (c, value) in pixel.channels_mut().iter_mut().enumerate() {
+            let sum: f32 = corners.iter().map(|(p, weight)| p.channels()[c] as f32 * weight).sum();
+            *value = sum.round().clamp(0.0, 255.0) as u8;
+        }
+        pixel
+    })
+}
+
+/// 识别前使用的图片
+pub struct PreparedImage {
+    pub data: Vec<u8>,
+    pub mime_type: String,
+}
+
+/// 按设置预处理待识别的图片，结果缓存在数据目录的 cache/ocr 下，重新识别时直接使用
+///
+/// 缓存文件按存储路径、源文件大小与修改时间以及设置摘要命名，源文件被替换（恢复、迁移数据目录）
+/// 后不会读到旧结果。PDF 每页一个文件，全部写入后再写页数文件，避免中断后读到不完整的结果。
+pub struct Preprocessor {
+    settings: PreprocessSettings,
+    cache_dir: PathBuf,
+}
+
+impl Preprocessor {
+    pub fn new(settings: PreprocessSettings, app_dir: &Path) -> Self {
+        Self { settings, cache_dir: app_dir.join(CACHE_DIR) }
+    }
+
+    /// 预处理图片文件；关闭预处理或图片无法解码时使用原文件
+    pub async fn image(&self, path: &Path, stored_path: &str, mime_type: &str) -> Result<PreparedImage, String> {
+        let original = |data| PreparedImage { data, mime_type: mime_type.to_string() };
+        if !self.settings.enabled {
+            return std::fs::read(path).map(original).map_err(|e| format!("读取文件失败 - {}", e));
+        }
+
+        let key = self.cache_key(path, stored_path);
+        if let Some(key) = &key
+            && let Ok(data) = std::fs::read(self.cache_path(key, None))
+        {
+            return Ok(self.prepared(data));
+        }
+
+        let bytes = std::fs::read(path).map_err(|e| format!("读取文件失败 - {}", e))?;
+        let settings = self.settings;
+        let (bytes, processed) = tokio::task::spawn_blocking(move || {
+            let processed = process(&bytes, &settings);
+            (bytes, processed)
+        })
+        .await
+        .map_err(|e| format!("图片预处理失败 - {}", e))?;
+
+        match processed {
+            Ok(data) => {
+                if let Some(key) = &key {
+                    self.write_cache(&self.cache_path(key, None), &data);
+                }
+                Ok(self.prepared(data))
+            }
+            Err(e) => {
+                log::warn!("图片预处理失败，使用原文件识别 {}: {}", path.display(), e);
+                Ok(original(bytes))
+            }
+        }
+    }
+
+    /// 渲染并预处理 PDF 的每一页；关闭预处理时每次重新渲染，某页预处理失败时该页使用渲染结果
+    pub async fn pdf_pages(&self, pdf: &PdfRenderer, path: &Path, stored_path: &str) -> Result<Vec<PreparedImage>, String> {
+        if !self.settings.enabled {
+            let pages = pdf.render(path).await?;
+            return Ok(pages.into_iter().map(rendered).collect());
+        }
+
+        let key = self.cache_key(path, stored_path);
+        if let Some(pages) = key.as_deref().and_then(|key| self.cached_pages(key)) {
+            return Ok(pages);
+        }
+
+        let pages = pdf.render(path).await?;
+        let settings = self.settings;
+        let pages = tokio::task::spawn_blocking(move || {
+            pages
+                .into_iter()
+                .map(|page| {
+                    let processed = process(&page, &settings);
+                    (page, processed)
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|e| format!("图片预处理失败 - {}", e))?;
+
+        // 有页面使用渲染结果时不写页数文件，下次识别重新处理
+        let mut complete = key.is_some();
+        let mut prepared = Vec::with_capacity(pages.len());
+        for (i, (page, processed)) in pages.into_iter().enumerate() {
+            match processed {
+                Ok(data) => {
+                    if let Some(key) = &key {
+                        self.write_cache(&self.cache_path(key, Some(i + 1)), &data);
+                    }
+                    prepared.push(self.prepared(data));
+                }
+                Err(e) => {
+                    log::warn!("PDF 第 {} 页预处理失败，使用渲染结果识别 {}: {}", i + 1, path.display(), e);
+                    complete = false;
+                    prepared.push(rendered(page));
+                }
+            }
+        }
+        if let Some(key) = &key
+            && complete
+        {
+            self.write_cache(&self.page_count_path(key), prepared.len().to_string().as_bytes());
+        }
+        Ok(prepared)
+    }
+
+    fn cached_pages(&self, key: &str) -> Option<Vec<PreparedImage>> {
+        let count: usize = std::fs::read_to_string(self.page_count_path(key)).ok()?.trim().parse().ok()?;
+        (1..=count)
+            .map(|page| std::fs::read(self.cache_path(key, Some(page))).ok().map(|data| self.prepared(data)))
+            .collect()
+    }
+
+    /// 缓存文件名（不含页码与扩展名）；读取不到源文件信息时不使用缓存
+    fn cache_key(&self, path: &Path, stored_path: &str) -> Option<String> {
+        let metadata = std::fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(std::time::UNIX_EPOCH).ok()?;
+        let source = format!("{}:{}", metadata.len(), modified.as_nanos());
+        Some(format!(
+            "{}-{}-{}",
+            path_key(stored_path),
+            hex_prefix(source.as_bytes(), 8),
+            self.settings.fingerprint()
+        ))
+    }
+
+    fn prepared(&self, data: Vec<u8>) -> PreparedImage {
+        PreparedImage { data, mime_type: OUTPUT_MIME_TYPE.into() }
+    }
+
+    fn cache_path(&self, key: &str, page: Option<usize>) -> PathBuf {
+        let page = page.map(|n| format!("-p{}", n)).unwrap_or_default();
+        self.cache_dir.join(format!("{}{}.{}", key, page, OUTPUT_EXTENSION))
+    }
+
+    fn page_count_path(&self, key: &str) -> PathBuf {
+        self.cache_dir.join(format!("{}.pages", key))
+    }
+
+    /// 先写临时文件再改名；缓存写入失败不影响识别
+    fn write_cache(&self, path: &Path, data: &[u8]) {
+        let temp = path.with_extension("tmp");
+        let written = std::fs::create_dir_all(&self.cache_dir)
+            .and_then(|_| std::fs::write(&temp, data))
+            .and_then(|_| std::fs::rename(&temp, path));
+        if let Err(e) = written {
+            log::warn!("写入预处理缓存失败 {}: {}", path.display(), e);
+            std::fs::remove_file(&temp).ok();
+        }
+    }
+}
+
+/// pdftoppm 渲染出的 PNG 页面
+fn rendered(data: Vec<u8>) -> PreparedImage {
+    PreparedImage { data, mime_type: "image/png".into() }
+}
+
+/// 删除文件在各种设置下的全部预处理缓存（文件被彻底删除时调用）
+pub fn remove_cache(app_dir: &Path, stored_path: &str) {
+    let prefix = format!("{}-", path_key(stored_path));
+    let Ok(entries) = std::fs::read_dir(app_dir.join(CACHE_DIR)) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with(&prefix)
+            && let Err(e) = std::fs::remove_file(entry.path())
+        {
+            log::warn!("删除预处理缓存失败 {}: {}", entry.path().display(), e);
+        }
+    }
+}
+
+/// 存储路径的摘要，作为缓存文件名前缀
+fn path_key(stored_path: &str) -> String {
+    hex_prefix(stored_path.as_bytes(), 16)
+}
+
+fn hex_prefix(data: &[u8], len: usize) -> String {
+    let digest = Sha256::digest(data);
+    digest.iter().map(|b| format!("{:02x}", b)).collect::<String>()[..len].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_are_downscaled_and_deskewed_before_ocr() {
+        use image::{GenericImageView, ImageFormat, Rgb, RgbImage};
+
+        // 白底上每隔 40 像素一条向右下倾斜 2° 的黑线，模拟拍歪的报告
+        let tan = 2f32.to_radians().tan();
+        let page = RgbImage::from_fn(1200, 600, |x, y| {
+            let offset = (y as f32 - x as f32 * tan).rem_euclid(40.0);
+            if offset < 4.0 { Rgb([0, 0, 0]) } else { Rgb([255, 255, 255]) }
+        });
+        let angle = skew_angle(&image::DynamicImage::ImageRgb8(page.clone()).to_luma8()).to_degrees();
+        assert!((angle - 2.0).abs() < 0.3, "angle = {}", angle);
+
+        let mut png = Vec::new();
+        page.write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png).unwrap();
+
+        let settings = PreprocessSettings { max_dimension: 600, deskew: true, ..Default::default() };
+        let output = process(&png, &settings).unwrap();
+        assert_eq!(image::guess_format(&output).unwrap(), ImageFormat::Jpeg);
+        let resized = image::load_from_memory(&output).unwrap();
+        assert_eq!(resized.dimensions(), (600, 300));
+        // 校正后的文字行接近水平
+        assert!(skew_angle(&resized.to_luma8()).to_degrees().abs() < 0.5);
+
+        let settings = PreprocessSettings { grayscale: true, max_dimension: 0, ..Default::default() };
+        let output = process(&png, &settings).unwrap();
+        let gray = image::load_from_memory(&output).unwrap();
+        assert_eq!(gray.dimensions(), (1200, 600));
+        assert_eq!(gray.color(), image::ColorType::L8);
+
+        assert!(process(b"not an image", &settings).is_err());
+    }
+
+    #[tokio::test]
+    async fn replaced_source_files_are_processed_again() {
+        use image::{GenericImageView, ImageFormat, RgbImage};
+
+        let dir = std::env::temp_dir().join(format!("health-preprocess-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("report.png");
+        let write_png = |width, height| {
+            let mut png = Vec::new();
+            RgbImage::new(width, height).write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png).unwrap();
+            std::fs::write(&path, png).unwrap();
+        };
+        let preprocessor = Preprocessor::new(PreprocessSettings { max_dimension: 0, ..Default::default() }, &dir);
+        let dimensions = |image: PreparedImage| image::load_from_memory(&image.data).unwrap().dimensions();
+
+        write_png(40, 20);
+        let first = preprocessor.image(&path, "pictures/a.png", "image/png").await.unwrap();
+        assert_eq!(dimensions(first), (40, 20));
+
+        // 同一存储路径的文件被替换（例如从回收站恢复了另一份数据）时不使用旧缓存
+        write_png(30, 60);
+        let second = preprocessor.image(&path, "pictures/a.png", "image/png").await.unwrap();
+        assert_eq!(dimensions(second), (30, 60));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn pdf_pages_fall_back_to_rendered_pages() {
+        let dir = std::env::temp_dir().join(format!("health-preprocess-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let renderer = crate::services::pdf::tests::fake_renderer(&dir, 2);
+        let preprocessor = Preprocessor::new(PreprocessSettings::default(), &dir);
+        let path = dir.join("report.pdf");
+        std::fs::write(&path, b"%PDF").unwrap();
+
+        // 渲染结果无法解码时与图片一样使用原始数据，且不写入缓存
+        let pages = preprocessor.pdf_pages(&renderer, &path, "pictures/a.pdf").await.unwrap();
+        assert_eq!(pages.len(), 2);
+        assert!(pages.iter().all(|page| page.mime_type == "image/png"));
+        let key = preprocessor.cache_key(&path, "pictures/a.pdf").unwrap();
+        assert!(preprocessor.cached_pages(&key).is_none());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
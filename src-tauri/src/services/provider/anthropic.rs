@@ -0,0 +1,90 @@
+use super::{error_message, sse_data, ChatPart, ChatProvider, ChatRequest};
+use crate::services::http_client::AiClientConfig;
+use reqwest::{Client, RequestBuilder};
+use serde_json::{json, Value};
+
+const API_VERSION: &str = "2023-06-01";
+
+/// Anthropic Messages 接口，`api_url` 为完整的 /v1/messages 地址
+pub struct Anthropic;
+
+impl ChatProvider for Anthropic {
+    fn request(&self, client: &Client, config: &AiClientConfig, _stream: bool) -> RequestBuilder {
+        client
+            .post(&config.api_url)
+            .header("x-api-key", &config.api_key)
+            .header("anthropic-version", API_VERSION)
+    }
+
+    fn body(&self, config: &AiClientConfig, request: &ChatRequest, stream: bool) -> Value {
+        let messages: Vec<Value> = request
+            .messages
+            .iter()
+            .map(|message| {
+                let content: Vec<Value> = message
+                    .parts
+                    .iter()
+                    .map(|part| match part {
+                        ChatPart::Text(text) => json!({ "type": "text", "text": text }),
+                        ChatPart::Image { mime_type, data } => json!({
+                            "type": "image",
+                            "source": { "type": "base64", "media_type": mime_type, "data": data }
+                        }),
+                    })
+                    .collect();
+                json!({ "role": "user", "content": content })
+            })
+            .collect();
+
+        let mut body = json!({
+            "model": config.model,
+            "messages": messages,
+            "max_tokens": request.max_tokens,
+            "stream": stream,
+        });
+        if let Some(system) = &request.system {
+            body["system"] = json!(system);
+        }
+        // 没有 response_format，通过强制调用一个工具获得符合 Schema 的输入参数
+        if let Some(schema) = &request.schema {
+            body["tools"] = json!([{
+                "name": schema.name,
+                "description": "按要求的结构返回结果",
+                "input_schema": schema.schema,
+            }]);
+            body["tool_choice"] = json!({ "type": "tool", "name": schema.name });
+        }
+        body
+    }
+
+    fn parse_response(&self, body: &Value) -> Result<String, String> {
+        if let Some(error) = error_message(body) {
+            return Err(error);
+        }
+        let blocks = body["content"].as_array().ok_or_else(|| "响应中没有模型输出".to_string())?;
+        // 结构化输出时结果为工具调用的参数
+        if let Some(block) = blocks.iter().find(|block| block["type"] == "tool_use") {
+            return Ok(block["input"].to_string());
+        }
+        // 只取文本块，忽略 thinking 等其他类型
+        Ok(blocks
+            .iter()
+            .filter(|block| block["type"] == "text")
+            .filter_map(|block| block["text"].as_str())
+            .collect())
+    }
+
+    fn parse_stream_line(&self, line: &str) -> Result<Option<String>, String> {
+        // event: 行只是事件名，data 中的 type 与之相同
+        let Some(data) = sse_data(line) else {
+            return Ok(None);
+        };
+        match data["type"].as_str() {
+            Some("error") => Err(error_message(&data).unwrap_or_else(|| data.to_string())),
+            Some("content_block_delta") if data["delta"]["type"] == "text_delta" => {
+                Ok(data["delta"]["text"].as_str().map(str::to_string))
+            }
+            _ => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use super::{error_message, sse_data, ChatPart, ChatProvider, ChatRequest};
+use crate::services::http_client::AiClientConfig;
+use reqwest::{Client, RequestBuilder};
+use serde_json::{json, Map, Value};
+
+/// Google Gemini 接口，`api_url` 为 API 根地址（如 https://generativelanguage.googleapis.com/v1beta），
+/// 模型名称拼接在地址中
+pub struct Gemini;
+
+/// 放在请求体顶层的参数，其余均为生成参数放入 generationConfig
+const TOP_LEVEL_PARAMETERS: &[&str] = &["safetySettings", "cachedContent", "toolConfig"];
+
+/// 常用的 OpenAI 风格参数名对应的 generationConfig 字段，其余保持原名
+fn generation_key(key: &str) -> &str {
+    match key {
+        "max_tokens" => "maxOutputTokens",
+        "top_p" => "topP",
+        "top_k" => "topK",
+        "presence_penalty" => "presencePenalty",
+        "frequency_penalty" => "frequencyPenalty",
+        other => other,
+    }
+}
+
+/// 第一个候选回复中的全部文本
+fn candidate_text(body: &Value) -> Option<String> {
+    let parts = body["candidates"][0]["content"]["parts"].as_array()?;
+    Some(parts.iter().filter_map(|part| part["text"].as_str()).collect())
+}
+
+impl ChatProvider for Gemini {
+    fn request(&self, client: &Client, config: &AiClientConfig, stream: bool) -> RequestBuilder {
+        let method = if stream { "streamGenerateContent?alt=sse" } else { "generateContent" };
+        let url = format!("{}/models/{}:{}", config.api_url.trim_end_matches('/'), config.model, method);
+        client.post(url).header("x-goog-api-key", &config.api_key)
+    }
+
+    fn body(&self, _config: &AiClientConfig, request: &ChatRequest, _stream: bool) -> Value {
+        let contents: Vec<Value> = request
+            .messages
+            .iter()
+            .map(|message| {
+                let parts: Vec<Value> = message
+                    .parts
+                    .iter()
+                    .map(|part| match part {
+                        ChatPart::Text(text) => json!({ "text": text }),
+                        ChatPart::Image { mime_type, data } => json!({
+                            "inline_data": { "mime_type": mime_type, "data": data }
+                        }),
+                    })
+                    .collect();
+                json!({ "role": "user", "parts": parts })
+            })
+            .collect();
+
+        let mut body = json!({
+            "contents": contents,
+            "generationConfig": { "maxOutputTokens": request.max_tokens },
+        });
+        if let Some(system) = &request.system {
+            body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+        }
+        if let Some(schema) = &request.schema {
+            body["generationConfig"]["responseMimeType"] = json!("application/json");
+            body["generationConfig"]["responseJsonSchema"] = schema.schema.clone();
+        }
+        body
+    }
+
+    fn merge_parameters(&self, body: &mut Value, parameters: &Map<String, Value>) {
+        for (key, value) in parameters {
+            if TOP_LEVEL_PARAMETERS.contains(&key.as_str()) {
+                body[key.as_str()] = value.clone();
+            } else {
+                body["generationConfig"][generation_key(key)] = value.clone();
+            }
+        }
+    }
+
+    fn parse_response(&self, body: &Value) -> Result<String, String> {
+        if let Some(error) = error_message(body) {
+            return Err(error);
+        }
+        if let Some(reason) = body["promptFeedback"]["blockReason"].as_str() {
+            return Err(format!("请求被拦截: {}", reason));
+        }
+        candidate_text(body).ok_or_else(|| "响应中没有模型输出".to_string())
+    }
+
+    fn parse_stream_line(&self, line: &str) -> Result<Option<String>, String> {
+        let Some(data) = sse_data(line) else {
+            return Ok(None);
+        };
+        if let Some(error) = error_message(&data) {
+            return Err(error);
+        }
+        Ok(candidate_text(&data))
+    }
+}
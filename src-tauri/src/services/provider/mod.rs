@@ -0,0 +1,438 @@
+//! AI 服务接口适配
+//!
+//! 各家接口的请求格式与响应解析由 [`ChatProvider`] 实现，发送、重试、超时与流式读取由
+//! [`ChatClient`] 统一处理，OCR 与分析只需构建与接口无关的 [`ChatRequest`]。
+
+mod anthropic;
+mod gemini;
+mod ollama;
+mod openai;
+
+use crate::error::{AppError, AppResult};
+use crate::repo::ai_profile::{ProviderKind, RESERVED_PARAMETERS};
+use crate::services::http_client::{self, AiClientConfig, RetryAttempt, Timeouts};
+use reqwest::{Client, RequestBuilder, Response};
+use serde_json::{Map, Value};
+use tokio_util::sync::CancellationToken;
+
+/// 消息中的一段内容
+#[derive(Debug, Clone)]
+pub enum ChatPart {
+    Text(String),
+    /// Base64 编码的图片
+    Image { mime_type: String, data: String },
+}
+
+/// 用户消息，系统提示词见 [`ChatRequest::system`]
+#[derive(Debug, Clone)]
+pub struct ChatMessage {
+    pub parts: Vec<ChatPart>,
+}
+
+impl ChatMessage {
+    pub fn user(text: impl Into<String>) -> Self {
+        Self { parts: vec![ChatPart::Text(text.into())] }
+    }
+
+    /// 附加一张图片
+    pub fn with_image(mut self, mime_type: &str, bytes: &[u8]) -> Self {
+        self.parts.push(ChatPart::Image {
+            mime_type: mime_type.to_string(),
+            data: base64::Engine::encode(&base64::engine::general_purpose::STANDARD, bytes),
+        });
+        self
+    }
+
+    /// 全部文本内容
+    pub fn text(&self) -> String {
+        self.parts
+            .iter()
+            .filter_map(|part| match part {
+                ChatPart::Text(text) => Some(text.as_str()),
+                ChatPart::Image { .. } => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn has_images(&self) -> bool {
+        self.parts.iter().any(|part| matches!(part, ChatPart::Image { .. }))
+    }
+}
+
+/// 要求模型按 JSON Schema 输出，各接口使用各自的结构化输出方式
+#[derive(Debug, Clone)]
+pub struct OutputSchema {
+    /// 结构名称，OpenAI 的 json_schema.name 与 Anthropic 的工具名
+    pub name: String,
+    pub schema: serde_json::Value,
+}
+
+/// 一次对话请求，与具体接口无关
+#[derive(Debug, Clone)]
+pub struct ChatRequest {
+    pub system: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    pub max_tokens: u32,
+    /// 为空时输出自由文本
+    pub schema: Option<OutputSchema>,
+}
+
+/// 一种 AI 接口的请求格式与响应解析
+pub trait ChatProvider: Send + Sync {
+    /// 请求地址与认证头
+    fn request(&self, client: &Client, config: &AiClientConfig, stream: bool) -> RequestBuilder;
+
+    /// 请求体，配置中的额外参数随后由 [`ChatProvider::merge_parameters`] 合并
+    fn body(&self, config: &AiClientConfig, request: &ChatRequest, stream: bool) -> serde_json::Value;
+
+    /// 将配置中的额外参数合并到请求体，默认合并到顶层并覆盖同名的默认参数
+    fn merge_parameters(&self, body: &mut Value, parameters: &Map<String, Value>) {
+        if let Some(object) = body.as_object_mut() {
+            for (key, value) in parameters {
+                object.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    /// 从完整响应中取出模型输出的文本
+    fn parse_response(&self, body: &serde_json::Value) -> Result<String, String>;
+
+    /// 解析流式响应的一行，返回其中新增的文本；服务端在流中报告错误时返回 Err
+    fn parse_stream_line(&self, line: &str) -> Result<Option<String>, String>;
+}
+
+/// 接口协议对应的实现
+pub fn provider(kind: ProviderKind) -> &'static dyn ChatProvider {
+    match kind {
+        ProviderKind::Openai => &openai::OpenAi,
+        ProviderKind::Anthropic => &anthropic::Anthropic,
+        ProviderKind::Gemini => &gemini::Gemini,
+        ProviderKind::Ollama => &ollama::Ollama,
+    }
+}
+
+/// SSE 中 `data:` 行的 JSON 内容
+fn sse_data(line: &str) -> Option<serde_json::Value> {
+    let data = line.strip_prefix("data:")?.trim();
+    if data == "[DONE]" {
+        return None;
+    }
+    serde_json::from_str(data).ok()
+}
+
+/// 响应中 `error.message` 或字符串形式的 `error`
+fn error_message(body: &serde_json::Value) -> Option<String> {
+    let error = body.get("error")?;
+    error["message"].as_str().or(error.as_str()).map(str::to_string)
+}
+
+fn ai_error(message: String, status: Option<u16>) -> AppError {
+    AppError::AiProvider { message, status }
+}
+
+fn status_error(status: reqwest::StatusCode, body: &str) -> AppError {
+    ai_error(format!("AI API 错误 ({}): {}", status, body), Some(status.as_u16()))
+}
+
+/// 按配置的接口协议发送对话请求
+pub struct ChatClient<'a> {
+    config: &'a AiClientConfig,
+    provider: &'static dyn ChatProvider,
+    client: Client,
+    timeouts: Timeouts,
+}
+
+impl<'a> ChatClient<'a> {
+    pub fn new(config: &'a AiClientConfig, timeouts: Timeouts) -> AppResult<Self> {
+        Ok(Self {
+            client: http_client::build_client(config, &timeouts)?,
+            provider: provider(config.provider),
+            config,
+            timeouts,
+        })
+    }
+
+    /// 请求体：配置中的额外参数按接口各自的位置合并；保留字段即使存在于旧配置中也不合并
+    pub fn body(&self, request: &ChatRequest, stream: bool) -> serde_json::Value {
+        let mut body = self.provider.body(self.config, request, stream);
+        let parameters: Map<String, Value> = self
+            .config
+            .parameters
+            .iter()
+            .filter(|(key, _)| !RESERVED_PARAMETERS.contains(&key.as_str()))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        self.provider.merge_parameters(&mut body, &parameters);
+        body
+    }
+
+    /// 发送请求并按策略重试，返回状态码为成功的响应
+    pub async fn send(
+        &self,
+        request: &ChatRequest,
+        stream: bool,
+        on_retry: impl Fn(&RetryAttempt),
+    ) -> AppResult<Response> {
+        let body = self.body(request, stream);
+        let build = || self.provider.request(&self.client, self.config, stream).json(&body);
+        let response = self
+            .config
+            .retry
+            .send(&self.timeouts, build, on_retry)
+            .await
+            .map_err(|e| ai_error(format!("AI 请求失败: {}", e), None))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(status_error(status, &response.text().await.unwrap_or_default()));
+        }
+        Ok(response)
+    }
+
+    /// 请求完整回复，返回模型输出的文本；读取响应体时连接中断也会重试
+    pub async fn complete(&self, request: &ChatRequest, on_retry: impl Fn(&RetryAttempt)) -> AppResult<String> {
+        let body = self.body(request, false);
+        let build = || self.provider.request(&self.client, self.config, false).json(&body);
+        let (status, body) = self
+            .config
+            .retry
+            .fetch(&self.timeouts, build, on_retry)
+            .await
+            .map_err(|e| ai_error(format!("AI 请求失败: {}", e), None))?;
+        if !status.is_success() {
+            return Err(status_error(status, &String::from_utf8_lossy(&body)));
+        }
+
+        let body: serde_json::Value =
+            serde_json::from_slice(&body).map_err(|e| ai_error(format!("解析响应失败: {}", e), None))?;
+        self.provider.parse_response(&body).map_err(|e| ai_error(e, None))
+    }
+
+    /// 流式请求，每收到一段文本调用 `on_delta`，返回收到的全部文本
+    ///
+    /// 只重试建立连接阶段，开始接收内容后不再重试，避免重复输出；接收中连接中断或超时返回错误，
+    /// 不把不完整的内容当作完成。`cancel` 触发后停止接收并返回已收到的部分。
+    pub async fn stream(
+        &self,
+        request: &ChatRequest,
+        on_retry: impl Fn(&RetryAttempt),
+        mut on_delta: impl FnMut(&str),
+        cancel: &CancellationToken,
+    ) -> AppResult<String> {
+        let response = tokio::select! {
+            response = self.send(request, true, on_retry) => response?,
+            _ = cancel.cancelled() => return Ok(String::new()),
+        };
+
+        let mut full_content = String::new();
+        let mut stream = response.bytes_stream();
+        let mut buffer = String::new();
+
+        loop {
+            // 取消时丢弃连接，停止接收后续内容
+            let next = tokio::select! {
+                next = http_client::next_chunk(&mut stream, self.timeouts.idle()) => next,
+                _ = cancel.cancelled() => break,
+            };
+            let Some(chunk_result) = next else {
+                break;
+            };
+            let chunk = chunk_result.map_err(|e| ai_error(format!("AI 输出中断: {}", e), None))?;
+
+            buffer.push_str(&String::from_utf8_lossy(&chunk));
+
+            // 逐行处理，不完整的行留到下一块数据
+            while let Some(line_end) = buffer.find('\n') {
+                let line = buffer[..line_end].trim().to_string();
+                buffer = buffer[line_end + 1..].to_string();
+                if line.is_empty() {
+                    continue;
+                }
+
+                match self.provider.parse_stream_line(&line) {
+                    Ok(Some(content)) if !content.is_empty() => {
+                        full_content.push_str(&content);
+                        on_delta(&content);
+                    }
+                    Ok(_) => {}
+                    Err(e) => return Err(ai_error(format!("AI 输出中断: {}", e), None)),
+                }
+            }
+        }
+
+        // 最后一行可能没有换行符
+        if !cancel.is_cancelled()
+            && let Ok(Some(content)) = self.provider.parse_stream_line(buffer.trim())
+        {
+            full_content.push_str(&content);
+            on_delta(&content);
+        }
+
+        Ok(full_content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::ai_profile::{AiTask, CreateAiProfileInput};
+    use crate::repo::AiProfileRepo;
+    use crate::services::http_client::load_ai_config;
+    use serde_json::json;
+
+    /// 使用指定接口与额外参数的配置
+    fn config(provider: ProviderKind, parameters: Value) -> AiClientConfig {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::migrations::run(&mut conn, std::path::Path::new("unused")).unwrap();
+        AiProfileRepo::new(&conn)
+            .create(CreateAiProfileInput {
+                name: "默认".into(),
+                provider: Some(provider),
+                api_url: "https://example.com".into(),
+                api_key: "key".into(),
+                proxy_enabled: None,
+                proxy_url: None,
+                proxy_username: None,
+                proxy_password: None,
+                model: "model".into(),
+                parameters: None,
+            })
+            .unwrap();
+        let mut config = load_ai_config(&conn, AiTask::Analysis).unwrap();
+        // 直接设置以覆盖保存时的校验，模拟旧版本保存的保留字段
+        config.parameters = parameters.as_object().unwrap().clone();
+        config
+    }
+
+    #[test]
+    fn parameters_are_merged_natively_per_provider() {
+        let parameters = json!({ "temperature": 0.2, "max_tokens": 16000, "top_p": 0.9, "format": "json" });
+        let request = ChatRequest { system: None, messages: vec![ChatMessage::user("你好")], max_tokens: 100, schema: None };
+        let body = |provider| {
+            let config = config(provider, parameters.clone());
+            ChatClient::new(&config, config.timeouts.analysis).unwrap().body(&request, false)
+        };
+
+        let openai = body(ProviderKind::Openai);
+        assert_eq!((openai["temperature"].clone(), openai["max_tokens"].clone()), (json!(0.2), json!(16000)));
+        assert!(openai.get("format").is_none());
+
+        let gemini = body(ProviderKind::Gemini);
+        assert_eq!(gemini["generationConfig"]["temperature"], 0.2);
+        assert_eq!(gemini["generationConfig"]["maxOutputTokens"], 16000);
+        assert_eq!(gemini["generationConfig"]["topP"], 0.9);
+        assert!(gemini.get("temperature").is_none());
+
+        let ollama = body(ProviderKind::Ollama);
+        assert_eq!(ollama["options"]["temperature"], 0.2);
+        assert_eq!(ollama["options"]["num_predict"], 16000);
+        assert!(ollama.get("format").is_none() && ollama["options"].get("format").is_none());
+
+        let config = config(ProviderKind::Gemini, json!({ "safetySettings": [] }));
+        let body = ChatClient::new(&config, config.timeouts.analysis).unwrap().body(&request, false);
+        assert_eq!(body["safetySettings"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn stream_interrupted_midway_is_an_error() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind((std::net::Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let mut config = config(ProviderKind::Openai, json!({}));
+        config.api_url = format!("http://{}", listener.local_addr().unwrap());
+        // 发送一段内容后不结束分块编码直接断开
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 4096];
+            assert!(socket.read(&mut request).await.unwrap() > 0);
+            let line = "data: {\"choices\":[{\"delta\":{\"content\":\"部分\"}}]}\n\n";
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n",
+                line.len(),
+                line
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let client = ChatClient::new(&config, config.timeouts.analysis).unwrap();
+        let request = ChatRequest { system: None, messages: vec![ChatMessage::user("你好")], max_tokens: 10, schema: None };
+        let mut received = String::new();
+        let result = client
+            .stream(&request, |_| {}, |delta| received.push_str(delta), &CancellationToken::new())
+            .await;
+        assert_eq!(received, "部分");
+        assert_eq!(result.unwrap_err().code(), "ai_provider");
+    }
+
+    #[test]
+    fn chat_providers_use_their_native_formats() {
+        let config = config(ProviderKind::Openai, json!({}));
+        let request = ChatRequest {
+            system: Some("系统".into()),
+            messages: vec![ChatMessage::user("识别").with_image("image/png", b"png")],
+            max_tokens: 100,
+            schema: None,
+        };
+
+        let openai = provider(ProviderKind::Openai);
+        let body = openai.body(&config, &request, false);
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["messages"][1]["content"][1]["image_url"]["url"], "data:image/png;base64,cG5n");
+        let text_only = ChatRequest { system: None, messages: vec![ChatMessage::user("你好")], max_tokens: 10, schema: None };
+        assert_eq!(openai.body(&config, &text_only, true)["messages"][0]["content"], "你好");
+        assert_eq!(openai.parse_response(&json!({ "choices": [{ "message": { "content": "[]" } }] })).unwrap(), "[]");
+        assert_eq!(openai.parse_stream_line(r#"data: {"choices":[{"delta":{"content":"好"}}]}"#).unwrap().as_deref(), Some("好"));
+        assert_eq!(openai.parse_stream_line("data: [DONE]").unwrap(), None);
+
+        let anthropic = provider(ProviderKind::Anthropic);
+        let body = anthropic.body(&config, &request, true);
+        assert_eq!(body["system"], "系统");
+        assert_eq!(body["messages"][0]["content"][1]["source"]["data"], "cG5n");
+        let response = json!({ "content": [{ "type": "thinking", "thinking": "..." }, { "type": "text", "text": "OK" }] });
+        assert_eq!(anthropic.parse_response(&response).unwrap(), "OK");
+        let delta = r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"好"}}"#;
+        assert_eq!(anthropic.parse_stream_line(delta).unwrap().as_deref(), Some("好"));
+        assert_eq!(anthropic.parse_stream_line("event: content_block_delta").unwrap(), None);
+        assert!(anthropic.parse_stream_line(r#"data: {"type":"error","error":{"message":"overloaded"}}"#).is_err());
+
+        let gemini = provider(ProviderKind::Gemini);
+        let body = gemini.body(&config, &request, false);
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "系统");
+        assert_eq!(body["contents"][0]["parts"][1]["inline_data"]["mime_type"], "image/png");
+        assert_eq!(body["generationConfig"]["maxOutputTokens"], 100);
+        let response = json!({ "candidates": [{ "content": { "parts": [{ "text": "O" }, { "text": "K" }] } }] });
+        assert_eq!(gemini.parse_response(&response).unwrap(), "OK");
+        assert!(gemini.parse_response(&json!({ "promptFeedback": { "blockReason": "SAFETY" } })).is_err());
+
+        let ollama = provider(ProviderKind::Ollama);
+        let body = ollama.body(&config, &request, false);
+        assert_eq!(body["stream"], false);
+        assert_eq!(body["messages"][1]["images"][0], "cG5n");
+        assert_eq!(ollama.parse_stream_line(r#"{"message":{"content":"好"},"done":false}"#).unwrap().as_deref(), Some("好"));
+        assert!(ollama.parse_stream_line(r#"{"error":"model not found"}"#).is_err());
+    }
+
+    #[test]
+    fn structured_output_uses_native_format() {
+        use crate::services::ocr::{items_schema, parse_items};
+
+        // 各接口使用各自的结构化输出方式
+        let config = config(ProviderKind::Openai, json!({}));
+        let request = ChatRequest {
+            system: None,
+            messages: vec![ChatMessage::user("识别")],
+            max_tokens: 100,
+            schema: Some(items_schema()),
+        };
+        let body = provider(ProviderKind::Openai).body(&config, &request, false);
+        assert_eq!(body["response_format"]["json_schema"]["strict"], true);
+        let anthropic = provider(ProviderKind::Anthropic);
+        assert_eq!(anthropic.body(&config, &request, false)["tool_choice"]["name"], "checkup_items");
+        let response = json!({ "content": [{ "type": "tool_use", "name": "checkup_items", "input": { "items": [] } }] });
+        assert_eq!(parse_items(&anthropic.parse_response(&response).unwrap()).unwrap().len(), 0);
+        let body = provider(ProviderKind::Gemini).body(&config, &request, false);
+        assert_eq!(body["generationConfig"]["responseMimeType"], "application/json");
+        assert_eq!(provider(ProviderKind::Ollama).body(&config, &request, false)["format"]["required"][0], "items");
+    }
+}
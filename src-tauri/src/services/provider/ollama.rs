@@ -0,0 +1,88 @@
+use super::{error_message, ChatPart, ChatProvider, ChatRequest};
+use crate::services::http_client::AiClientConfig;
+use reqwest::{Client, RequestBuilder};
+use serde_json::{json, Map, Value};
+
+/// 放在请求体顶层的参数，其余均为模型参数放入 options
+const TOP_LEVEL_PARAMETERS: &[&str] = &["keep_alive", "think"];
+
+/// Ollama 原生接口，`api_url` 为完整的 /api/chat 地址；流式响应为逐行 JSON
+pub struct Ollama;
+
+impl ChatProvider for Ollama {
+    fn request(&self, client: &Client, config: &AiClientConfig, _stream: bool) -> RequestBuilder {
+        let request = client.post(&config.api_url);
+        // 本地服务无需密钥，经反向代理访问时可能需要
+        if config.api_key.is_empty() {
+            request
+        } else {
+            request.header("Authorization", format!("Bearer {}", config.api_key))
+        }
+    }
+
+    fn body(&self, config: &AiClientConfig, request: &ChatRequest, stream: bool) -> Value {
+        let mut messages = Vec::new();
+        if let Some(system) = &request.system {
+            messages.push(json!({ "role": "system", "content": system }));
+        }
+        for message in &request.messages {
+            let images: Vec<&str> = message
+                .parts
+                .iter()
+                .filter_map(|part| match part {
+                    ChatPart::Image { data, .. } => Some(data.as_str()),
+                    ChatPart::Text(_) => None,
+                })
+                .collect();
+            let mut value = json!({ "role": "user", "content": message.text() });
+            if !images.is_empty() {
+                value["images"] = json!(images);
+            }
+            messages.push(value);
+        }
+
+        // Ollama 默认流式输出，需显式关闭
+        let mut body = json!({
+            "model": config.model,
+            "messages": messages,
+            "stream": stream,
+            "options": { "num_predict": request.max_tokens },
+        });
+        if let Some(schema) = &request.schema {
+            body["format"] = schema.schema.clone();
+        }
+        body
+    }
+
+    fn merge_parameters(&self, body: &mut Value, parameters: &Map<String, Value>) {
+        for (key, value) in parameters {
+            if TOP_LEVEL_PARAMETERS.contains(&key.as_str()) {
+                body[key.as_str()] = value.clone();
+            } else {
+                // 与其他接口一致，max_tokens 对应 num_predict
+                let key = if key == "max_tokens" { "num_predict" } else { key.as_str() };
+                body["options"][key] = value.clone();
+            }
+        }
+    }
+
+    fn parse_response(&self, body: &Value) -> Result<String, String> {
+        if let Some(error) = error_message(body) {
+            return Err(error);
+        }
+        body["message"]["content"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| "响应中没有模型输出".to_string())
+    }
+
+    fn parse_stream_line(&self, line: &str) -> Result<Option<String>, String> {
+        let Ok(data) = serde_json::from_str::<Value>(line) else {
+            return Ok(None);
+        };
+        if let Some(error) = error_message(&data) {
+            return Err(error);
+        }
+        Ok(data["message"]["content"].as_str().map(str::to_string))
+    }
+}
@@ -0,0 +1,75 @@
+use super::{error_message, sse_data, ChatPart, ChatProvider, ChatRequest};
+use crate::services::http_client::AiClientConfig;
+use reqwest::{Client, RequestBuilder};
+use serde_json::{json, Value};
+
+/// OpenAI Chat Completions 及兼容接口，`api_url` 为完整的 chat/completions 地址
+pub struct OpenAi;
+
+impl ChatProvider for OpenAi {
+    fn request(&self, client: &Client, config: &AiClientConfig, _stream: bool) -> RequestBuilder {
+        client
+            .post(&config.api_url)
+            .header("Authorization", format!("Bearer {}", config.api_key))
+    }
+
+    fn body(&self, config: &AiClientConfig, request: &ChatRequest, stream: bool) -> Value {
+        let mut messages = Vec::new();
+        if let Some(system) = &request.system {
+            messages.push(json!({ "role": "system", "content": system }));
+        }
+        for message in &request.messages {
+            // 纯文本消息使用字符串，部分兼容接口不支持数组形式的内容
+            let content = if message.has_images() {
+                message
+                    .parts
+                    .iter()
+                    .map(|part| match part {
+                        ChatPart::Text(text) => json!({ "type": "text", "text": text }),
+                        ChatPart::Image { mime_type, data } => json!({
+                            "type": "image_url",
+                            "image_url": { "url": format!("data:{};base64,{}", mime_type, data) }
+                        }),
+                    })
+                    .collect()
+            } else {
+                Value::String(message.text())
+            };
+            messages.push(json!({ "role": "user", "content": content }));
+        }
+
+        let mut body = json!({
+            "model": config.model,
+            "messages": messages,
+            "max_tokens": request.max_tokens,
+            "stream": stream,
+        });
+        if let Some(schema) = &request.schema {
+            body["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": { "name": schema.name, "strict": true, "schema": schema.schema },
+            });
+        }
+        body
+    }
+
+    fn parse_response(&self, body: &Value) -> Result<String, String> {
+        if let Some(error) = error_message(body) {
+            return Err(error);
+        }
+        body["choices"][0]["message"]["content"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| "响应中没有模型输出".to_string())
+    }
+
+    fn parse_stream_line(&self, line: &str) -> Result<Option<String>, String> {
+        let Some(data) = sse_data(line) else {
+            return Ok(None);
+        };
+        if let Some(error) = error_message(&data) {
+            return Err(error);
+        }
+        Ok(data["choices"][0]["delta"]["content"].as_str().map(str::to_string))
+    }
+}
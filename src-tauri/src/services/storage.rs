@@ -0,0 +1,72 @@
+use crate::error::{AppError, AppResult};
+use crate::repo::file::{CheckupFile, NewCheckupFile};
+use crate::repo::{FileRepo, ProjectRepo};
+use rusqlite::Connection;
+use std::path::Path;
+
+/// 可作为检查报告导入的文件扩展名
+pub const REPORT_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "bmp", "webp", "pdf"];
+
+/// 将文件写入 pictures/<项目名>/<日期>/ 并登记到检查记录
+pub fn store_file(
+    conn: &Connection,
+    app_dir: &Path,
+    record_id: &str,
+    project_id: &str,
+    checkup_date: &str,
+    filename: &str,
+    bytes: &[u8],
+) -> AppResult<CheckupFile> {
+    let id = uuid::Uuid::new_v4().to_string();
+
+    // 获取项目名称用于目录结构
+    let project_name = ProjectRepo::new(conn).name_of(project_id)?;
+
+    // 构建存储路径: pictures/<项目名>/<日期>/<文件名>
+    let store_dir = app_dir
+        .join("pictures")
+        .join(&project_name)
+        .join(checkup_date);
+
+    std::fs::create_dir_all(&store_dir)
+        .map_err(|e| AppError::io("创建目录失败", e))?;
+
+    // 避免文件名冲突：加上UUID前缀
+    let stored_filename = format!("{}_{}", &id[..8], filename);
+    let stored_path = store_dir.join(&stored_filename);
+
+    std::fs::write(&stored_path, bytes)
+        .map_err(|e| AppError::io("文件保存失败", e))?;
+
+    // 获取相对路径
+    let relative_path = stored_path
+        .strip_prefix(app_dir)
+        .unwrap_or(&stored_path)
+        .to_string_lossy()
+        .to_string();
+
+    FileRepo::new(conn).insert(NewCheckupFile {
+        id,
+        record_id: record_id.to_string(),
+        project_id: project_id.to_string(),
+        project_name,
+        original_filename: filename.to_string(),
+        stored_path: relative_path,
+        file_size: bytes.len() as i64,
+        mime_type: guess_mime_type(filename),
+    })
+}
+
+/// 根据文件扩展名推断 MIME 类型
+pub fn guess_mime_type(filename: &str) -> String {
+    let ext = filename.rsplit('.').next().unwrap_or("").to_lowercase();
+    match ext.as_str() {
+        "jpg" | "jpeg" => "image/jpeg".to_string(),
+        "png" => "image/png".to_string(),
+        "gif" => "image/gif".to_string(),
+        "bmp" => "image/bmp".to_string(),
+        "webp" => "image/webp".to_string(),
+        "pdf" => "application/pdf".to_string(),
+        _ => "application/octet-stream".to_string(),
+    }
+}
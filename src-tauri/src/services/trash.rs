@@ -0,0 +1,49 @@
+use crate::db::Database;
+use crate::error::AppResult;
+use crate::repo::trash::{self, Purged};
+use crate::repo::{ConfigRepo, TrashRepo};
+use crate::services::preprocess;
+use std::path::Path;
+
+/// 回收站保留天数的配置项，0 表示不自动清理
+pub const RETENTION_DAYS_KEY: &str = "trash_retention_days";
+
+/// 默认保留 30 天
+pub const DEFAULT_RETENTION_DAYS: i64 = 30;
+
+/// 删除彻底删除的数据对应的物理文件及其预处理缓存，单个文件失败只记录日志
+pub fn remove_stored_files(app_dir: &Path, purged: &Purged) {
+    for stored_path in &purged.stored_paths {
+        let full_path = app_dir.join(stored_path);
+        if let Err(e) = std::fs::remove_file(&full_path)
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            log::warn!("删除文件失败 {}: {}", full_path.display(), e);
+        }
+        preprocess::remove_cache(app_dir, stored_path);
+    }
+}
+
+/// 清理超过保留天数的回收站数据（启动时执行，数据库锁定时跳过）
+pub fn auto_purge(db: &Database, app_dir: &Path) -> AppResult<usize> {
+    if db.status()?.locked {
+        return Ok(0);
+    }
+
+    let conn = db.write()?;
+    let days = ConfigRepo::new(&conn)
+        .get_or(RETENTION_DAYS_KEY, &DEFAULT_RETENTION_DAYS.to_string())?
+        .trim()
+        .parse::<i64>()
+        .unwrap_or(DEFAULT_RETENTION_DAYS);
+    if days <= 0 {
+        return Ok(0);
+    }
+
+    let cutoff = trash::deleted_at(chrono::Utc::now() - chrono::Duration::days(days));
+    let purged = TrashRepo::new(&conn).purge_all(Some(&cutoff), None)?;
+    drop(conn);
+
+    remove_stored_files(app_dir, &purged);
+    Ok(purged.count)
+}
@@ -0,0 +1,126 @@
+use rusqlite::Connection;
+use serde::Serialize;
+use crate::error::{AppResult, DbResultExt};
+use crate::repo::ProjectRepo;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TrendDataPoint {
+    pub checkup_date: String,
+    pub value: Option<f64>,
+    pub value_text: String,
+    pub is_abnormal: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct IndicatorTrend {
+    pub indicator_id: String,
+    pub indicator_name: String,
+    pub unit: String,
+    pub reference_range: String,
+    pub data_points: Vec<TrendDataPoint>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ProjectTrend {
+    pub project_id: String,
+    pub project_name: String,
+    pub indicators: Vec<IndicatorTrend>,
+}
+
+/// 基于 indicator_values 汇总指标的历史趋势
+pub struct TrendService<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> TrendService<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// 成员在某个项目下全部指标的趋势（核心指标在前）
+    pub fn project_trend(&self, patient_id: &str, project_id: &str) -> AppResult<ProjectTrend> {
+        let project_name = ProjectRepo::new(self.conn).name_of(project_id)?;
+
+        let mut ind_stmt = self
+            .conn
+            .prepare(
+                "SELECT id, name, unit, reference_range FROM indicators
+                 WHERE project_id = ?1
+                 ORDER BY is_core DESC, sort_order ASC, name ASC"
+            )
+            .db_context("查询指标失败")?;
+
+        let indicators: Vec<(String, String, String, String)> = ind_stmt
+            .query_map([project_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2).unwrap_or_default(),
+                    row.get::<_, String>(3).unwrap_or_default(),
+                ))
+            })
+            .db_context("查询指标失败")?
+            .collect::<Result<Vec<_>, _>>()
+            .db_context("解析指标数据失败")?;
+
+        let mut trend_indicators = Vec::new();
+        for (indicator_id, indicator_name, unit, reference_range) in indicators {
+            let data_points = self.data_points(patient_id, &indicator_id, project_id)?;
+            trend_indicators.push(IndicatorTrend {
+                indicator_id,
+                indicator_name,
+                unit,
+                reference_range,
+                data_points,
+            });
+        }
+
+        Ok(ProjectTrend {
+            project_id: project_id.to_string(),
+            project_name,
+            indicators: trend_indicators,
+        })
+    }
+
+    /// 成员在全部启用项目下的趋势，单个项目失败时跳过
+    pub fn all_trends(&self, patient_id: &str) -> AppResult<Vec<ProjectTrend>> {
+        let projects = ProjectRepo::new(self.conn).list_active()?;
+
+        let mut result = Vec::new();
+        for (project_id, _) in &projects {
+            match self.project_trend(patient_id, project_id) {
+                Ok(trend) => result.push(trend),
+                Err(e) => log::error!("获取项目趋势失败: {}", e),
+            }
+        }
+        Ok(result)
+    }
+
+    fn data_points(&self, patient_id: &str, indicator_id: &str, project_id: &str) -> AppResult<Vec<TrendDataPoint>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT v.checkup_date, v.value, v.value_text, v.is_abnormal
+                 FROM indicator_values v
+                 JOIN checkup_records r ON v.record_id = r.id
+                 JOIN ocr_results o ON v.ocr_result_id = o.id
+                 JOIN checkup_files f ON o.file_id = f.id
+                 WHERE v.indicator_id = ?1 AND v.project_id = ?2 AND r.patient_id = ?3
+                   AND r.deleted_at IS NULL AND f.deleted_at IS NULL
+                 ORDER BY v.checkup_date ASC"
+            )
+            .db_context("查询指标值失败")?;
+
+        stmt.query_map(rusqlite::params![indicator_id, project_id, patient_id], |row| {
+            Ok(TrendDataPoint {
+                checkup_date: row.get(0)?,
+                value: row.get(1)?,
+                value_text: row.get::<_, String>(2).unwrap_or_default(),
+                is_abnormal: row.get::<_, i32>(3).unwrap_or(0) != 0,
+            })
+        })
+        .db_context("查询失败")?
+        .collect::<Result<Vec<_>, _>>()
+        .db_context("解析数据失败")
+    }
+}